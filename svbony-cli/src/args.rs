@@ -0,0 +1,216 @@
+//! Parsers for command-line values: durations, ROIs, control assignments
+//! and SDK enum names.
+
+use std::time::Duration;
+
+use svbony::{CameraMode, ControlCaps, GuideDirection, ImageType};
+
+/// Formats accepted by `--format`.
+const FORMATS: &[ImageType] = &[
+    ImageType::Raw8,
+    ImageType::Raw10,
+    ImageType::Raw12,
+    ImageType::Raw14,
+    ImageType::Raw16,
+    ImageType::Y8,
+    ImageType::Y10,
+    ImageType::Y12,
+    ImageType::Y14,
+    ImageType::Y16,
+    ImageType::Rgb24,
+    ImageType::Rgb32,
+];
+
+/// Modes accepted by `--mode`.
+const MODES: &[CameraMode] = &[
+    CameraMode::Normal,
+    CameraMode::TrigSoft,
+    CameraMode::TrigRiseEdge,
+    CameraMode::TrigFallEdge,
+    CameraMode::TrigDoubleEdge,
+    CameraMode::TrigHighLevel,
+    CameraMode::TrigLowLevel,
+];
+
+/// A ROI given as `X,Y,WIDTH,HEIGHT` in binned pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Roi {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+/// A `NAME=VALUE` or `NAME=auto` control assignment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControlArg {
+    pub name: String,
+    /// `None` for `auto`, which keeps the current value.
+    pub value: Option<i64>,
+}
+
+/// Parses a duration with an optional `us`, `ms` or `s` suffix; plain
+/// numbers are seconds.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let (number, scale) = if let Some(n) = s.strip_suffix("us") {
+        (n, 1e-6)
+    } else if let Some(n) = s.strip_suffix("ms") {
+        (n, 1e-3)
+    } else {
+        (s.strip_suffix('s').unwrap_or(s), 1.0)
+    };
+    let value: f64 = number
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid duration {s:?} (e.g. 0.5, 500ms, 250us)"))?;
+    Duration::try_from_secs_f64(value * scale).map_err(|_| format!("invalid duration {s:?}"))
+}
+
+pub fn parse_roi(s: &str) -> Result<Roi, String> {
+    let values = s
+        .split(',')
+        .map(|v| v.trim().parse::<i32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| format!("invalid ROI {s:?}, expected X,Y,WIDTH,HEIGHT"))?;
+    match values[..] {
+        [x, y, width, height] => Ok(Roi {
+            x,
+            y,
+            width,
+            height,
+        }),
+        _ => Err(format!("invalid ROI {s:?}, expected X,Y,WIDTH,HEIGHT")),
+    }
+}
+
+pub fn parse_control(s: &str) -> Result<ControlArg, String> {
+    let (name, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("invalid control {s:?}, expected NAME=VALUE or NAME=auto"))?;
+    let value = match value.trim() {
+        v if v.eq_ignore_ascii_case("auto") => None,
+        v => Some(
+            v.parse()
+                .map_err(|_| format!("invalid value {v:?} for control {name}"))?,
+        ),
+    };
+    Ok(ControlArg {
+        name: name.trim().to_owned(),
+        value,
+    })
+}
+
+pub fn parse_format(s: &str) -> Result<ImageType, String> {
+    parse_name(s, FORMATS, "format")
+}
+
+pub fn parse_mode(s: &str) -> Result<CameraMode, String> {
+    parse_name(s, MODES, "mode")
+}
+
+pub fn parse_direction(s: &str) -> Result<GuideDirection, String> {
+    match normalize(s).as_str() {
+        "n" | "north" => Ok(GuideDirection::North),
+        "s" | "south" => Ok(GuideDirection::South),
+        "e" | "east" => Ok(GuideDirection::East),
+        "w" | "west" => Ok(GuideDirection::West),
+        _ => Err(format!(
+            "invalid direction {s:?}, expected north, south, east or west"
+        )),
+    }
+}
+
+/// Finds a control by its [`ControlType`](svbony::ControlType) name or SDK
+/// name, ignoring case, spaces, `-` and `_`.
+pub fn find_control<'a>(caps: &'a [ControlCaps], name: &str) -> Option<&'a ControlCaps> {
+    let name = normalize(name);
+    caps.iter()
+        .find(|c| normalize(&format!("{:?}", c.control_type)) == name || normalize(&c.name) == name)
+}
+
+/// Matches `s` against the `Debug` names of `values`.
+fn parse_name<T: Copy + std::fmt::Debug>(s: &str, values: &[T], what: &str) -> Result<T, String> {
+    let wanted = normalize(s);
+    values
+        .iter()
+        .copied()
+        .find(|v| normalize(&format!("{v:?}")) == wanted)
+        .ok_or_else(|| {
+            let names: Vec<_> = values.iter().map(|v| format!("{v:?}")).collect();
+            format!("unknown {what} {s:?}, expected one of {}", names.join(", "))
+        })
+}
+
+fn normalize(s: &str) -> String {
+    s.chars()
+        .filter(|c| !matches!(c, ' ' | '-' | '_'))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use svbony::ControlType;
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("1.5"), Ok(Duration::from_millis(1500)));
+        assert_eq!(parse_duration("2s"), Ok(Duration::from_secs(2)));
+        assert_eq!(parse_duration("20ms"), Ok(Duration::from_millis(20)));
+        assert_eq!(parse_duration("250us"), Ok(Duration::from_micros(250)));
+        assert!(parse_duration("-1").is_err());
+        assert!(parse_duration("fast").is_err());
+    }
+
+    #[test]
+    fn rois_and_controls() {
+        assert_eq!(
+            parse_roi("8, 16,640,480"),
+            Ok(Roi {
+                x: 8,
+                y: 16,
+                width: 640,
+                height: 480
+            })
+        );
+        assert!(parse_roi("1,2,3").is_err());
+        assert!(parse_roi("a,b,c,d").is_err());
+
+        assert_eq!(
+            parse_control("gain=120"),
+            Ok(ControlArg {
+                name: "gain".into(),
+                value: Some(120),
+            })
+        );
+        assert_eq!(parse_control("Exposure=AUTO").unwrap().value, None);
+        assert!(parse_control("gain").is_err());
+        assert!(parse_control("gain=high").is_err());
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(parse_format("raw16"), Ok(ImageType::Raw16));
+        assert_eq!(parse_format("RGB24"), Ok(ImageType::Rgb24));
+        assert!(parse_format("jpeg").unwrap_err().contains("Raw8"));
+        assert_eq!(parse_mode("trig-soft"), Ok(CameraMode::TrigSoft));
+        assert_eq!(parse_direction("W"), Ok(GuideDirection::West));
+        assert!(parse_direction("up").is_err());
+
+        let caps = [ControlCaps {
+            name: "Black Level".into(),
+            description: String::new(),
+            max_value: 255,
+            min_value: 0,
+            default_value: 0,
+            is_auto_supported: false,
+            is_writable: true,
+            control_type: ControlType::BlackLevel,
+        }];
+        assert!(find_control(&caps, "black_level").is_some());
+        assert!(find_control(&caps, "BlackLevel").is_some());
+        assert!(find_control(&caps, "gain").is_none());
+    }
+}
@@ -0,0 +1,243 @@
+//! The `capture` command: grabs frames in video mode and writes them as PNG,
+//! FITS or SER.
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use clap::Args;
+use serde_json::{json, Value};
+use svbony::fits;
+use svbony::{Camera, CameraInfo, CameraMode, ControlType, Error, Frame, ImageType};
+
+use crate::args::{parse_duration, parse_format};
+use crate::ser::SerWriter;
+use crate::{apply_roi, CliError, RoiArgs};
+
+/// Consecutive frame timeouts tolerated before giving up.
+const MAX_TIMEOUTS: u32 = 3;
+
+#[derive(Debug, Args)]
+pub struct CaptureArgs {
+    /// Output file: `.png`, `.fits` (`.fit`, `.fts`) or `.ser`. When
+    /// capturing several PNG or FITS frames, a frame number is added to the
+    /// file name.
+    #[arg(short, long)]
+    output: PathBuf,
+    /// Number of frames.
+    #[arg(short = 'n', long, default_value_t = 1)]
+    count: usize,
+    /// Exposure time, e.g. `2`, `500ms` or `250us`.
+    #[arg(short, long, value_parser = parse_duration)]
+    exposure: Option<Duration>,
+    /// Gain in SDK units.
+    #[arg(short, long)]
+    gain: Option<i64>,
+    #[command(flatten)]
+    roi: RoiArgs,
+    /// Output format, e.g. `raw16` or `rgb24`.
+    #[arg(short, long, value_parser = parse_format)]
+    format: Option<ImageType>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Container {
+    Png,
+    Fits,
+    Ser,
+}
+
+impl Container {
+    fn from_path(path: &Path) -> Result<Self, CliError> {
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_ascii_lowercase);
+        match ext.as_deref() {
+            Some("png") => Ok(Self::Png),
+            Some("fits" | "fit" | "fts") => Ok(Self::Fits),
+            Some("ser") => Ok(Self::Ser),
+            _ => Err(CliError::Usage(format!(
+                "cannot tell the file type of {}; use .png, .fits or .ser",
+                path.display()
+            ))),
+        }
+    }
+}
+
+/// Captures the frames and returns one report per frame.
+pub fn run(cam: &Camera, info: &CameraInfo, args: &CaptureArgs) -> Result<Vec<Value>, CliError> {
+    let container = Container::from_path(&args.output)?;
+    if args.count == 0 {
+        return Err(CliError::Usage("--count must be at least 1".into()));
+    }
+    if let Some(exposure) = args.exposure {
+        cam.set_exposure(exposure)?;
+    }
+    if let Some(gain) = args.gain {
+        cam.set_control(ControlType::Gain, gain, false)?;
+    }
+    apply_roi(cam, &args.roi)?;
+    if let Some(format) = args.format {
+        cam.set_output_image_type(format)?;
+    }
+
+    let mode = cam.mode()?;
+    if mode != CameraMode::Normal {
+        cam.set_mode(CameraMode::Normal)?;
+    }
+    cam.start_capture()?;
+    let result = capture(cam, info, args, container);
+    let _ = cam.stop_capture();
+    if mode != CameraMode::Normal {
+        let _ = cam.set_mode(mode);
+    }
+    result
+}
+
+fn capture(
+    cam: &Camera,
+    info: &CameraInfo,
+    args: &CaptureArgs,
+    container: Container,
+) -> Result<Vec<Value>, CliError> {
+    let exposure = cam.exposure()?;
+    let wait_ms = (exposure.as_millis() * 2 + 1000).min(i32::MAX as u128) as i32;
+    let bayer = cam.property()?.bayer_pattern;
+    let gain = cam.get_control(ControlType::Gain).ok().map(|(g, _)| g);
+    let pixel_size = cam.pixel_size().ok();
+
+    let mut ser = None;
+    let mut reports = Vec::with_capacity(args.count);
+    let mut timeouts = 0;
+    while reports.len() < args.count {
+        let frame = match cam.capture_frame(wait_ms) {
+            Ok(frame) => frame,
+            Err(Error::Timeout) if timeouts < MAX_TIMEOUTS => {
+                timeouts += 1;
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        timeouts = 0;
+        let received = SystemTime::now();
+        let started = received.checked_sub(exposure).unwrap_or(received);
+        let index = reports.len();
+
+        let path = match container {
+            Container::Ser => args.output.clone(),
+            _ => numbered(&args.output, index, args.count),
+        };
+        let report = report(&frame, &path, index, started);
+        match container {
+            Container::Png => frame
+                .into_image()
+                .save_with_format(&path, image::ImageFormat::Png)?,
+            Container::Fits => {
+                let cards = fits::Observation {
+                    instrument: &info.name,
+                    exposure,
+                    started,
+                    pixel_size_um: pixel_size,
+                    gain,
+                    temperature: None,
+                    bayer_pattern: bayer,
+                }
+                .cards(&frame);
+                let mut out = BufWriter::new(create(&path)?);
+                fits::write_frame(&mut out, &frame, &cards).map_err(|e| file_error(&path, e))?;
+            }
+            Container::Ser => {
+                if ser.is_none() {
+                    let out = BufWriter::new(create(&path)?);
+                    let writer = SerWriter::new(out, &frame, bayer, &info.name, started)
+                        .map_err(|e| file_error(&path, e))?;
+                    ser = Some(writer);
+                }
+                if let Some(writer) = ser.as_mut() {
+                    writer
+                        .write(&frame, started)
+                        .map_err(|e| file_error(&path, e))?;
+                }
+            }
+        }
+        reports.push(report);
+    }
+    if let Some(writer) = ser {
+        writer.finish().map_err(|e| file_error(&args.output, e))?;
+    }
+    Ok(reports)
+}
+
+fn report(frame: &Frame, path: &Path, index: usize, started: SystemTime) -> Value {
+    let stats = frame.stats();
+    json!({
+        "frame": index,
+        "file": path,
+        "width": frame.width(),
+        "height": frame.height(),
+        "format": frame.image_type,
+        "started": fits::timestamp(started),
+        "mean": stats.mean,
+        "min": stats.min,
+        "max": stats.max,
+    })
+}
+
+/// `path` with a frame number before the extension when capturing several
+/// frames, e.g. `m42_0003.fits`.
+fn numbered(path: &Path, index: usize, count: usize) -> PathBuf {
+    if count == 1 {
+        return path.to_path_buf();
+    }
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let mut name = format!("{stem}_{index:04}");
+    if let Some(ext) = path.extension() {
+        name.push('.');
+        name.push_str(&ext.to_string_lossy());
+    }
+    path.with_file_name(name)
+}
+
+fn create(path: &Path) -> Result<File, CliError> {
+    File::create(path).map_err(|e| file_error(path, e))
+}
+
+fn file_error(path: &Path, source: std::io::Error) -> CliError {
+    CliError::File {
+        path: path.to_path_buf(),
+        source,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn containers() {
+        assert_eq!(
+            Container::from_path(Path::new("a.PNG")).unwrap(),
+            Container::Png
+        );
+        assert_eq!(
+            Container::from_path(Path::new("a.fts")).unwrap(),
+            Container::Fits
+        );
+        assert_eq!(
+            Container::from_path(Path::new("dir/a.ser")).unwrap(),
+            Container::Ser
+        );
+        assert!(Container::from_path(Path::new("a.jpg")).is_err());
+        assert!(Container::from_path(Path::new("a")).is_err());
+    }
+
+    #[test]
+    fn numbered_names() {
+        let path = Path::new("out/m42.fits");
+        assert_eq!(numbered(path, 3, 1), path);
+        assert_eq!(numbered(path, 3, 10), Path::new("out/m42_0003.fits"));
+        assert_eq!(numbered(Path::new("frame"), 0, 2), Path::new("frame_0000"));
+    }
+}
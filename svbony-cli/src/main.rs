@@ -0,0 +1,432 @@
+//! `svbony`: command-line access to SVBony cameras.
+//!
+//! Lists cameras, shows their properties and controls, changes settings,
+//! captures frames to PNG, FITS or SER and sends ST4 guide pulses. Every
+//! command prints JSON instead of text with `--json`.
+
+mod args;
+mod capture;
+mod ser;
+
+use std::io;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
+
+use clap::{Args, Parser, Subcommand};
+use serde_json::{json, Value};
+use svbony::guide::{GuidePort, St4Port};
+use svbony::{Camera, CameraInfo, CameraMode, GuideDirection, ImageType, RoiFormat};
+
+use crate::args::{
+    find_control, parse_control, parse_direction, parse_duration, parse_format, parse_mode,
+    parse_roi, ControlArg, Roi,
+};
+
+#[derive(Debug, Parser)]
+#[command(name = "svbony", version, about = "Control SVBony cameras")]
+struct Cli {
+    /// Camera to use, as its index in `svbony list`.
+    #[arg(short, long, global = true, default_value_t = 0)]
+    camera: usize,
+    /// Print JSON instead of text.
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// List connected cameras.
+    List,
+    /// Show the camera's properties, firmware and serial number.
+    Info,
+    /// Show every control with its range and current value.
+    ///
+    /// Values are in SDK units: exposure in µs, temperature in 0.1 °C.
+    Controls,
+    /// Change controls, ROI, output format or camera mode.
+    Set(SetArgs),
+    /// Capture frames to PNG, FITS or SER.
+    Capture(capture::CaptureArgs),
+    /// Send a guide pulse on the ST4 port.
+    Guide {
+        /// north, south, east or west.
+        #[arg(value_parser = parse_direction)]
+        direction: GuideDirection,
+        /// Pulse length, e.g. `500ms`.
+        #[arg(value_parser = parse_duration)]
+        duration: Duration,
+    },
+}
+
+#[derive(Debug, Args)]
+struct SetArgs {
+    /// `NAME=VALUE` or `NAME=auto`, e.g. `gain=120`; may be repeated.
+    #[arg(long = "control", value_name = "NAME=VALUE", value_parser = parse_control)]
+    controls: Vec<ControlArg>,
+    #[command(flatten)]
+    roi: RoiArgs,
+    /// Output format, e.g. `raw16` or `rgb24`.
+    #[arg(short, long, value_parser = parse_format)]
+    format: Option<ImageType>,
+    /// Camera mode, e.g. `normal` or `trig-soft`.
+    #[arg(short, long, value_parser = parse_mode)]
+    mode: Option<CameraMode>,
+    /// Keep the settings in the camera across power cycles.
+    #[arg(long)]
+    save: bool,
+}
+
+#[derive(Debug, Args)]
+struct RoiArgs {
+    /// Region of interest as `X,Y,WIDTH,HEIGHT` in binned pixels.
+    #[arg(long, value_name = "X,Y,W,H", value_parser = parse_roi)]
+    roi: Option<Roi>,
+    /// Binning factor.
+    #[arg(long)]
+    bin: Option<i32>,
+}
+
+#[derive(Debug, thiserror::Error)]
+enum CliError {
+    #[error(transparent)]
+    Camera(#[from] svbony::Error),
+    #[error("{path}: {source}")]
+    File { path: PathBuf, source: io::Error },
+    #[error(transparent)]
+    Image(#[from] image::ImageError),
+    #[error("{0}")]
+    Usage(String),
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(&cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("svbony: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(cli: &Cli) -> Result<(), CliError> {
+    match &cli.command {
+        Command::List => list(cli.json),
+        Command::Info => {
+            let (cam, info) = open(cli.camera)?;
+            let value = describe(&cam, &info);
+            if cli.json {
+                print_json(&value);
+            } else {
+                print_fields(&value, "");
+            }
+            Ok(())
+        }
+        Command::Controls => {
+            let (cam, _) = open(cli.camera)?;
+            let controls = controls(&cam);
+            if cli.json {
+                print_json(&Value::Array(controls));
+            } else {
+                for c in &controls {
+                    println!(
+                        "{:<20} {:>8} {:<5} [{} .. {}, default {}]{}",
+                        c["name"].as_str().unwrap_or_default(),
+                        c["value"],
+                        if c["auto"] == true { "auto" } else { "" },
+                        c["min"],
+                        c["max"],
+                        c["default"],
+                        if c["writable"] == true {
+                            ""
+                        } else {
+                            " read-only"
+                        },
+                    );
+                }
+            }
+            Ok(())
+        }
+        Command::Set(args) => {
+            let (cam, _) = open(cli.camera)?;
+            set(&cam, args)?;
+            let state = json!({
+                "roi": cam.roi()?,
+                "format": cam.output_image_type()?,
+                "mode": cam.mode()?,
+                "controls": controls(&cam),
+            });
+            if cli.json {
+                print_json(&state);
+            } else {
+                let roi = cam.roi()?;
+                println!(
+                    "roi: {}x{} at {},{} bin {}",
+                    roi.width, roi.height, roi.start_x, roi.start_y, roi.bin
+                );
+                println!("format: {:?}", cam.output_image_type()?);
+                println!("mode: {:?}", cam.mode()?);
+                for c in &args.controls {
+                    if let Some(caps) = find_control(cam.supported_controls(), &c.name) {
+                        let (value, auto) = cam.get_control(caps.control_type)?;
+                        println!(
+                            "{}: {value}{}",
+                            caps.name,
+                            if auto { " (auto)" } else { "" }
+                        );
+                    }
+                }
+            }
+            Ok(())
+        }
+        Command::Capture(args) => {
+            let (cam, info) = open(cli.camera)?;
+            let reports = capture::run(&cam, &info, args)?;
+            if cli.json {
+                print_json(&Value::Array(reports));
+            } else {
+                for r in &reports {
+                    println!(
+                        "{}: {}x{} {}, mean {:.1}, min {}, max {}",
+                        r["file"].as_str().unwrap_or_default(),
+                        r["width"],
+                        r["height"],
+                        r["format"].as_str().unwrap_or_default(),
+                        r["mean"].as_f64().unwrap_or_default(),
+                        r["min"],
+                        r["max"],
+                    );
+                }
+            }
+            Ok(())
+        }
+        Command::Guide {
+            direction,
+            duration,
+        } => {
+            let (cam, _) = open(cli.camera)?;
+            St4Port::new(Arc::new(cam))?.pulse(*direction, *duration)?;
+            if cli.json {
+                print_json(&json!({
+                    "direction": direction,
+                    "duration_ms": duration.as_millis() as u64,
+                }));
+            } else {
+                println!("pulsed {direction:?} for {duration:?}");
+            }
+            Ok(())
+        }
+    }
+}
+
+fn list(as_json: bool) -> Result<(), CliError> {
+    let cameras = svbony::connected_cameras()?;
+    if as_json {
+        print_json(&Value::Array(cameras.iter().map(camera_info).collect()));
+    } else if cameras.is_empty() {
+        println!("no cameras connected");
+    } else {
+        for (index, info) in cameras.iter().enumerate() {
+            println!(
+                "{index}: {} (serial {}, {})",
+                info.name, info.serial, info.port_type
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Opens the `index`th connected camera.
+fn open(index: usize) -> Result<(Camera, CameraInfo), CliError> {
+    let cameras = svbony::connected_cameras()?;
+    let count = cameras.len();
+    let info = cameras.into_iter().nth(index).ok_or_else(|| {
+        CliError::Usage(match count {
+            0 => "no cameras connected".to_owned(),
+            n => format!("no camera {index}; {n} connected"),
+        })
+    })?;
+    Ok((Camera::open(info.camera_id)?, info))
+}
+
+fn camera_info(info: &CameraInfo) -> Value {
+    json!({
+        "name": info.name,
+        "serial": info.serial,
+        "port_type": info.port_type,
+        "device_id": info.device_id,
+        "camera_id": info.camera_id,
+    })
+}
+
+/// Everything `info` shows. Queries the camera rejects are `null`.
+fn describe(cam: &Camera, info: &CameraInfo) -> Value {
+    let upgrade = cam.needs_upgrade().ok();
+    json!({
+        "camera": camera_info(info),
+        "property": cam.property().ok(),
+        "property_ex": cam.property_ex().ok(),
+        "firmware_version": cam.firmware_version().ok(),
+        "pixel_size_um": cam.pixel_size().ok(),
+        "needs_upgrade": upgrade.as_ref().map(|(need, _)| need),
+        "minimum_firmware": upgrade.map(|(_, version)| version),
+        "sdk_version": svbony::sdk_version(),
+    })
+}
+
+/// Every supported control with its caps and current value.
+fn controls(cam: &Camera) -> Vec<Value> {
+    cam.supported_controls()
+        .iter()
+        .map(|caps| {
+            let current = cam.get_control(caps.control_type).ok();
+            json!({
+                "name": caps.name,
+                "type": caps.control_type,
+                "value": current.map(|(value, _)| value),
+                "auto": current.map(|(_, auto)| auto),
+                "min": caps.min_value,
+                "max": caps.max_value,
+                "default": caps.default_value,
+                "writable": caps.is_writable,
+                "auto_supported": caps.is_auto_supported,
+                "description": caps.description,
+            })
+        })
+        .collect()
+}
+
+fn set(cam: &Camera, args: &SetArgs) -> Result<(), CliError> {
+    if args.controls.is_empty()
+        && args.roi.roi.is_none()
+        && args.roi.bin.is_none()
+        && args.format.is_none()
+        && args.mode.is_none()
+        && !args.save
+    {
+        return Err(CliError::Usage(
+            "nothing to set; see `svbony set --help`".into(),
+        ));
+    }
+    for c in &args.controls {
+        let caps = find_control(cam.supported_controls(), &c.name)
+            .ok_or_else(|| CliError::Usage(format!("unknown control {:?}", c.name)))?;
+        match c.value {
+            Some(value) => cam.set_control(caps.control_type, value, false)?,
+            None => {
+                let (value, _) = cam.get_control(caps.control_type)?;
+                cam.set_control(caps.control_type, value, true)?;
+            }
+        }
+    }
+    apply_roi(cam, &args.roi)?;
+    if let Some(format) = args.format {
+        cam.set_output_image_type(format)?;
+    }
+    if let Some(mode) = args.mode {
+        cam.set_mode(mode)?;
+    }
+    if args.save {
+        cam.set_auto_save(true)?;
+    }
+    Ok(())
+}
+
+/// Applies `--roi` and `--bin`. A bin on its own selects the full frame;
+/// a ROI on its own keeps the current bin.
+fn apply_roi(cam: &Camera, args: &RoiArgs) -> Result<(), CliError> {
+    if args.roi.is_none() && args.bin.is_none() {
+        return Ok(());
+    }
+    let prop = cam.property()?;
+    let bin = match args.bin {
+        Some(bin) => bin,
+        None => cam.roi()?.bin,
+    };
+    let builder = RoiFormat::builder(&prop).bin(bin);
+    let roi = match args.roi {
+        Some(r) => builder.at(r.x, r.y, r.width, r.height),
+        None => builder.full_frame(),
+    }
+    .build()?;
+    cam.set_roi(&roi)?;
+    Ok(())
+}
+
+fn print_json(value: &Value) {
+    println!(
+        "{}",
+        serde_json::to_string_pretty(value).expect("JSON values always serialize")
+    );
+}
+
+/// Prints a JSON object as indented `key: value` lines.
+fn print_fields(value: &Value, indent: &str) {
+    let Some(map) = value.as_object() else {
+        return;
+    };
+    for (key, value) in map {
+        match value {
+            Value::Object(_) => {
+                println!("{indent}{key}:");
+                print_fields(value, &format!("{indent}  "));
+            }
+            Value::String(s) => println!("{indent}{key}: {s}"),
+            Value::Array(items) => {
+                let items: Vec<_> = items
+                    .iter()
+                    .map(|v| match v {
+                        Value::String(s) => s.clone(),
+                        v => v.to_string(),
+                    })
+                    .collect();
+                println!("{indent}{key}: {}", items.join(", "));
+            }
+            v => println!("{indent}{key}: {v}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn cli_is_consistent() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn parses_commands() {
+        let cli = Cli::try_parse_from([
+            "svbony",
+            "set",
+            "--control",
+            "gain=100",
+            "--control",
+            "exposure=auto",
+            "--roi",
+            "0,0,640,480",
+            "--bin",
+            "2",
+            "-c",
+            "1",
+        ])
+        .unwrap();
+        assert_eq!(cli.camera, 1);
+        let Command::Set(args) = cli.command else {
+            panic!("expected set");
+        };
+        assert_eq!(args.controls.len(), 2);
+        assert_eq!(args.roi.bin, Some(2));
+
+        assert!(Cli::try_parse_from(["svbony", "--json", "guide", "north", "250ms"]).is_ok());
+        assert!(Cli::try_parse_from(["svbony", "guide", "up", "1"]).is_err());
+        assert!(Cli::try_parse_from(["svbony", "capture"]).is_err());
+    }
+}
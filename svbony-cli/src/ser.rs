@@ -0,0 +1,259 @@
+//! Writer for SER video files, the uncompressed frame sequence format read
+//! by planetary stacking software.
+//!
+//! A file is a 178-byte header, the frames back to back and a trailer with
+//! one UTC timestamp per frame. The frame count in the header is filled in
+//! by [`SerWriter::finish`].
+
+use std::io::{self, Seek, SeekFrom, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use svbony::{BayerPattern, Frame};
+
+const FILE_ID: &[u8; 14] = b"LUCAM-RECORDER";
+/// Offset of the frame count in the header.
+const FRAME_COUNT_OFFSET: u64 = 38;
+/// Length of the observer, instrument and telescope fields.
+const TEXT_FIELD: usize = 40;
+
+/// `ColorID` values.
+const MONO: i32 = 0;
+const BAYER_RGGB: i32 = 8;
+const BAYER_GRBG: i32 = 9;
+const BAYER_GBRG: i32 = 10;
+const BAYER_BGGR: i32 = 11;
+const BGR: i32 = 101;
+
+/// .NET ticks (100 ns since 0001-01-01) at the Unix epoch.
+const UNIX_EPOCH_TICKS: i64 = 621_355_968_000_000_000;
+
+/// Writes frames of one size and format to a SER file.
+pub struct SerWriter<W: Write + Seek> {
+    out: W,
+    width: usize,
+    height: usize,
+    image_type: svbony::ImageType,
+    timestamps: Vec<i64>,
+}
+
+impl<W: Write + Seek> SerWriter<W> {
+    /// Writes the header for frames shaped like `first`. Raw frames from a
+    /// colour sensor are tagged with `bayer` so stacking software can
+    /// debayer them.
+    pub fn new(
+        mut out: W,
+        first: &Frame,
+        bayer: Option<BayerPattern>,
+        instrument: &str,
+        started: SystemTime,
+    ) -> io::Result<Self> {
+        let color = match (first.channels(), first.image_type.is_raw(), bayer) {
+            (1, true, Some(BayerPattern::Rg)) => BAYER_RGGB,
+            (1, true, Some(BayerPattern::Gr)) => BAYER_GRBG,
+            (1, true, Some(BayerPattern::Gb)) => BAYER_GBRG,
+            (1, true, Some(BayerPattern::Bg)) => BAYER_BGGR,
+            (1, _, _) => MONO,
+            // The SDK delivers colour as BGR(A).
+            _ => BGR,
+        };
+        let depth = if is_16bit(first) { 16 } else { 8 };
+        let ticks = ticks(started);
+
+        out.write_all(FILE_ID)?;
+        for field in [
+            0, // LuID
+            color,
+            // Little-endian 16-bit data; most readers expect 0 here despite
+            // the field's name.
+            0,
+            first.width() as i32,
+            first.height() as i32,
+            depth,
+            0, // frame count, set by finish()
+        ] {
+            out.write_all(&field.to_le_bytes())?;
+        }
+        out.write_all(&text_field(""))?; // observer
+        out.write_all(&text_field(instrument))?;
+        out.write_all(&text_field(""))?; // telescope
+
+        // Local time is not known without a time zone database; both fields
+        // hold UTC.
+        out.write_all(&ticks.to_le_bytes())?;
+        out.write_all(&ticks.to_le_bytes())?;
+
+        Ok(Self {
+            out,
+            width: first.width(),
+            height: first.height(),
+            image_type: first.image_type,
+            timestamps: Vec::new(),
+        })
+    }
+
+    /// Appends `frame`, captured at `time`.
+    pub fn write(&mut self, frame: &Frame, time: SystemTime) -> io::Result<()> {
+        if (frame.width(), frame.height(), frame.image_type)
+            != (self.width, self.height, self.image_type)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "frame is {}x{} {:?}, the SER file holds {}x{} {:?}",
+                    frame.width(),
+                    frame.height(),
+                    frame.image_type,
+                    self.width,
+                    self.height,
+                    self.image_type
+                ),
+            ));
+        }
+        if frame.channels() == 4 {
+            // Drop the alpha channel of BGRA.
+            let bgr: Vec<u8> = frame
+                .data
+                .chunks_exact(4)
+                .flat_map(|p| [p[0], p[1], p[2]])
+                .collect();
+            self.out.write_all(&bgr)?;
+        } else {
+            self.out.write_all(&frame.data)?;
+        }
+        self.timestamps.push(ticks(time));
+        Ok(())
+    }
+
+    /// Writes the timestamp trailer and the frame count, and returns the
+    /// output.
+    pub fn finish(mut self) -> io::Result<W> {
+        for t in &self.timestamps {
+            self.out.write_all(&t.to_le_bytes())?;
+        }
+        let end = self.out.stream_position()?;
+        self.out.seek(SeekFrom::Start(FRAME_COUNT_OFFSET))?;
+        self.out
+            .write_all(&(self.timestamps.len() as i32).to_le_bytes())?;
+        self.out.seek(SeekFrom::Start(end))?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+fn is_16bit(frame: &Frame) -> bool {
+    frame.image_type.bytes_per_pixel() / frame.channels() == 2
+}
+
+fn ticks(t: SystemTime) -> i64 {
+    let since = t.duration_since(UNIX_EPOCH).unwrap_or_default();
+    UNIX_EPOCH_TICKS + (since.as_nanos() / 100) as i64
+}
+
+/// A space-padded header text field.
+fn text_field(s: &str) -> [u8; TEXT_FIELD] {
+    let mut field = [b' '; TEXT_FIELD];
+    let bytes = s.as_bytes();
+    let n = bytes.len().min(TEXT_FIELD);
+    field[..n].copy_from_slice(&bytes[..n]);
+    field
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::time::Duration;
+    use svbony::{ImageType, RoiFormat};
+
+    fn frame(image_type: ImageType, fill: u8) -> Frame {
+        let roi = RoiFormat {
+            start_x: 0,
+            start_y: 0,
+            width: 8,
+            height: 2,
+            bin: 1,
+        };
+        let len = Frame::buffer_size(&roi, image_type);
+        Frame::new(roi, image_type, vec![fill; len]).unwrap()
+    }
+
+    fn field(bytes: &[u8], offset: usize) -> i32 {
+        i32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn header_frames_and_trailer() {
+        let t0 = UNIX_EPOCH + Duration::from_secs(1);
+        let first = frame(ImageType::Raw16, 1);
+        let mut ser = SerWriter::new(
+            Cursor::new(Vec::new()),
+            &first,
+            Some(BayerPattern::Gb),
+            "SV305",
+            t0,
+        )
+        .unwrap();
+        ser.write(&first, t0).unwrap();
+        ser.write(&frame(ImageType::Raw16, 2), t0 + Duration::from_millis(10))
+            .unwrap();
+        assert!(ser.write(&frame(ImageType::Raw8, 0), t0).is_err());
+        let bytes = ser.finish().unwrap().into_inner();
+
+        assert_eq!(&bytes[..14], b"LUCAM-RECORDER");
+        assert_eq!(field(&bytes, 18), BAYER_GBRG);
+        assert_eq!(field(&bytes, 26), 8);
+        assert_eq!(field(&bytes, 30), 2);
+        assert_eq!(field(&bytes, 34), 16);
+        assert_eq!(field(&bytes, 38), 2);
+        assert_eq!(&bytes[82..87], b"SV305");
+        assert_eq!(bytes[87], b' ');
+        let ticks0 = UNIX_EPOCH_TICKS + 10_000_000;
+        assert_eq!(
+            i64::from_le_bytes(bytes[170..178].try_into().unwrap()),
+            ticks0
+        );
+
+        let frame_len = 8 * 2 * 2;
+        assert_eq!(bytes.len(), 178 + 2 * frame_len + 2 * 8);
+        assert!(bytes[178..178 + frame_len].iter().all(|&b| b == 1));
+        assert!(bytes[178 + frame_len..178 + 2 * frame_len]
+            .iter()
+            .all(|&b| b == 2));
+        let trailer = 178 + 2 * frame_len;
+        let t1 = i64::from_le_bytes(bytes[trailer + 8..trailer + 16].try_into().unwrap());
+        assert_eq!(t1, ticks0 + 100_000);
+    }
+
+    #[test]
+    fn colour_is_bgr_without_alpha() {
+        let first = frame(ImageType::Rgb32, 7);
+        let mut ser =
+            SerWriter::new(Cursor::new(Vec::new()), &first, None, "", UNIX_EPOCH).unwrap();
+        ser.write(&first, UNIX_EPOCH).unwrap();
+        let bytes = ser.finish().unwrap().into_inner();
+        assert_eq!(field(&bytes, 18), BGR);
+        assert_eq!(field(&bytes, 34), 8);
+        assert_eq!(bytes.len(), 178 + 8 * 2 * 3 + 8);
+
+        let mono = frame(ImageType::Raw8, 0);
+        let ser = SerWriter::new(
+            Cursor::new(Vec::new()),
+            &mono,
+            Some(BayerPattern::Rg),
+            "",
+            UNIX_EPOCH,
+        )
+        .unwrap();
+        assert_eq!(field(&ser.finish().unwrap().into_inner(), 18), BAYER_RGGB);
+        let y8 = frame(ImageType::Y8, 0);
+        let ser = SerWriter::new(
+            Cursor::new(Vec::new()),
+            &y8,
+            Some(BayerPattern::Rg),
+            "",
+            UNIX_EPOCH,
+        )
+        .unwrap();
+        assert_eq!(field(&ser.finish().unwrap().into_inner(), 18), MONO);
+    }
+}
@@ -0,0 +1,395 @@
+//! ASCOM Alpaca camera server (requires the `alpaca` feature).
+//!
+//! [`AlpacaServer`] exposes a camera as Alpaca device `camera/0`, so
+//! N.I.N.A., SharpCap and other Alpaca clients can drive it over the
+//! network. It implements `ICameraV3` over HTTP/JSON:
+//!
+//! - exposure with `StartExposure` / `AbortExposure`, `CameraState`,
+//!   `PercentCompleted`, `ImageReady` and `ImageArray`, sent as JSON or, to
+//!   clients that accept it, as `application/imagebytes`
+//! - subframes with `BinX` / `BinY`, `NumX` / `NumY` and `StartX` /
+//!   `StartY`, in binned pixels and applied when the next exposure starts;
+//!   sizes are rounded down to the SDK's alignment (see [`RoiBuilder`])
+//! - `Gain` and `Offset` from the camera's controls, and one `ReadoutMode`
+//!   per supported [`ImageType`]
+//! - `CCDTemperature`, `SetCCDTemperature`, `CoolerOn` and `CoolerPower` on
+//!   cooled cameras
+//! - `PulseGuide` on cameras with an ST4 port, driven by a
+//!   [`Guider`](crate::guide::Guider)
+//! - `SensorType` and `BayerOffsetX` / `Y` from the camera's
+//!   [`BayerPattern`](crate::BayerPattern) for raw readout modes
+//!
+//! The management API (`/management/...`) lists the camera, and the server
+//! answers Alpaca discovery broadcasts on UDP port [`DISCOVERY_PORT`] so
+//! clients find it without configuration.
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use svbony::alpaca::{AlpacaConfig, AlpacaServer, DEFAULT_PORT};
+//! use svbony::Camera;
+//!
+//! let cam = Arc::new(Camera::open(0)?);
+//! let server = AlpacaServer::bind(("0.0.0.0", DEFAULT_PORT), cam, AlpacaConfig::default())?;
+//! println!("serving {} on {}", server.device_name(), server.local_addr()?);
+//! server.run()?;
+//! # Ok::<(), svbony::alpaca::AlpacaError>(())
+//! ```
+//!
+//! [`RoiBuilder`]: crate::RoiBuilder
+//! [`ImageType`]: crate::ImageType
+
+mod camera;
+mod http;
+
+use std::io::{self, BufReader, ErrorKind};
+use std::net::{Ipv4Addr, Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+use crate::server::{self, ServerThread};
+use crate::{Camera, Error};
+use camera::{Answer, Device, Fault};
+use http::{Request, Response};
+
+/// Port used by most Alpaca servers; any port works with discovery.
+pub const DEFAULT_PORT: u16 = 11111;
+
+/// The Alpaca discovery port.
+pub const DISCOVERY_PORT: u16 = 32227;
+
+/// Discovery requests start with this.
+const DISCOVERY_MESSAGE: &[u8] = b"alpacadiscovery1";
+
+/// Interval at which the discovery thread checks for shutdown.
+const DISCOVERY_POLL: Duration = Duration::from_millis(250);
+
+/// Server settings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlpacaConfig {
+    /// Device name; defaults to the camera's model name.
+    pub device_name: Option<String>,
+    /// UDP port to answer discovery on, or `None` to disable discovery.
+    pub discovery_port: Option<u16>,
+    /// `Location` in the management description, e.g. an observatory name.
+    pub location: String,
+    /// Writes to a client taking longer than this drop the client.
+    pub write_timeout: Duration,
+}
+
+impl Default for AlpacaConfig {
+    fn default() -> Self {
+        Self {
+            device_name: None,
+            discovery_port: Some(DISCOVERY_PORT),
+            location: String::new(),
+            write_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Errors from setting up or running an Alpaca server.
+#[derive(Debug, thiserror::Error)]
+pub enum AlpacaError {
+    /// Reading the camera's properties failed.
+    #[error(transparent)]
+    Camera(#[from] Error),
+    /// Binding the HTTP or discovery socket failed.
+    #[error("Alpaca server: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// State shared by the accept loop, connection threads and discovery.
+struct Shared {
+    device: Arc<Device>,
+    /// Open connections, shut down when the server stops.
+    connections: Mutex<Vec<(u64, TcpStream)>>,
+    shutdown: Arc<AtomicBool>,
+    next_id: AtomicU64,
+    server_tx: AtomicU32,
+    config: AlpacaConfig,
+    port: u16,
+}
+
+impl Shared {
+    /// Answers requests on one connection until it closes.
+    fn serve(&self, id: u64, stream: TcpStream) {
+        let mut out = match stream.try_clone() {
+            Ok(out) => out,
+            Err(_) => return,
+        };
+        let mut reader = BufReader::new(stream);
+        loop {
+            let (response, keep_alive) = match Request::read(&mut reader) {
+                Ok(Some(req)) => (self.route(&req), req.keep_alive),
+                Ok(None) => break,
+                Err(e) if e.kind() == ErrorKind::InvalidData => {
+                    (Response::error(400, &e.to_string()), false)
+                }
+                Err(_) => break,
+            };
+            if response.write_to(&mut out, keep_alive).is_err() || !keep_alive {
+                break;
+            }
+        }
+        self.connections.lock().unwrap().retain(|(c, _)| *c != id);
+    }
+
+    fn route(&self, req: &Request) -> Response {
+        let path = req.path.trim_end_matches('/');
+        let segments: Vec<&str> = path.split('/').skip(1).collect();
+        match segments.as_slice() {
+            ["api", "v1", "camera", number, member] => {
+                if *number != "0" {
+                    return Response::error(404, &format!("no camera {number}"));
+                }
+                if req.method != "GET" && req.method != "PUT" {
+                    return Response::error(405, "only GET and PUT are supported");
+                }
+                self.device_call(req, member)
+            }
+            ["management", "apiversions"] => self.management(req, json!([1])),
+            ["management", "v1", "description"] => self.management(
+                req,
+                json!({
+                    "ServerName": "svbony Alpaca server",
+                    "Manufacturer": "svbony crate",
+                    "ManufacturerVersion": env!("CARGO_PKG_VERSION"),
+                    "Location": self.config.location,
+                }),
+            ),
+            ["management", "v1", "configureddevices"] => self.management(
+                req,
+                json!([{
+                    "DeviceName": self.device.name(),
+                    "DeviceType": "Camera",
+                    "DeviceNumber": 0,
+                    "UniqueID": self.device.unique_id(),
+                }]),
+            ),
+            ["setup"] | ["setup", "v1", "camera", "0", "setup"] if req.method == "GET" => {
+                Response::html(format!(
+                    "<!DOCTYPE html><html><head><title>{0}</title></head><body>\
+                     <h1>{0}</h1><p>Alpaca camera 0. Settings are made by the client.</p>\
+                     </body></html>",
+                    html_escape(self.device.name())
+                ))
+            }
+            _ => Response::error(404, &format!("unknown path {}", req.path)),
+        }
+    }
+
+    fn management(&self, req: &Request, value: Value) -> Response {
+        if req.method != "GET" {
+            return Response::error(405, "only GET is supported");
+        }
+        Response::json(self.envelope(req, Some(value), None))
+    }
+
+    fn device_call(&self, req: &Request, member: &str) -> Response {
+        let result = self.device.call(req, member);
+        let client_tx = client_transaction(req);
+        match result {
+            Ok(Answer::Value(value)) => Response::json(self.envelope(req, Some(value), None)),
+            Ok(Answer::Done) => Response::json(self.envelope(req, None, None)),
+            Ok(Answer::Image(frame)) => {
+                let server_tx = self.next_transaction();
+                if req.accepts("application/imagebytes") {
+                    Response {
+                        status: 200,
+                        content_type: "application/imagebytes",
+                        body: camera::image_bytes(&frame, client_tx, server_tx),
+                    }
+                } else {
+                    Response::json(camera::image_json(&frame, client_tx, server_tx))
+                }
+            }
+            Err(Fault::Ascom(code, message)) => {
+                Response::json(self.envelope(req, None, Some((code, &message))))
+            }
+            Err(Fault::BadRequest(message)) => Response::error(400, &message),
+            Err(Fault::NotFound) => Response::error(404, &format!("unknown member {member}")),
+        }
+    }
+
+    /// The standard response fields around `value` or an ASCOM error.
+    fn envelope(&self, req: &Request, value: Option<Value>, error: Option<(i32, &str)>) -> String {
+        let (number, message) = error.unwrap_or((0, ""));
+        let mut body = json!({
+            "ClientTransactionID": client_transaction(req),
+            "ServerTransactionID": self.next_transaction(),
+            "ErrorNumber": number,
+            "ErrorMessage": message,
+        });
+        if let Some(value) = value {
+            body["Value"] = value;
+        }
+        body.to_string()
+    }
+
+    fn next_transaction(&self) -> u32 {
+        self.server_tx
+            .fetch_add(1, Ordering::Relaxed)
+            .wrapping_add(1)
+    }
+
+    /// Answers discovery broadcasts until shutdown.
+    fn discovery(&self, socket: UdpSocket) {
+        let reply = json!({ "AlpacaPort": self.port }).to_string();
+        let mut buf = [0; 256];
+        while !self.shutdown.load(Ordering::Relaxed) {
+            match socket.recv_from(&mut buf) {
+                Ok((n, from)) if buf[..n].starts_with(DISCOVERY_MESSAGE) => {
+                    let _ = socket.send_to(reply.as_bytes(), from);
+                }
+                // Other datagrams, and the read timeout used to poll shutdown.
+                _ => {}
+            }
+        }
+    }
+}
+
+/// The `ClientTransactionID` parameter, or 0 when missing or invalid.
+fn client_transaction(req: &Request) -> u32 {
+    req.param("ClientTransactionID")
+        .and_then(|id| id.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// An Alpaca server for one camera; see the [module docs](self).
+pub struct AlpacaServer {
+    listener: TcpListener,
+    discovery: Option<UdpSocket>,
+    shared: Arc<Shared>,
+}
+
+impl AlpacaServer {
+    /// Binds the HTTP server to `addr` and, if enabled, the discovery
+    /// responder to all interfaces.
+    ///
+    /// Port 0 picks a free port; see [`local_addr`](Self::local_addr).
+    pub fn bind(
+        addr: impl ToSocketAddrs,
+        camera: Arc<Camera>,
+        config: AlpacaConfig,
+    ) -> Result<Self, AlpacaError> {
+        let name = match &config.device_name {
+            Some(name) => name.clone(),
+            None => camera.info()?.name,
+        };
+        let device = Device::new(camera, name)?;
+        let listener = TcpListener::bind(addr)?;
+        let discovery = match config.discovery_port {
+            Some(port) => {
+                let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, port))?;
+                socket.set_read_timeout(Some(DISCOVERY_POLL))?;
+                Some(socket)
+            }
+            None => None,
+        };
+        let port = listener.local_addr()?.port();
+        Ok(Self {
+            listener,
+            discovery,
+            shared: Arc::new(Shared {
+                device: Arc::new(device),
+                connections: Mutex::new(Vec::new()),
+                shutdown: Arc::new(AtomicBool::new(false)),
+                next_id: AtomicU64::new(0),
+                server_tx: AtomicU32::new(0),
+                config,
+                port,
+            }),
+        })
+    }
+
+    /// Address the HTTP API listens on.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Address of the discovery responder, if discovery is enabled.
+    pub fn discovery_addr(&self) -> Option<SocketAddr> {
+        self.discovery.as_ref().and_then(|s| s.local_addr().ok())
+    }
+
+    /// The device name clients see.
+    pub fn device_name(&self) -> &str {
+        self.shared.device.name()
+    }
+
+    /// Serves clients on the current thread until the server is stopped
+    /// through an [`AlpacaServerHandle`], or forever.
+    pub fn run(mut self) -> io::Result<()> {
+        let discovery = self.discovery.take().map(|socket| {
+            let shared = Arc::clone(&self.shared);
+            thread::spawn(move || shared.discovery(socket))
+        });
+        self.accept_loop();
+
+        self.shared.shutdown.store(true, Ordering::Relaxed);
+        for (_, stream) in self.shared.connections.lock().unwrap().drain(..) {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+        if let Some(discovery) = discovery {
+            let _ = discovery.join();
+        }
+        Ok(())
+    }
+
+    fn accept_loop(&self) {
+        let shared = &self.shared;
+        server::accept_loop(
+            &self.listener,
+            &shared.shutdown,
+            shared.config.write_timeout,
+            |stream| {
+                let id = shared.next_id.fetch_add(1, Ordering::Relaxed);
+                shared
+                    .connections
+                    .lock()
+                    .unwrap()
+                    .push((id, stream.try_clone()?));
+                let shared = Arc::clone(shared);
+                thread::spawn(move || shared.serve(id, stream));
+                Ok(())
+            },
+        )
+    }
+
+    /// Runs the server on a background thread.
+    pub fn spawn(self) -> io::Result<AlpacaServerHandle> {
+        let addr = self.local_addr()?;
+        let shutdown = Arc::clone(&self.shared.shutdown);
+        Ok(AlpacaServerHandle {
+            server: ServerThread::spawn("Alpaca", addr, shutdown, move || self.run()),
+        })
+    }
+}
+
+/// A server running on a background thread. Stops the server when dropped.
+pub struct AlpacaServerHandle {
+    server: ServerThread,
+}
+
+impl AlpacaServerHandle {
+    /// Address the HTTP server listens on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.server.local_addr()
+    }
+
+    /// Closes all connections, stops the server and waits for it to finish.
+    /// Fails only if the server thread panicked.
+    pub fn stop(mut self) -> io::Result<()> {
+        self.server.stop()
+    }
+}
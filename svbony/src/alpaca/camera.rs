@@ -0,0 +1,953 @@
+//! The ASCOM `ICameraV3` members, implemented on a [`Camera`].
+
+use std::fmt::Write as _;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use serde_json::{json, Value};
+
+use super::http::Request;
+use crate::fits;
+use crate::guide::{GuidePulse, Guider, GuiderConfig, PulseHandle};
+use crate::{
+    BayerPattern, Camera, Capabilities, Celsius, ControlType, Error, Frame, GuideDirection,
+    ImageType, RoiFormat,
+};
+
+/// ASCOM error numbers.
+pub(super) const NOT_IMPLEMENTED: i32 = 0x400;
+pub(super) const INVALID_VALUE: i32 = 0x401;
+pub(super) const VALUE_NOT_SET: i32 = 0x402;
+pub(super) const NOT_CONNECTED: i32 = 0x407;
+pub(super) const INVALID_OPERATION: i32 = 0x40B;
+pub(super) const ACTION_NOT_IMPLEMENTED: i32 = 0x40C;
+pub(super) const DRIVER_ERROR: i32 = 0x500;
+
+/// The `ICamera` version implemented.
+const INTERFACE_VERSION: i32 = 3;
+
+/// `CameraState` values.
+const CAMERA_IDLE: i32 = 0;
+const CAMERA_EXPOSING: i32 = 2;
+const CAMERA_DOWNLOAD: i32 = 4;
+const CAMERA_ERROR: i32 = 5;
+
+/// `SensorType` values.
+const SENSOR_MONOCHROME: i32 = 0;
+const SENSOR_COLOR: i32 = 1;
+const SENSOR_RGGB: i32 = 2;
+
+/// Alpaca `ImageArray` element types.
+const ELEMENT_INT32: i32 = 2;
+const ELEMENT_BYTE: i32 = 6;
+const ELEMENT_UINT16: i32 = 8;
+
+/// Size of the `application/imagebytes` header.
+const IMAGE_BYTES_HEADER: usize = 44;
+
+/// Members readable while disconnected.
+const DEVICE_GETS: &[&str] = &[
+    "connected",
+    "description",
+    "driverinfo",
+    "driverversion",
+    "interfaceversion",
+    "name",
+    "supportedactions",
+];
+
+const CAMERA_GETS: &[&str] = &[
+    "bayeroffsetx",
+    "bayeroffsety",
+    "binx",
+    "biny",
+    "camerastate",
+    "cameraxsize",
+    "cameraysize",
+    "canabortexposure",
+    "canasymmetricbin",
+    "canfastreadout",
+    "cangetcoolerpower",
+    "canpulseguide",
+    "cansetccdtemperature",
+    "canstopexposure",
+    "ccdtemperature",
+    "cooleron",
+    "coolerpower",
+    "electronsperadu",
+    "exposuremax",
+    "exposuremin",
+    "exposureresolution",
+    "fastreadout",
+    "fullwellcapacity",
+    "gain",
+    "gainmax",
+    "gainmin",
+    "gains",
+    "hasshutter",
+    "heatsinktemperature",
+    "imagearray",
+    "imagearrayvariant",
+    "imageready",
+    "ispulseguiding",
+    "lastexposureduration",
+    "lastexposurestarttime",
+    "maxadu",
+    "maxbinx",
+    "maxbiny",
+    "numx",
+    "numy",
+    "offset",
+    "offsetmax",
+    "offsetmin",
+    "offsets",
+    "percentcompleted",
+    "pixelsizex",
+    "pixelsizey",
+    "readoutmode",
+    "readoutmodes",
+    "sensorname",
+    "sensortype",
+    "setccdtemperature",
+    "startx",
+    "starty",
+    "subexposureduration",
+];
+
+const CAMERA_PUTS: &[&str] = &[
+    "abortexposure",
+    "binx",
+    "biny",
+    "cooleron",
+    "fastreadout",
+    "gain",
+    "numx",
+    "numy",
+    "offset",
+    "pulseguide",
+    "readoutmode",
+    "setccdtemperature",
+    "startexposure",
+    "startx",
+    "starty",
+    "stopexposure",
+    "subexposureduration",
+];
+
+/// Why a request failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) enum Fault {
+    /// A missing or malformed parameter, answered with HTTP 400.
+    BadRequest(String),
+    /// An unknown member, answered with HTTP 404.
+    NotFound,
+    /// An ASCOM error number and message, reported in the JSON response.
+    Ascom(i32, String),
+}
+
+impl Fault {
+    fn not_implemented(member: &str) -> Self {
+        Self::Ascom(NOT_IMPLEMENTED, format!("{member} is not implemented"))
+    }
+
+    fn invalid_value(message: impl Into<String>) -> Self {
+        Self::Ascom(INVALID_VALUE, message.into())
+    }
+
+    fn invalid_operation(message: impl Into<String>) -> Self {
+        Self::Ascom(INVALID_OPERATION, message.into())
+    }
+}
+
+impl From<Error> for Fault {
+    fn from(e: Error) -> Self {
+        let code = match e {
+            Error::Unsupported(_) => NOT_IMPLEMENTED,
+            Error::InvalidArgument(_)
+            | Error::OutOfRange { .. }
+            | Error::InvalidSize
+            | Error::OutOfBoundary
+            | Error::InvalidDirection => INVALID_VALUE,
+            _ => DRIVER_ERROR,
+        };
+        Self::Ascom(code, e.to_string())
+    }
+}
+
+/// A successful answer.
+pub(super) enum Answer {
+    Value(Value),
+    /// A method or property write with no value.
+    Done,
+    Image(Arc<Frame>),
+}
+
+fn value(v: impl Into<Value>) -> Result<Answer, Fault> {
+    Ok(Answer::Value(v.into()))
+}
+
+struct Exposure {
+    id: u64,
+    cancel: Arc<AtomicBool>,
+    duration: Duration,
+    started: Instant,
+    started_at: SystemTime,
+}
+
+/// What an exposure thread applies to the camera before exposing.
+struct Job {
+    id: u64,
+    roi: RoiFormat,
+    format: ImageType,
+    duration: Duration,
+    cancel: Arc<AtomicBool>,
+}
+
+struct State {
+    connected: bool,
+    /// Subframe in binned pixels, applied by the next exposure.
+    bin: i32,
+    start_x: i32,
+    start_y: i32,
+    num_x: i32,
+    num_y: i32,
+    format: ImageType,
+    exposure: Option<Exposure>,
+    image: Option<Arc<Frame>>,
+    /// Duration and start of the exposure that produced `image`.
+    last: Option<(Duration, SystemTime)>,
+    failed: Option<String>,
+    pulses: Vec<PulseHandle>,
+}
+
+/// Device 0 of the server: one camera and its exposure state.
+pub(super) struct Device {
+    camera: Arc<Camera>,
+    caps: Capabilities,
+    name: String,
+    unique_id: String,
+    guider: Option<Guider>,
+    /// Held by exposure threads while they drive the camera, so an exposure
+    /// started right after an abort waits for the old one to stop.
+    capture: Mutex<()>,
+    next_exposure: AtomicU64,
+    state: Mutex<State>,
+}
+
+impl Device {
+    pub fn new(camera: Arc<Camera>, name: String) -> crate::Result<Self> {
+        let caps = camera.capabilities()?;
+        let roi = camera.roi()?;
+        let format = camera.output_image_type()?;
+        let unique_id = match camera.info()?.serial {
+            serial if serial.is_empty() => name.clone(),
+            serial => serial,
+        };
+        let guider = if caps.has_st4() {
+            Some(Guider::new(Arc::clone(&camera), GuiderConfig::default())?)
+        } else {
+            None
+        };
+        Ok(Self {
+            camera,
+            caps,
+            name,
+            unique_id,
+            guider,
+            capture: Mutex::new(()),
+            next_exposure: AtomicU64::new(0),
+            state: Mutex::new(State {
+                connected: false,
+                bin: roi.bin.max(1),
+                start_x: roi.start_x,
+                start_y: roi.start_y,
+                num_x: roi.width,
+                num_y: roi.height,
+                format,
+                exposure: None,
+                image: None,
+                last: None,
+                failed: None,
+                pulses: Vec::new(),
+            }),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn unique_id(&self) -> &str {
+        &self.unique_id
+    }
+
+    /// Handles `GET` or `PUT` of `member`.
+    pub fn call(self: &Arc<Self>, req: &Request, member: &str) -> Result<Answer, Fault> {
+        let get = req.method == "GET";
+        if get && DEVICE_GETS.contains(&member) {
+            return self.get_device(member);
+        }
+        match (get, member) {
+            (false, "connected") => {
+                self.set_connected(bool_param(req, "Connected")?);
+                return Ok(Answer::Done);
+            }
+            (false, "action") => {
+                return Err(Fault::Ascom(
+                    ACTION_NOT_IMPLEMENTED,
+                    "no actions are supported".into(),
+                ))
+            }
+            (false, "commandblind" | "commandbool" | "commandstring") => {
+                return Err(Fault::not_implemented(member))
+            }
+            _ => {}
+        }
+        let known = if get { CAMERA_GETS } else { CAMERA_PUTS };
+        if !known.contains(&member) {
+            return Err(Fault::NotFound);
+        }
+        if !self.state.lock().unwrap().connected {
+            return Err(Fault::Ascom(
+                NOT_CONNECTED,
+                "camera is not connected".into(),
+            ));
+        }
+        if get {
+            self.get(member)
+        } else {
+            self.put(member, req)
+        }
+    }
+
+    fn get_device(&self, member: &str) -> Result<Answer, Fault> {
+        match member {
+            "connected" => value(self.state.lock().unwrap().connected),
+            "description" => value(format!("SVBony {}", self.name)),
+            "driverinfo" => value("svbony Rust crate Alpaca camera server"),
+            "driverversion" => value(env!("CARGO_PKG_VERSION")),
+            "interfaceversion" => value(INTERFACE_VERSION),
+            "name" => value(self.name.as_str()),
+            "supportedactions" => Ok(Answer::Value(json!([]))),
+            _ => Err(Fault::NotFound),
+        }
+    }
+
+    fn get(&self, member: &str) -> Result<Answer, Fault> {
+        let prop = &self.caps.property;
+        let mut state = self.state.lock().unwrap();
+        match member {
+            "bayeroffsetx" | "bayeroffsety" => {
+                let (x, y) = self
+                    .bayer_offsets(&state)
+                    .ok_or_else(|| Fault::not_implemented("BayerOffset on a non-Bayer readout"))?;
+                value(if member == "bayeroffsetx" { x } else { y })
+            }
+            "binx" | "biny" => value(state.bin),
+            "camerastate" => value(state.camera_state()),
+            "cameraxsize" => value(prop.max_width),
+            "cameraysize" => value(prop.max_height),
+            "canabortexposure" => value(true),
+            "canasymmetricbin" | "canfastreadout" | "canstopexposure" | "hasshutter" => {
+                value(false)
+            }
+            "cangetcoolerpower" | "cansetccdtemperature" => value(self.caps.has_cooler()),
+            "canpulseguide" => value(self.guider.is_some()),
+            "ccdtemperature" => {
+                if !self.caps.has_temperature_sensor() {
+                    return Err(Fault::not_implemented("CCDTemperature"));
+                }
+                value(self.camera.sensor_temperature()?.0)
+            }
+            "cooleron" => {
+                self.require_cooler("CoolerOn")?;
+                value(self.camera.cooler_enabled()?)
+            }
+            "coolerpower" => {
+                self.require_cooler("CoolerPower")?;
+                value(self.camera.cooler_power()?.0)
+            }
+            "exposuremax" | "exposuremin" => {
+                let caps = self
+                    .caps
+                    .control(ControlType::Exposure)
+                    .ok_or_else(|| Fault::not_implemented("exposure limits"))?;
+                let us = if member == "exposuremax" {
+                    caps.max_value
+                } else {
+                    caps.min_value
+                };
+                value(us as f64 / 1e6)
+            }
+            "exposureresolution" => value(1e-6),
+            "gain" => self.control(ControlType::Gain, "Gain"),
+            "gainmax" | "gainmin" => self.limit(ControlType::Gain, "Gain", member == "gainmax"),
+            "offset" => self.control(ControlType::BlackLevel, "Offset"),
+            "offsetmax" | "offsetmin" => {
+                self.limit(ControlType::BlackLevel, "Offset", member == "offsetmax")
+            }
+            "imagearray" | "imagearrayvariant" => match (&state.image, &state.failed) {
+                (Some(image), None) if state.exposure.is_none() => {
+                    Ok(Answer::Image(Arc::clone(image)))
+                }
+                (_, Some(failed)) => Err(Fault::invalid_operation(failed.as_str())),
+                _ => Err(Fault::invalid_operation("no image is available")),
+            },
+            "imageready" => value(state.image_ready()),
+            "ispulseguiding" => {
+                state.pulses.retain_mut(|p| !p.is_finished());
+                value(!state.pulses.is_empty())
+            }
+            "lastexposureduration" | "lastexposurestarttime" => {
+                let (duration, started_at) = state.last.ok_or_else(|| {
+                    Fault::Ascom(VALUE_NOT_SET, "no exposure has been taken".into())
+                })?;
+                if member == "lastexposureduration" {
+                    value(duration.as_secs_f64())
+                } else {
+                    value(fits::timestamp(started_at))
+                }
+            }
+            "maxadu" => value(max_adu(state.format)),
+            "maxbinx" | "maxbiny" => value(self.caps.max_bin()),
+            "numx" => value(state.num_x),
+            "numy" => value(state.num_y),
+            "startx" => value(state.start_x),
+            "starty" => value(state.start_y),
+            "percentcompleted" => value(state.percent_completed()),
+            "pixelsizex" | "pixelsizey" => match self.caps.pixel_size_um {
+                Some(size) => value(f64::from(size)),
+                None => Err(Fault::not_implemented("PixelSize")),
+            },
+            "readoutmode" => {
+                let index = self.readout_modes().position(|f| f == state.format);
+                value(index.unwrap_or(0) as i32)
+            }
+            "readoutmodes" => {
+                let names: Vec<_> = self.readout_modes().map(|f| format!("{f:?}")).collect();
+                value(names)
+            }
+            "sensortype" => value(sensor_type(state.format, prop.bayer_pattern)),
+            "setccdtemperature" => {
+                self.require_cooler("SetCCDTemperature")?;
+                value(self.camera.target_temperature()?.0)
+            }
+            "electronsperadu"
+            | "fastreadout"
+            | "fullwellcapacity"
+            | "gains"
+            | "heatsinktemperature"
+            | "offsets"
+            | "sensorname"
+            | "subexposureduration" => Err(Fault::not_implemented(member)),
+            _ => Err(Fault::NotFound),
+        }
+    }
+
+    fn put(self: &Arc<Self>, member: &str, req: &Request) -> Result<Answer, Fault> {
+        match member {
+            "abortexposure" => self.abort(),
+            "binx" | "biny" => {
+                let bin: i32 = param(req, if member == "binx" { "BinX" } else { "BinY" })?;
+                if !self.caps.supports_bin(bin) {
+                    return Err(Fault::invalid_value(format!("bin {bin} is not supported")));
+                }
+                // Binning is symmetric, so either axis sets both.
+                self.state.lock().unwrap().bin = bin;
+            }
+            "cooleron" => {
+                self.require_cooler("CoolerOn")?;
+                self.camera
+                    .set_cooler_enabled(bool_param(req, "CoolerOn")?)?;
+            }
+            "gain" => self.set_control(ControlType::Gain, "Gain", req)?,
+            "offset" => self.set_control(ControlType::BlackLevel, "Offset", req)?,
+            "numx" | "numy" | "startx" | "starty" => {
+                let (name, min) = match member {
+                    "numx" => ("NumX", 1),
+                    "numy" => ("NumY", 1),
+                    "startx" => ("StartX", 0),
+                    _ => ("StartY", 0),
+                };
+                let v: i32 = param(req, name)?;
+                if v < min {
+                    return Err(Fault::invalid_value(format!(
+                        "{name} must be at least {min}"
+                    )));
+                }
+                let mut state = self.state.lock().unwrap();
+                *match member {
+                    "numx" => &mut state.num_x,
+                    "numy" => &mut state.num_y,
+                    "startx" => &mut state.start_x,
+                    _ => &mut state.start_y,
+                } = v;
+            }
+            "pulseguide" => self.pulse_guide(param(req, "Direction")?, param(req, "Duration")?)?,
+            "readoutmode" => {
+                let index: i32 = param(req, "ReadoutMode")?;
+                let format = usize::try_from(index)
+                    .ok()
+                    .and_then(|i| self.readout_modes().nth(i))
+                    .ok_or_else(|| Fault::invalid_value(format!("no readout mode {index}")))?;
+                self.state.lock().unwrap().format = format;
+            }
+            "setccdtemperature" => {
+                self.require_cooler("SetCCDTemperature")?;
+                let target: f64 = param(req, "SetCCDTemperature")?;
+                if !(-273.15..=100.0).contains(&target) {
+                    return Err(Fault::invalid_value(format!(
+                        "set point {target} C is out of range"
+                    )));
+                }
+                self.camera.set_target_temperature(Celsius(target))?;
+            }
+            "startexposure" => {
+                self.start_exposure(param(req, "Duration")?, bool_param(req, "Light")?)?
+            }
+            "fastreadout" | "stopexposure" | "subexposureduration" => {
+                return Err(Fault::not_implemented(member))
+            }
+            _ => return Err(Fault::NotFound),
+        }
+        Ok(Answer::Done)
+    }
+
+    fn set_connected(&self, connected: bool) {
+        let mut state = self.state.lock().unwrap();
+        if !connected {
+            if let Some(exposure) = state.exposure.take() {
+                exposure.cancel.store(true, Ordering::Relaxed);
+            }
+            for pulse in state.pulses.drain(..) {
+                pulse.cancel();
+            }
+        }
+        state.connected = connected;
+    }
+
+    fn require_cooler(&self, member: &str) -> Result<(), Fault> {
+        match self.caps.has_cooler() {
+            true => Ok(()),
+            false => Err(Fault::not_implemented(member)),
+        }
+    }
+
+    fn control(&self, ctrl: ControlType, member: &str) -> Result<Answer, Fault> {
+        if !self.caps.has_control(ctrl) {
+            return Err(Fault::not_implemented(member));
+        }
+        value(self.camera.get_control(ctrl)?.0)
+    }
+
+    fn limit(&self, ctrl: ControlType, member: &str, max: bool) -> Result<Answer, Fault> {
+        let caps = self
+            .caps
+            .control(ctrl)
+            .ok_or_else(|| Fault::not_implemented(member))?;
+        value(if max { caps.max_value } else { caps.min_value })
+    }
+
+    fn set_control(&self, ctrl: ControlType, member: &str, req: &Request) -> Result<(), Fault> {
+        if !self.caps.has_control(ctrl) {
+            return Err(Fault::not_implemented(member));
+        }
+        let v: i64 = param(req, member)?;
+        self.camera.set_control(ctrl, v, false)?;
+        Ok(())
+    }
+
+    /// Image formats offered as readout modes, in the camera's order.
+    fn readout_modes(&self) -> impl Iterator<Item = ImageType> + '_ {
+        self.caps
+            .property
+            .supported_formats
+            .iter()
+            .copied()
+            .filter(|f| !matches!(f, ImageType::Other(_)))
+    }
+
+    fn bayer_offsets(&self, state: &State) -> Option<(i32, i32)> {
+        if sensor_type(state.format, self.caps.property.bayer_pattern) != SENSOR_RGGB {
+            return None;
+        }
+        bayer_offsets(self.caps.property.bayer_pattern?)
+    }
+
+    fn start_exposure(self: &Arc<Self>, seconds: f64, _light: bool) -> Result<(), Fault> {
+        let duration = Duration::try_from_secs_f64(seconds)
+            .map_err(|_| Fault::invalid_value(format!("invalid exposure time {seconds} s")))?;
+        if let Some(caps) = self.caps.control(ControlType::Exposure) {
+            let us = duration.as_micros();
+            if us < caps.min_value.max(0) as u128 || us > caps.max_value.max(0) as u128 {
+                return Err(Fault::invalid_value(format!(
+                    "exposure time {seconds} s is outside {}..={} s",
+                    caps.min_value as f64 / 1e6,
+                    caps.max_value as f64 / 1e6
+                )));
+            }
+        }
+        let mut state = self.state.lock().unwrap();
+        if state.exposure.is_some() {
+            return Err(Fault::invalid_operation("an exposure is in progress"));
+        }
+        let roi = RoiFormat::builder(&self.caps.property)
+            .bin(state.bin)
+            .at(state.start_x, state.start_y, state.num_x, state.num_y)
+            .build()?;
+        let job = Job {
+            id: self.next_exposure.fetch_add(1, Ordering::Relaxed),
+            roi,
+            format: state.format,
+            duration,
+            cancel: Arc::new(AtomicBool::new(false)),
+        };
+        state.exposure = Some(Exposure {
+            id: job.id,
+            cancel: Arc::clone(&job.cancel),
+            duration,
+            started: Instant::now(),
+            started_at: SystemTime::now(),
+        });
+        state.image = None;
+        state.failed = None;
+        drop(state);
+
+        let device = Arc::clone(self);
+        thread::spawn(move || device.run_exposure(job));
+        Ok(())
+    }
+
+    fn run_exposure(&self, job: Job) {
+        let result = {
+            let _capture = self.capture.lock().unwrap();
+            if job.cancel.load(Ordering::Relaxed) {
+                Ok(None)
+            } else {
+                self.camera
+                    .set_roi(&job.roi)
+                    .and_then(|()| self.camera.set_output_image_type(job.format))
+                    .and_then(|()| self.camera.set_exposure(job.duration))
+                    .and_then(|()| self.camera.expose(job.duration, &job.cancel))
+            }
+        };
+        let mut state = self.state.lock().unwrap();
+        let exposure = match state.exposure.take() {
+            Some(e) if e.id == job.id => e,
+            // Aborted, possibly with another exposure started since.
+            other => {
+                state.exposure = other;
+                return;
+            }
+        };
+        match result {
+            Ok(Some(frame)) => {
+                state.image = Some(Arc::new(frame));
+                state.last = Some((exposure.duration, exposure.started_at));
+            }
+            Ok(None) => {}
+            Err(e) => state.failed = Some(format!("exposure failed: {e}")),
+        }
+    }
+
+    fn abort(&self) {
+        if let Some(exposure) = self.state.lock().unwrap().exposure.take() {
+            exposure.cancel.store(true, Ordering::Relaxed);
+        }
+    }
+
+    fn pulse_guide(&self, direction: i32, ms: i32) -> Result<(), Fault> {
+        let Some(guider) = &self.guider else {
+            return Err(Fault::not_implemented("PulseGuide"));
+        };
+        let direction = match direction {
+            0 => GuideDirection::North,
+            1 => GuideDirection::South,
+            2 => GuideDirection::East,
+            3 => GuideDirection::West,
+            _ => {
+                return Err(Fault::invalid_value(format!(
+                    "invalid direction {direction}"
+                )))
+            }
+        };
+        let ms = u64::try_from(ms)
+            .map_err(|_| Fault::invalid_value(format!("invalid duration {ms} ms")))?;
+        if ms == 0 {
+            return Ok(());
+        }
+        let handle = guider.pulse(GuidePulse::new(direction, Duration::from_millis(ms)))?;
+        self.state.lock().unwrap().pulses.push(handle);
+        Ok(())
+    }
+}
+
+impl State {
+    fn camera_state(&self) -> i32 {
+        match (&self.exposure, &self.failed) {
+            (Some(e), _) if e.started.elapsed() < e.duration => CAMERA_EXPOSING,
+            (Some(_), _) => CAMERA_DOWNLOAD,
+            (None, Some(_)) => CAMERA_ERROR,
+            (None, None) => CAMERA_IDLE,
+        }
+    }
+
+    fn image_ready(&self) -> bool {
+        self.exposure.is_none() && self.image.is_some()
+    }
+
+    fn percent_completed(&self) -> i32 {
+        match &self.exposure {
+            Some(e) => {
+                let done = e.started.elapsed().as_secs_f64() / e.duration.as_secs_f64();
+                // A zero duration gives NaN, which saturates to 0.
+                (done.min(1.0) * 100.0) as i32
+            }
+            None if self.image.is_some() => 100,
+            None => 0,
+        }
+    }
+}
+
+/// Reads a required parameter.
+fn param<T: FromStr>(req: &Request, name: &str) -> Result<T, Fault> {
+    let raw = req
+        .param(name)
+        .ok_or_else(|| Fault::BadRequest(format!("missing parameter {name}")))?;
+    raw.trim()
+        .parse()
+        .map_err(|_| Fault::BadRequest(format!("invalid value {raw:?} for {name}")))
+}
+
+/// Reads a required `True` / `False` parameter, ignoring case.
+fn bool_param(req: &Request, name: &str) -> Result<bool, Fault> {
+    let raw: String = param(req, name)?;
+    match raw.to_ascii_lowercase().as_str() {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(Fault::BadRequest(format!(
+            "invalid value {raw:?} for {name}"
+        ))),
+    }
+}
+
+/// `SensorType` of images in `format`.
+fn sensor_type(format: ImageType, pattern: Option<BayerPattern>) -> i32 {
+    match format {
+        ImageType::Rgb24 | ImageType::Rgb32 => SENSOR_COLOR,
+        f if f.is_raw() && pattern.and_then(bayer_offsets).is_some() => SENSOR_RGGB,
+        _ => SENSOR_MONOCHROME,
+    }
+}
+
+/// `BayerOffsetX` / `BayerOffsetY` of `pattern` relative to RGGB.
+fn bayer_offsets(pattern: BayerPattern) -> Option<(i32, i32)> {
+    match pattern {
+        BayerPattern::Rg => Some((0, 0)),
+        BayerPattern::Gr => Some((1, 0)),
+        BayerPattern::Gb => Some((0, 1)),
+        BayerPattern::Bg => Some((1, 1)),
+        BayerPattern::Other(_) => None,
+    }
+}
+
+fn max_adu(format: ImageType) -> i32 {
+    match format.bytes_per_pixel() {
+        1 | 3 | 4 => u8::MAX.into(),
+        _ => u16::MAX.into(),
+    }
+}
+
+/// Buffer channels of the image planes, in red-green-blue order for colour
+/// frames.
+fn planes(frame: &Frame) -> Vec<usize> {
+    match frame.image_type.channel_order() {
+        Some(order) => order.rgb_indices().to_vec(),
+        None => vec![0],
+    }
+}
+
+/// Calls `f` for each sample in .NET array order, `[x, y]` or
+/// `[x, y, plane]` with the last index varying fastest.
+fn for_each_sample(frame: &Frame, mut f: impl FnMut(u16)) {
+    let planes = planes(frame);
+    for x in 0..frame.width() {
+        for y in 0..frame.height() {
+            for &c in &planes {
+                f(frame.sample(x, y, c));
+            }
+        }
+    }
+}
+
+/// `Rank` and the three `Dimension`s of `frame` as an image array.
+fn dimensions(frame: &Frame) -> (i32, [i32; 3]) {
+    let (w, h) = (frame.width() as i32, frame.height() as i32);
+    match planes(frame).len() {
+        1 => (2, [w, h, 0]),
+        n => (3, [w, h, n as i32]),
+    }
+}
+
+/// Encodes `frame` as an Alpaca JSON `ImageArray` response.
+pub(super) fn image_json(frame: &Frame, client_tx: u32, server_tx: u32) -> String {
+    let (rank, _) = dimensions(frame);
+    let planes = planes(frame).len();
+    let samples = frame.width() * frame.height() * planes;
+    let mut out = String::with_capacity(samples * 6 + 200);
+    let _ = write!(
+        out,
+        "{{\"Type\":{ELEMENT_INT32},\"Rank\":{rank},\"ClientTransactionID\":{client_tx},\
+         \"ServerTransactionID\":{server_tx},\"ErrorNumber\":0,\"ErrorMessage\":\"\",\"Value\":["
+    );
+    let per_column = frame.height() * planes;
+    let mut i = 0;
+    for_each_sample(frame, |v| {
+        if i % per_column == 0 {
+            out.push_str(if i == 0 { "[" } else { "],[" });
+        } else {
+            out.push(',');
+        }
+        if planes > 1 {
+            if i % planes == 0 {
+                out.push('[');
+            }
+            let _ = write!(out, "{v}");
+            if i % planes == planes - 1 {
+                out.push(']');
+            }
+        } else {
+            let _ = write!(out, "{v}");
+        }
+        i += 1;
+    });
+    if i > 0 {
+        out.push(']');
+    }
+    out.push_str("]}");
+    out
+}
+
+/// Encodes `frame` in the Alpaca `application/imagebytes` format.
+pub(super) fn image_bytes(frame: &Frame, client_tx: u32, server_tx: u32) -> Vec<u8> {
+    let (rank, dims) = dimensions(frame);
+    let wide = frame.image_type.bytes_per_pixel() / frame.channels() == 2;
+    let samples = frame.width() * frame.height() * planes(frame).len();
+    let mut out = Vec::with_capacity(IMAGE_BYTES_HEADER + samples * if wide { 2 } else { 1 });
+    let header = [
+        1, // metadata version
+        0, // error number
+        client_tx as i32,
+        server_tx as i32,
+        IMAGE_BYTES_HEADER as i32,
+        ELEMENT_INT32,
+        if wide { ELEMENT_UINT16 } else { ELEMENT_BYTE },
+        rank,
+        dims[0],
+        dims[1],
+        dims[2],
+    ];
+    for field in header {
+        out.extend_from_slice(&field.to_le_bytes());
+    }
+    if wide {
+        for_each_sample(frame, |v| out.extend_from_slice(&v.to_le_bytes()));
+    } else {
+        for_each_sample(frame, |v| out.push(v as u8));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(width: i32, height: i32, image_type: ImageType) -> Frame {
+        let roi = RoiFormat {
+            start_x: 0,
+            start_y: 0,
+            width,
+            height,
+            bin: 1,
+        };
+        let mut frame = Frame::zeroed(roi, image_type);
+        for y in 0..height as usize {
+            for x in 0..width as usize {
+                for c in 0..frame.channels() {
+                    frame.set_sample(x, y, c, (10 * x + y) as u16 * 10 + c as u16);
+                }
+            }
+        }
+        frame
+    }
+
+    #[test]
+    fn json_is_column_major() {
+        let json = image_json(&frame(2, 3, ImageType::Raw16), 7, 9);
+        let v: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(v["Type"], 2);
+        assert_eq!(v["Rank"], 2);
+        assert_eq!(v["ClientTransactionID"], 7);
+        assert_eq!(v["ServerTransactionID"], 9);
+        assert_eq!(v["ErrorNumber"], 0);
+        assert_eq!(v["Value"], json!([[0, 10, 20], [100, 110, 120]]));
+    }
+
+    #[test]
+    fn json_colour_planes_are_rgb() {
+        let json = image_json(&frame(1, 2, ImageType::Rgb24), 0, 1);
+        let v: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(v["Rank"], 3);
+        // Buffer channels are BGR: red is channel 2.
+        assert_eq!(v["Value"], json!([[[2, 1, 0], [12, 11, 10]]]));
+    }
+
+    #[test]
+    fn image_bytes_layout() {
+        let bytes = image_bytes(&frame(2, 2, ImageType::Raw16), 3, 4);
+        let field = |i: usize| i32::from_le_bytes(bytes[4 * i..4 * i + 4].try_into().unwrap());
+        let header: Vec<i32> = (0..11).map(field).collect();
+        assert_eq!(header, [1, 0, 3, 4, 44, 2, 8, 2, 2, 2, 0]);
+        let data: Vec<u16> = bytes[44..]
+            .chunks(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect();
+        assert_eq!(data, [0, 10, 100, 110]);
+
+        let bytes = image_bytes(&frame(2, 1, ImageType::Raw8), 0, 0);
+        assert_eq!(i32::from_le_bytes(bytes[24..28].try_into().unwrap()), 6);
+        assert_eq!(&bytes[44..], [0, 100]);
+    }
+
+    #[test]
+    fn sensor_types() {
+        let rggb = Some(BayerPattern::Rg);
+        assert_eq!(sensor_type(ImageType::Raw16, rggb), SENSOR_RGGB);
+        assert_eq!(sensor_type(ImageType::Rgb24, rggb), SENSOR_COLOR);
+        assert_eq!(sensor_type(ImageType::Y16, rggb), SENSOR_MONOCHROME);
+        assert_eq!(sensor_type(ImageType::Raw16, None), SENSOR_MONOCHROME);
+        assert_eq!(bayer_offsets(BayerPattern::Gb), Some((0, 1)));
+        assert_eq!(bayer_offsets(BayerPattern::Bg), Some((1, 1)));
+        assert_eq!(max_adu(ImageType::Raw8), 255);
+        assert_eq!(max_adu(ImageType::Raw12), 65535);
+    }
+
+    #[test]
+    fn errors_map_to_ascom_numbers() {
+        assert_eq!(
+            Fault::from(Error::Unsupported("x".into())),
+            Fault::Ascom(NOT_IMPLEMENTED, "not supported by this camera: x".into())
+        );
+        assert!(matches!(
+            Fault::from(Error::InvalidSize),
+            Fault::Ascom(INVALID_VALUE, _)
+        ));
+        assert!(matches!(
+            Fault::from(Error::Timeout),
+            Fault::Ascom(DRIVER_ERROR, _)
+        ));
+    }
+}
@@ -0,0 +1,321 @@
+//! Minimal HTTP/1.1 for the Alpaca REST API: request parsing with
+//! form-encoded parameters, and responses with keep-alive.
+
+use std::io::{self, BufRead, Read, Write};
+
+/// Longest accepted request line or header line.
+const MAX_LINE: usize = 8 * 1024;
+/// Most header lines accepted in one request.
+const MAX_HEADERS: usize = 100;
+/// Largest accepted request body; Alpaca parameters are small.
+const MAX_BODY: usize = 64 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Request {
+    pub method: String,
+    /// Path without the query string.
+    pub path: String,
+    /// Query parameters of a GET, or form parameters from the body of a PUT.
+    pub params: Vec<(String, String)>,
+    pub headers: Vec<(String, String)>,
+    /// Whether the client wants the connection kept open.
+    pub keep_alive: bool,
+}
+
+impl Request {
+    /// Reads the next request. Returns `Ok(None)` if the client closed the
+    /// connection between requests.
+    pub fn read(reader: &mut impl BufRead) -> io::Result<Option<Self>> {
+        let mut line = String::new();
+        // Tolerate blank lines before a request (RFC 9112, 2.2).
+        loop {
+            line.clear();
+            if read_line(reader, &mut line)? == 0 {
+                return Ok(None);
+            }
+            if !line.trim().is_empty() {
+                break;
+            }
+        }
+        let mut parts = line.split_whitespace();
+        let (Some(method), Some(target), Some(version), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid(format!("malformed request line {:?}", line.trim())));
+        };
+        let (method, target) = (method.to_owned(), target.to_owned());
+        let http10 = version == "HTTP/1.0";
+
+        let mut headers = Vec::new();
+        loop {
+            line.clear();
+            if read_line(reader, &mut line)? == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            let header = line.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if headers.len() == MAX_HEADERS {
+                return Err(invalid("too many headers".into()));
+            }
+            let Some((name, value)) = header.split_once(':') else {
+                return Err(invalid(format!("malformed header {header:?}")));
+            };
+            headers.push((name.trim().to_owned(), value.trim().to_owned()));
+        }
+
+        let (path, query) = match target.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (target.as_str(), None),
+        };
+        let mut request = Self {
+            method,
+            path: percent_decode(path, false),
+            params: Vec::new(),
+            headers,
+            keep_alive: false,
+        };
+        request.keep_alive = match request.header("Connection") {
+            Some(c) if c.eq_ignore_ascii_case("close") => false,
+            Some(c) if c.eq_ignore_ascii_case("keep-alive") => true,
+            _ => !http10,
+        };
+
+        let length = match request.header("Content-Length") {
+            Some(len) => len
+                .parse::<usize>()
+                .map_err(|_| invalid(format!("bad Content-Length {len:?}")))?,
+            None => 0,
+        };
+        if length > MAX_BODY {
+            return Err(invalid(format!(
+                "request body of {length} bytes is too large"
+            )));
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body)?;
+
+        request.params = if request.method == "PUT" {
+            decode_form(&String::from_utf8_lossy(&body))
+        } else {
+            query.map(decode_form).unwrap_or_default()
+        };
+        Ok(Some(request))
+    }
+
+    /// Looks up a header by case-insensitive name.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        lookup(&self.headers, name)
+    }
+
+    /// Looks up a parameter by case-insensitive name, as Alpaca requires.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        lookup(&self.params, name)
+    }
+
+    /// Returns `true` if the `Accept` header lists `media_type`.
+    pub fn accepts(&self, media_type: &str) -> bool {
+        self.header("Accept").map_or(false, |accept| {
+            accept.split(',').any(|t| {
+                let t = t.split(';').next().unwrap_or_default().trim();
+                t.eq_ignore_ascii_case(media_type)
+            })
+        })
+    }
+}
+
+fn lookup<'a>(pairs: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    pairs
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
+}
+
+/// Reads one line, failing on lines longer than [`MAX_LINE`].
+fn read_line(reader: &mut impl BufRead, line: &mut String) -> io::Result<usize> {
+    let n = reader.take(MAX_LINE as u64 + 1).read_line(line)?;
+    if n > MAX_LINE {
+        return Err(invalid("line too long".into()));
+    }
+    Ok(n)
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Decodes `application/x-www-form-urlencoded` pairs.
+fn decode_form(s: &str) -> Vec<(String, String)> {
+    s.split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(name, true), percent_decode(value, true))
+        })
+        .collect()
+}
+
+/// Decodes `%XX` escapes and, in form data, `+` as a space. Malformed
+/// escapes are kept as they are.
+fn percent_decode(s: &str, form: bool) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' if form => out.push(b' '),
+            b'%' => match bytes.get(i + 1..i + 3) {
+                Some(&[hi, lo]) if hi.is_ascii_hexdigit() && lo.is_ascii_hexdigit() => {
+                    out.push(hex_value(hi) << 4 | hex_value(lo));
+                    i += 2;
+                }
+                _ => out.push(b'%'),
+            },
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn hex_value(digit: u8) -> u8 {
+    match digit {
+        b'0'..=b'9' => digit - b'0',
+        _ => (digit | 0x20) - b'a' + 10,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn json(body: String) -> Self {
+        Self {
+            status: 200,
+            content_type: "application/json; charset=utf-8",
+            body: body.into_bytes(),
+        }
+    }
+
+    /// A plain-text error, used for malformed requests and unknown paths.
+    pub fn error(status: u16, message: &str) -> Self {
+        Self {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: message.as_bytes().to_vec(),
+        }
+    }
+
+    pub fn html(body: String) -> Self {
+        Self {
+            status: 200,
+            content_type: "text/html; charset=utf-8",
+            body: body.into_bytes(),
+        }
+    }
+
+    pub fn write_to(&self, out: &mut impl Write, keep_alive: bool) -> io::Result<()> {
+        let reason = match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            _ => "Internal Server Error",
+        };
+        write!(
+            out,
+            "HTTP/1.1 {} {reason}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: {}\r\n\r\n",
+            self.status,
+            self.content_type,
+            self.body.len(),
+            if keep_alive { "keep-alive" } else { "close" },
+        )?;
+        out.write_all(&self.body)?;
+        out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(raw: &str) -> io::Result<Option<Request>> {
+        Request::read(&mut raw.as_bytes())
+    }
+
+    #[test]
+    fn get_with_query() {
+        let req = parse(
+            "GET /api/v1/camera/0/gain?ClientID=3&clienttransactionid=17 HTTP/1.1\r\n\
+             Host: localhost\r\nAccept: application/imagebytes, application/json\r\n\r\n",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(req.method, "GET");
+        assert_eq!(req.path, "/api/v1/camera/0/gain");
+        assert_eq!(req.param("ClientTransactionID"), Some("17"));
+        assert_eq!(req.param("clientid"), Some("3"));
+        assert!(req.keep_alive);
+        assert!(req.accepts("application/imagebytes"));
+        assert!(!req.accepts("text/html"));
+    }
+
+    #[test]
+    fn put_with_form_body() {
+        let body = "Duration=1.5&Light=True&Name=a+b%2Fc";
+        let raw = format!(
+            "PUT /api/v1/camera/0/startexposure?Ignored=1 HTTP/1.0\r\n\
+             Content-Type: application/x-www-form-urlencoded\r\n\
+             Content-Length: {}\r\n\r\n{body}",
+            body.len()
+        );
+        let req = parse(&raw).unwrap().unwrap();
+        assert_eq!(req.param("duration"), Some("1.5"));
+        assert_eq!(req.param("Name"), Some("a b/c"));
+        assert_eq!(req.param("Ignored"), None);
+        assert!(!req.keep_alive);
+    }
+
+    #[test]
+    fn eof_and_malformed() {
+        assert!(parse("").unwrap().is_none());
+        assert!(parse("\r\n").unwrap().is_none());
+        assert!(parse("GET /\r\n\r\n").is_err());
+        assert!(parse("GET / HTTP/1.1\r\nbroken\r\n\r\n").is_err());
+        assert!(parse("PUT / HTTP/1.1\r\nContent-Length: x\r\n\r\n").is_err());
+    }
+
+    #[test]
+    fn percent_decoding() {
+        assert_eq!(percent_decode("a%20b+c", false), "a b+c");
+        assert_eq!(percent_decode("a%20b+c", true), "a b c");
+        assert_eq!(percent_decode("100%", true), "100%");
+        assert_eq!(percent_decode("%zz%4", true), "%zz%4");
+        assert_eq!(
+            decode_form("a=1&&b&c=%3D"),
+            [
+                ("a".into(), "1".into()),
+                ("b".into(), "".into()),
+                ("c".into(), "=".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn response_headers() {
+        let mut out = Vec::new();
+        Response::json("{}".into())
+            .write_to(&mut out, true)
+            .unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(text.contains("Content-Length: 2\r\n"));
+        assert!(text.contains("Connection: keep-alive\r\n"));
+        assert!(text.ends_with("\r\n\r\n{}"));
+    }
+}
@@ -0,0 +1,170 @@
+//! [`ndarray`] views of frames and capture into caller-provided arrays
+//! (feature = "ndarray").
+//!
+//! Arrays are indexed `[row, column]` for single-channel formats and
+//! `[row, column, channel]` for `Rgb24` / `Rgb32`, whose channels stay in the
+//! SDK's blue-first order (see [`ImageType::channel_order`](crate::ImageType::channel_order)).
+
+use ndarray::{Array2, ArrayView2, ArrayView3, CowArray, Ix2};
+
+use crate::{Camera, Error, Frame, Result};
+
+impl Frame {
+    /// Borrows an 8-bit single-channel frame (`Raw8`, `Y8`) as a 2-D array.
+    ///
+    /// Returns [`Error::InvalidImageType`] for other formats.
+    pub fn as_array_u8(&self) -> Result<ArrayView2<'_, u8>> {
+        if self.image_type.bytes_per_pixel() != 1 {
+            return Err(Error::InvalidImageType);
+        }
+        ArrayView2::from_shape((self.height(), self.width()), &self.data)
+            .map_err(|_| Error::InvalidSize)
+    }
+
+    /// Returns a 16-bit single-channel frame (`Raw10`-`Raw16`, `Y10`-`Y16`) as
+    /// a 2-D array.
+    ///
+    /// The buffer is borrowed without copying when the host is little-endian
+    /// and the data happens to be 2-byte aligned; otherwise the samples are
+    /// decoded into an owned array. Returns [`Error::InvalidImageType`] for
+    /// other formats.
+    pub fn as_array_u16(&self) -> Result<CowArray<'_, u16, Ix2>> {
+        if self.image_type.bytes_per_pixel() != 2 {
+            return Err(Error::InvalidImageType);
+        }
+        let shape = (self.height(), self.width());
+        if cfg!(target_endian = "little") {
+            // SAFETY: every bit pattern is a valid u16, and `align_to` only
+            // returns a non-empty middle slice for correctly aligned data.
+            let (head, samples, tail) = unsafe { self.data.align_to::<u16>() };
+            if head.is_empty() && tail.is_empty() {
+                let view =
+                    ArrayView2::from_shape(shape, samples).map_err(|_| Error::InvalidSize)?;
+                return Ok(CowArray::from(view));
+            }
+        }
+        let samples: Vec<u16> = self
+            .data
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect();
+        let owned = Array2::from_shape_vec(shape, samples).map_err(|_| Error::InvalidSize)?;
+        Ok(CowArray::from(owned))
+    }
+
+    /// Borrows an `Rgb24` / `Rgb32` frame as a `[row, column, channel]` array
+    /// with 3 or 4 channels in BGR / BGRA order.
+    ///
+    /// Returns [`Error::InvalidImageType`] for single-channel formats.
+    pub fn as_array3(&self) -> Result<ArrayView3<'_, u8>> {
+        if self.image_type.channel_order().is_none() {
+            return Err(Error::InvalidImageType);
+        }
+        ArrayView3::from_shape((self.height(), self.width(), self.channels()), &self.data)
+            .map_err(|_| Error::InvalidSize)
+    }
+}
+
+impl Camera {
+    /// Reads one frame directly into `array` (8-bit single-channel formats).
+    ///
+    /// `array` must have shape `(height, width)` matching the current ROI and
+    /// be in standard (row-major, contiguous) layout.
+    pub fn get_frame_into_array_u8(&self, array: &mut Array2<u8>, wait_ms: i32) -> Result<()> {
+        self.check_array_target(array.dim(), 1)?;
+        let buf = array.as_slice_mut().ok_or_else(non_contiguous)?;
+        self.get_frame(buf, wait_ms)
+    }
+
+    /// Reads one frame directly into `array` (10- to 16-bit single-channel
+    /// formats), decoding the SDK's little-endian samples in place.
+    ///
+    /// `array` must have shape `(height, width)` matching the current ROI and
+    /// be in standard (row-major, contiguous) layout.
+    pub fn get_frame_into_array_u16(&self, array: &mut Array2<u16>, wait_ms: i32) -> Result<()> {
+        self.check_array_target(array.dim(), 2)?;
+        let samples = array.as_slice_mut().ok_or_else(non_contiguous)?;
+        // SAFETY: u8 has no alignment requirement and the byte slice covers
+        // exactly the memory of `samples`, which stays borrowed meanwhile.
+        let bytes = unsafe {
+            std::slice::from_raw_parts_mut(samples.as_mut_ptr() as *mut u8, samples.len() * 2)
+        };
+        self.get_frame(bytes, wait_ms)?;
+        decode_le_in_place(samples);
+        Ok(())
+    }
+
+    fn check_array_target(&self, dim: (usize, usize), bytes_per_pixel: usize) -> Result<()> {
+        let ty = self.output_image_type()?;
+        if ty.channels() != 1 || ty.bytes_per_pixel() != bytes_per_pixel {
+            return Err(Error::InvalidImageType);
+        }
+        let roi = self.roi()?;
+        if dim != (roi.height as usize, roi.width as usize) {
+            return Err(Error::InvalidSize);
+        }
+        Ok(())
+    }
+}
+
+fn non_contiguous() -> Error {
+    Error::InvalidArgument("array must be in standard contiguous layout".into())
+}
+
+/// Converts samples read as raw little-endian bytes to native endianness.
+fn decode_le_in_place(samples: &mut [u16]) {
+    if cfg!(target_endian = "big") {
+        for v in samples {
+            *v = u16::from_le(*v);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ImageType, RoiFormat};
+
+    fn roi(w: i32, h: i32) -> RoiFormat {
+        RoiFormat {
+            start_x: 0,
+            start_y: 0,
+            width: w,
+            height: h,
+            bin: 1,
+        }
+    }
+
+    #[test]
+    fn u8_view_is_zero_copy() {
+        let f = Frame::new(roi(3, 2), ImageType::Y8, vec![1, 2, 3, 4, 5, 6]).unwrap();
+        let a = f.as_array_u8().unwrap();
+        assert_eq!(a.dim(), (2, 3));
+        assert_eq!(a[[1, 0]], 4);
+        assert_eq!(a.as_ptr(), f.data.as_ptr());
+        assert_eq!(f.as_array_u16().unwrap_err(), Error::InvalidImageType);
+    }
+
+    #[test]
+    fn u16_view_decodes_little_endian() {
+        let data: Vec<u8> = [1u16, 2, 0x1234, 65535]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        let f = Frame::new(roi(2, 2), ImageType::Raw16, data).unwrap();
+        let a = f.as_array_u16().unwrap();
+        assert_eq!(a.dim(), (2, 2));
+        assert_eq!(a[[1, 0]], 0x1234);
+        assert_eq!(a[[1, 1]], 65535);
+    }
+
+    #[test]
+    fn rgb_view_has_channel_axis() {
+        let f = Frame::new(roi(2, 1), ImageType::Rgb32, (0..8).collect()).unwrap();
+        let a = f.as_array3().unwrap();
+        assert_eq!(a.dim(), (1, 2, 4));
+        assert_eq!(a[[0, 1, 2]], 6);
+        let mono = Frame::zeroed(roi(2, 2), ImageType::Y16);
+        assert_eq!(mono.as_array3().unwrap_err(), Error::InvalidImageType);
+    }
+}
@@ -0,0 +1,235 @@
+//! Aggregated description of what a camera can do.
+
+use crate::{
+    Camera, CameraMode, CameraProperty, CameraPropertyEx, ControlCaps, ControlType, ImageType,
+    Result,
+};
+
+/// Everything a camera reports about its features, gathered in one place.
+///
+/// Returned by [`Camera::capabilities`]. Queries that some models reject are
+/// stored as `None` (or empty) instead of failing, so the query methods
+/// always answer. With the `serde` feature the descriptor can be logged and
+/// compared across units.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Capabilities {
+    /// Sensor properties.
+    pub property: CameraProperty,
+    /// Extended properties, if the camera reports them.
+    pub property_ex: Option<CameraPropertyEx>,
+    /// Sensor pixel size in microns, if the camera reports it.
+    pub pixel_size_um: Option<f32>,
+    /// Firmware version, if the camera reports it.
+    pub firmware_version: Option<String>,
+    /// Supported camera modes. Empty for cameras without trigger support.
+    pub modes: Vec<CameraMode>,
+    /// `true` if the camera has an ST4 guide port.
+    pub st4: bool,
+    /// `true` if the camera supports the extended ROI calls and
+    /// [`BinMode`](crate::BinMode).
+    pub bin_mode: bool,
+    /// Capabilities of every control, in the camera's enumeration order.
+    pub controls: Vec<ControlCaps>,
+}
+
+impl Capabilities {
+    /// Returns the caps of `ctrl`, or `None` if the camera does not have it.
+    pub fn control(&self, ctrl: ControlType) -> Option<&ControlCaps> {
+        self.controls.iter().find(|c| c.control_type == ctrl)
+    }
+
+    /// Returns `true` if the camera has control `ctrl`.
+    pub fn has_control(&self, ctrl: ControlType) -> bool {
+        self.control(ctrl).is_some()
+    }
+
+    /// Returns `true` if the camera has a TEC cooler.
+    pub fn has_cooler(&self) -> bool {
+        self.has_control(ControlType::CoolerEnable)
+            || self.has_control(ControlType::TargetTemperature)
+    }
+
+    /// Returns `true` if the camera reports its sensor temperature.
+    pub fn has_temperature_sensor(&self) -> bool {
+        self.has_control(ControlType::CurrentTemperature)
+            || self
+                .property_ex
+                .as_ref()
+                .map_or(false, |p| p.supports_temp_control)
+    }
+
+    /// Returns `true` if the camera has an ST4 guide port.
+    pub fn has_st4(&self) -> bool {
+        self.st4
+    }
+
+    /// Returns `true` if the camera can run in `mode`.
+    ///
+    /// [`CameraMode::Normal`] is always supported.
+    pub fn supports_trigger(&self, mode: CameraMode) -> bool {
+        mode == CameraMode::Normal || self.modes.contains(&mode)
+    }
+
+    /// Returns `true` if `ty` is one of the camera's output formats.
+    pub fn supports_format(&self, ty: ImageType) -> bool {
+        self.property.supported_formats.contains(&ty)
+    }
+
+    /// Returns `true` if `bin` is one of the camera's binning factors.
+    pub fn supports_bin(&self, bin: i32) -> bool {
+        self.property.supported_bins.contains(&bin)
+    }
+
+    /// Returns the largest supported binning factor (1 if none are listed).
+    pub fn max_bin(&self) -> i32 {
+        self.property
+            .supported_bins
+            .iter()
+            .copied()
+            .max()
+            .unwrap_or(1)
+    }
+
+    /// Returns `true` if the camera supports a selectable binning mode.
+    pub fn supports_bin_mode(&self) -> bool {
+        self.bin_mode
+    }
+
+    /// Returns `true` if the camera has on-camera bad pixel correction.
+    pub fn has_bad_pixel_correction(&self) -> bool {
+        self.has_control(ControlType::BadPixelCorrEnable)
+    }
+
+    /// Returns `true` if the camera supports automatic exposure.
+    pub fn has_auto_exposure(&self) -> bool {
+        self.control(ControlType::Exposure)
+            .map_or(false, |c| c.is_auto_supported)
+    }
+}
+
+impl Camera {
+    /// Returns the camera's [`Capabilities`].
+    ///
+    /// Gathered on the first call and cached for the lifetime of the handle.
+    /// Only a failure to read [`property`](Self::property) is an error; the
+    /// other queries are optional.
+    pub fn capabilities(&self) -> Result<Capabilities> {
+        let mut cached = self.capabilities.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(caps) = &*cached {
+            return Ok(caps.clone());
+        }
+        let caps = self.read_capabilities()?;
+        *cached = Some(caps.clone());
+        Ok(caps)
+    }
+
+    fn read_capabilities(&self) -> Result<Capabilities> {
+        let property = self.property()?;
+        let property_ex = self.property_ex().ok();
+        let modes = if property.is_trigger_cam {
+            self.supported_modes().unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+        let st4 = self
+            .can_pulse_guide()
+            .ok()
+            .or_else(|| property_ex.as_ref().map(|p| p.supports_pulse_guide))
+            .unwrap_or(false);
+        Ok(Capabilities {
+            property,
+            property_ex,
+            pixel_size_um: self.pixel_size().ok(),
+            firmware_version: self.firmware_version().ok(),
+            modes,
+            st4,
+            bin_mode: self.supports_bin_mode(),
+            controls: self.supported_controls().to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BayerPattern;
+
+    fn control(control_type: ControlType, is_auto_supported: bool) -> ControlCaps {
+        ControlCaps {
+            name: format!("{control_type:?}"),
+            description: String::new(),
+            max_value: 100,
+            min_value: 0,
+            default_value: 0,
+            is_auto_supported,
+            is_writable: true,
+            control_type,
+        }
+    }
+
+    fn capabilities() -> Capabilities {
+        Capabilities {
+            property: CameraProperty {
+                max_width: 3008,
+                max_height: 3008,
+                is_color: true,
+                bayer_pattern: Some(BayerPattern::Rg),
+                supported_bins: vec![1, 2, 4],
+                supported_formats: vec![ImageType::Raw8, ImageType::Raw16],
+                max_bit_depth: 14,
+                is_trigger_cam: true,
+            },
+            property_ex: Some(CameraPropertyEx {
+                supports_pulse_guide: false,
+                supports_temp_control: true,
+            }),
+            pixel_size_um: Some(3.76),
+            firmware_version: None,
+            modes: vec![CameraMode::Normal, CameraMode::TrigSoft],
+            st4: false,
+            bin_mode: true,
+            controls: vec![
+                control(ControlType::Exposure, true),
+                control(ControlType::CoolerEnable, false),
+                control(ControlType::CurrentTemperature, false),
+            ],
+        }
+    }
+
+    #[test]
+    fn queries() {
+        let caps = capabilities();
+        assert!(caps.has_cooler());
+        assert!(caps.has_temperature_sensor());
+        assert!(!caps.has_st4());
+        assert!(caps.supports_trigger(CameraMode::TrigSoft));
+        assert!(!caps.supports_trigger(CameraMode::TrigRiseEdge));
+        assert!(caps.supports_format(ImageType::Raw16));
+        assert!(!caps.supports_format(ImageType::Rgb24));
+        assert_eq!(caps.max_bin(), 4);
+        assert!(caps.supports_bin(2) && !caps.supports_bin(3));
+        assert!(caps.supports_bin_mode());
+        assert!(!caps.has_bad_pixel_correction());
+        assert!(caps.has_auto_exposure());
+        assert_eq!(caps.control(ControlType::Gain), None);
+    }
+
+    #[test]
+    fn normal_mode_is_always_supported() {
+        let mut caps = capabilities();
+        caps.modes.clear();
+        caps.property.supported_bins.clear();
+        assert!(caps.supports_trigger(CameraMode::Normal));
+        assert_eq!(caps.max_bin(), 1);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn round_trips_through_json() {
+        let caps = capabilities();
+        let json = serde_json::to_string(&caps).unwrap();
+        let back: Capabilities = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, caps);
+    }
+}
@@ -0,0 +1,378 @@
+//! Control enumeration and typed accessors for controls with physical units.
+//!
+//! [`Camera::controls`] returns a [`ControlMap`] of every control the camera
+//! exposes, with its caps and last known value.
+//!
+//! [`Camera::get_control`] and [`Camera::set_control`] work on the SDK's raw
+//! integers. The typed methods here convert to and from [`Duration`],
+//! [`Celsius`], [`Percent`] and the crate's enums, and reject values that
+//! cannot be represented before anything is sent to the camera.
+
+use std::fmt;
+use std::os::raw::{c_int, c_long};
+use std::time::Duration;
+
+use crate::{Camera, ControlCaps, ControlType, Error, FlipStatus, FrameSpeed, Result};
+
+/// Value and auto-mode flag of a control.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ControlValue {
+    /// Raw value in SDK units.
+    pub value: i64,
+    /// Whether the camera adjusts the control automatically.
+    pub auto_: bool,
+}
+
+/// One control in a [`ControlMap`].
+#[derive(Debug, Clone)]
+pub struct ControlEntry {
+    /// Name, range and flags reported by the SDK.
+    pub caps: ControlCaps,
+    /// Value at the last refresh or write through this handle; `None` if the
+    /// camera did not report one.
+    pub current: Option<ControlValue>,
+}
+
+impl ControlEntry {
+    /// Returns the control's type.
+    pub fn control_type(&self) -> ControlType {
+        self.caps.control_type
+    }
+}
+
+/// Every control a camera exposes, in the camera's enumeration order.
+///
+/// Controls this crate does not recognise are kept as
+/// [`ControlType::Other`] with the name and description the SDK reports.
+#[derive(Debug, Clone, Default)]
+pub struct ControlMap {
+    entries: Vec<ControlEntry>,
+}
+
+impl ControlMap {
+    /// Returns the entry for `ctrl`.
+    pub fn get(&self, ctrl: ControlType) -> Option<&ControlEntry> {
+        self.entries.iter().find(|e| e.caps.control_type == ctrl)
+    }
+
+    /// Returns the caps of `ctrl`.
+    pub fn caps(&self, ctrl: ControlType) -> Option<&ControlCaps> {
+        self.get(ctrl).map(|e| &e.caps)
+    }
+
+    /// Returns the last known value of `ctrl`.
+    pub fn value(&self, ctrl: ControlType) -> Option<ControlValue> {
+        self.get(ctrl).and_then(|e| e.current)
+    }
+
+    /// Returns `true` if the camera exposes `ctrl`.
+    pub fn contains(&self, ctrl: ControlType) -> bool {
+        self.get(ctrl).is_some()
+    }
+
+    /// Iterates over the controls in enumeration order.
+    pub fn iter(&self) -> std::slice::Iter<'_, ControlEntry> {
+        self.entries.iter()
+    }
+
+    /// Returns the controls whose type this crate does not recognise.
+    pub fn unrecognized(&self) -> impl Iterator<Item = &ControlEntry> {
+        self.entries
+            .iter()
+            .filter(|e| matches!(e.caps.control_type, ControlType::Other(_)))
+    }
+
+    /// Number of controls.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if the camera exposes no controls.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl<'a> IntoIterator for &'a ControlMap {
+    type Item = &'a ControlEntry;
+    type IntoIter = std::slice::Iter<'a, ControlEntry>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter()
+    }
+}
+
+/// A temperature in degrees Celsius.
+///
+/// The SDK reports temperatures in tenths of a degree; conversions round to
+/// that resolution.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Celsius(pub f64);
+
+impl Celsius {
+    /// Converts an SDK value in tenths of a degree.
+    pub fn from_tenths(tenths: i64) -> Self {
+        Celsius(tenths as f64 / 10.0)
+    }
+
+    /// Converts to the SDK's tenths of a degree, rounding to nearest.
+    ///
+    /// Returns [`Error::InvalidArgument`] for NaN, infinite or out-of-range
+    /// temperatures.
+    pub fn to_tenths(self) -> Result<i64> {
+        let tenths = (self.0 * 10.0).round();
+        if !tenths.is_finite() || tenths.abs() > c_long::MAX as f64 {
+            return Err(Error::InvalidArgument(format!(
+                "temperature {} C is not representable",
+                self.0
+            )));
+        }
+        Ok(tenths as i64)
+    }
+}
+
+impl fmt::Display for Celsius {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.1} °C", self.0)
+    }
+}
+
+/// A percentage between 0 and 100.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Percent(pub u8);
+
+impl Percent {
+    /// Converts an SDK value, clamping it to `0..=100`.
+    pub fn from_raw(value: i64) -> Self {
+        Percent(value.clamp(0, 100) as u8)
+    }
+
+    /// Returns the value as a fraction in `0.0..=1.0`.
+    pub fn fraction(self) -> f64 {
+        f64::from(self.0) / 100.0
+    }
+}
+
+impl fmt::Display for Percent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} %", self.0)
+    }
+}
+
+/// Converts an exposure time to the SDK's microseconds.
+///
+/// Sub-microsecond fractions are truncated. Returns
+/// [`Error::InvalidArgument`] for exposures shorter than 1 µs or too long to
+/// pass to the SDK.
+fn exposure_to_us(exposure: Duration) -> Result<i64> {
+    let us = exposure.as_micros();
+    if us == 0 || us > c_long::MAX as u128 {
+        return Err(Error::InvalidArgument(format!(
+            "exposure {exposure:?} is outside 1 µs..={} µs",
+            c_long::MAX
+        )));
+    }
+    Ok(us as i64)
+}
+
+impl Camera {
+    /// Returns every control with its caps and the value cached at open, by
+    /// [`refresh_controls`](Self::refresh_controls), or by the last
+    /// [`set_control`](Self::set_control) through this handle.
+    ///
+    /// Values changed by the camera itself (auto modes, temperatures) are
+    /// only updated by a refresh.
+    pub fn controls(&self) -> ControlMap {
+        let values = self
+            .control_values
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        ControlMap {
+            entries: self
+                .caps
+                .iter()
+                .zip(values.iter())
+                .map(|(caps, &current)| ControlEntry {
+                    caps: caps.clone(),
+                    current,
+                })
+                .collect(),
+        }
+    }
+
+    /// Re-reads the value of every control and returns the updated map.
+    pub fn refresh_controls(&self) -> ControlMap {
+        let values = self.read_control_values();
+        *self
+            .control_values
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) = values;
+        self.controls()
+    }
+
+    /// Reads the current value of every cached control.
+    pub(crate) fn read_control_values(&self) -> Vec<Option<ControlValue>> {
+        self.caps
+            .iter()
+            .map(|caps| {
+                self.get_control(caps.control_type)
+                    .ok()
+                    .map(|(value, auto_)| ControlValue { value, auto_ })
+            })
+            .collect()
+    }
+
+    /// Records a successful write in the control cache.
+    pub(crate) fn cache_control_value(&self, ctrl: ControlType, value: i64, auto_: bool) {
+        if let Some(i) = self.caps.iter().position(|c| c.control_type == ctrl) {
+            let mut values = self
+                .control_values
+                .lock()
+                .unwrap_or_else(|e| e.into_inner());
+            values[i] = Some(ControlValue { value, auto_ });
+        }
+    }
+
+    /// Returns the exposure time ([`ControlType::Exposure`]).
+    pub fn exposure(&self) -> Result<Duration> {
+        let (us, _) = self.get_control(ControlType::Exposure)?;
+        Ok(Duration::from_micros(us.max(0) as u64))
+    }
+
+    /// Sets a manual exposure time, truncated to whole microseconds.
+    pub fn set_exposure(&self, exposure: Duration) -> Result<()> {
+        self.set_control(ControlType::Exposure, exposure_to_us(exposure)?, false)
+    }
+
+    /// Returns the sensor temperature ([`ControlType::CurrentTemperature`]).
+    pub fn sensor_temperature(&self) -> Result<Celsius> {
+        let (tenths, _) = self.get_control(ControlType::CurrentTemperature)?;
+        Ok(Celsius::from_tenths(tenths))
+    }
+
+    /// Returns the cooler set point ([`ControlType::TargetTemperature`]).
+    pub fn target_temperature(&self) -> Result<Celsius> {
+        let (tenths, _) = self.get_control(ControlType::TargetTemperature)?;
+        Ok(Celsius::from_tenths(tenths))
+    }
+
+    /// Sets the cooler set point, rounded to 0.1 °C.
+    pub fn set_target_temperature(&self, target: Celsius) -> Result<()> {
+        self.set_control(ControlType::TargetTemperature, target.to_tenths()?, false)
+    }
+
+    /// Returns whether the cooler is on ([`ControlType::CoolerEnable`]).
+    pub fn cooler_enabled(&self) -> Result<bool> {
+        Ok(self.get_control(ControlType::CoolerEnable)?.0 != 0)
+    }
+
+    /// Switches the cooler on or off.
+    pub fn set_cooler_enabled(&self, enabled: bool) -> Result<()> {
+        self.set_control(ControlType::CoolerEnable, i64::from(enabled), false)
+    }
+
+    /// Returns the cooler drive level ([`ControlType::CoolerPower`]).
+    pub fn cooler_power(&self) -> Result<Percent> {
+        Ok(Percent::from_raw(
+            self.get_control(ControlType::CoolerPower)?.0,
+        ))
+    }
+
+    /// Returns the readout speed ([`ControlType::FrameSpeedMode`]).
+    pub fn frame_speed(&self) -> Result<FrameSpeed> {
+        let (value, _) = self.get_control(ControlType::FrameSpeedMode)?;
+        Ok(FrameSpeed::from(value as c_int))
+    }
+
+    /// Sets the readout speed.
+    pub fn set_frame_speed(&self, speed: FrameSpeed) -> Result<()> {
+        self.set_control(ControlType::FrameSpeedMode, i64::from(speed.raw()), false)
+    }
+
+    /// Returns the image flip state ([`ControlType::Flip`]).
+    pub fn flip(&self) -> Result<FlipStatus> {
+        let (value, _) = self.get_control(ControlType::Flip)?;
+        Ok(FlipStatus::from(value as c_int))
+    }
+
+    /// Sets the image flip state.
+    pub fn set_flip(&self, flip: FlipStatus) -> Result<()> {
+        self.set_control(ControlType::Flip, i64::from(flip.raw()), false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(control_type: ControlType, name: &str, value: i64) -> ControlEntry {
+        ControlEntry {
+            caps: ControlCaps {
+                name: name.into(),
+                description: String::new(),
+                max_value: 100,
+                min_value: 0,
+                default_value: 0,
+                is_auto_supported: false,
+                is_writable: true,
+                control_type,
+            },
+            current: Some(ControlValue {
+                value,
+                auto_: false,
+            }),
+        }
+    }
+
+    #[test]
+    fn control_map_lookup() {
+        let map = ControlMap {
+            entries: vec![
+                entry(ControlType::Gain, "Gain", 10),
+                entry(ControlType::Other(42), "Fan", 1),
+            ],
+        };
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.value(ControlType::Gain).unwrap().value, 10);
+        assert!(!map.contains(ControlType::Exposure));
+        let unknown: Vec<&str> = map.unrecognized().map(|e| e.caps.name.as_str()).collect();
+        assert_eq!(unknown, ["Fan"]);
+        assert_eq!(map.caps(ControlType::Other(42)).unwrap().name, "Fan");
+        assert_eq!((&map).into_iter().count(), 2);
+    }
+
+    #[test]
+    fn celsius_round_trips_tenths() {
+        assert_eq!(Celsius::from_tenths(-105), Celsius(-10.5));
+        assert_eq!(Celsius(-10.5).to_tenths().unwrap(), -105);
+        assert_eq!(Celsius(25.04).to_tenths().unwrap(), 250);
+        assert_eq!(Celsius(25.06).to_tenths().unwrap(), 251);
+        assert!(Celsius(f64::NAN).to_tenths().is_err());
+        assert!(Celsius(f64::INFINITY).to_tenths().is_err());
+        assert_eq!(Celsius(-5.0).to_string(), "-5.0 °C");
+    }
+
+    #[test]
+    fn percent_is_clamped() {
+        assert_eq!(Percent::from_raw(42), Percent(42));
+        assert_eq!(Percent::from_raw(-3), Percent(0));
+        assert_eq!(Percent::from_raw(250), Percent(100));
+        assert_eq!(Percent(25).fraction(), 0.25);
+    }
+
+    #[test]
+    fn exposure_is_validated() {
+        assert_eq!(exposure_to_us(Duration::from_millis(20)).unwrap(), 20_000);
+        assert_eq!(exposure_to_us(Duration::from_nanos(1_999)).unwrap(), 1);
+        assert!(exposure_to_us(Duration::from_nanos(999)).is_err());
+        assert!(exposure_to_us(Duration::from_secs(u64::MAX)).is_err());
+    }
+
+    #[test]
+    fn frame_speed_from_raw() {
+        assert_eq!(FrameSpeed::from(2), FrameSpeed::High);
+        assert_eq!(FrameSpeed::from(3), FrameSpeed::Other(3));
+    }
+}
@@ -0,0 +1,709 @@
+//! Controlled cool-down and warm-up of TEC-cooled cameras.
+//!
+//! Stepping the cooler set point straight to its final value cools the
+//! sensor as fast as the TEC allows, which risks condensation on the sensor
+//! window and thermal stress. [`Cooler`] instead ramps
+//! [`ControlType::TargetTemperature`] at a fixed rate, waits until the sensor
+//! temperature has stayed within a tolerance of the target for a dwell time,
+//! and at the end of a session ramps back up before switching the cooler off.
+//!
+//! ```no_run
+//! use svbony::{Camera, Celsius};
+//! use svbony::cooler::{Cooler, CoolerConfig, RampOutcome};
+//!
+//! let cam = Camera::open(0)?;
+//! let mut cooler = Cooler::new(&cam, CoolerConfig::default())?;
+//! let outcome = cooler.cool_to(Celsius(-10.0), |s| {
+//!     println!("{:?}: {} (set point {})", s.phase, s.temperature, s.setpoint);
+//!     Ok(())
+//! })?;
+//! if let RampOutcome::TimedOut { temperature, .. } = outcome {
+//!     println!("not stable, sensor at {temperature}");
+//! }
+//! // ... imaging session ...
+//! cooler.warm_up(|_| Ok(()))?;
+//! # Ok::<(), svbony::Error>(())
+//! ```
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{Camera, Celsius, ControlType, Error, Percent, Result};
+
+/// Settings for [`Cooler`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoolerConfig {
+    /// Set point ramp rate in °C per minute, for both cooling and warming.
+    pub rate_per_min: f64,
+    /// Maximum deviation from the target that counts as on target, in °C.
+    pub tolerance: f64,
+    /// How long the temperature must stay within tolerance to be stable.
+    pub dwell: Duration,
+    /// Interval between temperature readings and set point updates.
+    pub poll_interval: Duration,
+    /// How long to wait for stability after the ramp has finished.
+    pub settle_timeout: Duration,
+    /// Temperature to ramp to before switching the cooler off. `None` uses
+    /// the sensor temperature recorded when [`Cooler::cool_to`] switched the
+    /// cooler on, or [`DEFAULT_WARM_UP_TARGET`] if it was already on.
+    pub warm_up_target: Option<Celsius>,
+}
+
+impl Default for CoolerConfig {
+    fn default() -> Self {
+        Self {
+            rate_per_min: 2.0,
+            tolerance: 0.5,
+            dwell: Duration::from_secs(60),
+            poll_interval: Duration::from_secs(5),
+            settle_timeout: Duration::from_secs(10 * 60),
+            warm_up_target: None,
+        }
+    }
+}
+
+/// Warm-up target used when neither [`CoolerConfig::warm_up_target`] nor an
+/// ambient reading is available.
+pub const DEFAULT_WARM_UP_TARGET: Celsius = Celsius(20.0);
+
+/// What the cooler is doing, as reported in [`CoolerStatus`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoolerPhase {
+    /// The set point is being lowered towards the target.
+    CoolingDown,
+    /// The set point is being raised towards the target.
+    WarmingUp,
+    /// The set point has reached the target; waiting for the sensor.
+    Settling,
+    /// The sensor has stayed within tolerance for the dwell time.
+    Stable,
+    /// The cooler has been switched off.
+    Off,
+}
+
+/// Progress report passed to the [`Cooler`] callbacks on every poll.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CoolerStatus {
+    /// What the manager is doing.
+    pub phase: CoolerPhase,
+    /// Set point currently written to the camera.
+    pub setpoint: Celsius,
+    /// Final target of the ramp.
+    pub target: Celsius,
+    /// Sensor temperature.
+    pub temperature: Celsius,
+    /// Cooler drive level, if the camera reports it.
+    pub power: Option<Percent>,
+    /// Time since the ramp started.
+    pub elapsed: Duration,
+    /// How long the temperature has been within tolerance of the target.
+    pub stable_for: Duration,
+}
+
+/// Result of [`Cooler::cool_to`] and [`Cooler::warm_up`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RampOutcome {
+    /// The temperature settled within tolerance of the target.
+    Stable {
+        /// Sensor temperature when it settled.
+        temperature: Celsius,
+        /// Time from the start of the ramp.
+        elapsed: Duration,
+    },
+    /// The temperature did not settle within
+    /// [`CoolerConfig::settle_timeout`] after the ramp.
+    TimedOut {
+        /// Last sensor temperature read.
+        temperature: Celsius,
+        /// Time from the start of the ramp.
+        elapsed: Duration,
+    },
+}
+
+/// A linear set point ramp.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ramp {
+    /// Set point at the start.
+    pub from: Celsius,
+    /// Set point at the end.
+    pub to: Celsius,
+    /// Rate in °C per minute; must be positive.
+    pub rate_per_min: f64,
+}
+
+impl Ramp {
+    /// Time the ramp takes to reach [`to`](Self::to).
+    pub fn duration(&self) -> Duration {
+        let minutes = (self.to.0 - self.from.0).abs() / self.rate_per_min;
+        if minutes.is_finite() {
+            Duration::from_secs_f64(minutes * 60.0)
+        } else {
+            Duration::ZERO
+        }
+    }
+
+    /// Set point `elapsed` after the start of the ramp.
+    pub fn setpoint_at(&self, elapsed: Duration) -> Celsius {
+        let step = self.rate_per_min * elapsed.as_secs_f64() / 60.0;
+        if self.to.0 < self.from.0 {
+            Celsius((self.from.0 - step).max(self.to.0))
+        } else {
+            Celsius((self.from.0 + step).min(self.to.0))
+        }
+    }
+
+    /// Returns `true` once the set point has reached the target.
+    pub fn is_complete(&self, elapsed: Duration) -> bool {
+        elapsed >= self.duration()
+    }
+
+    /// The ramp with both ends moved into the set point range `limits`.
+    fn clamped(self, limits: (Celsius, Celsius)) -> Self {
+        Self {
+            from: clamp(self.from, limits),
+            to: clamp(self.to, limits),
+            ..self
+        }
+    }
+}
+
+/// Moves `t` into `min..=max`.
+fn clamp(t: Celsius, (min, max): (Celsius, Celsius)) -> Celsius {
+    Celsius(t.0.max(min.0).min(max.0))
+}
+
+/// Tracks how long a temperature has stayed within tolerance of a target.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StabilityTracker {
+    tolerance: f64,
+    dwell: Duration,
+    since: Option<Duration>,
+}
+
+impl StabilityTracker {
+    /// Creates a tracker that reports stability once readings have stayed
+    /// within `tolerance` °C of the target for `dwell`.
+    pub fn new(tolerance: f64, dwell: Duration) -> Self {
+        Self {
+            tolerance,
+            dwell,
+            since: None,
+        }
+    }
+
+    /// Records a reading taken at `now` and returns `true` if the
+    /// temperature has been within tolerance for at least the dwell time.
+    /// Any reading outside the tolerance restarts the dwell.
+    pub fn update(&mut self, now: Duration, temperature: Celsius, target: Celsius) -> bool {
+        if (temperature.0 - target.0).abs() <= self.tolerance {
+            let since = *self.since.get_or_insert(now);
+            now.saturating_sub(since) >= self.dwell
+        } else {
+            self.since = None;
+            false
+        }
+    }
+
+    /// How long the temperature has been within tolerance as of `now`.
+    pub fn stable_for(&self, now: Duration) -> Duration {
+        self.since.map_or(Duration::ZERO, |s| now.saturating_sub(s))
+    }
+}
+
+/// Cool-down / warm-up manager for one camera.
+pub struct Cooler<'a> {
+    camera: &'a Camera,
+    config: CoolerConfig,
+    /// Sensor temperature when `cool_to` switched the cooler on.
+    ambient: Option<Celsius>,
+}
+
+impl<'a> Cooler<'a> {
+    /// Creates a manager for `camera`.
+    ///
+    /// Returns [`Error::Unsupported`] if the camera has no cooler and
+    /// [`Error::InvalidArgument`] for a non-positive rate or poll interval or
+    /// a negative tolerance.
+    pub fn new(camera: &'a Camera, config: CoolerConfig) -> Result<Self> {
+        let has_cooler = [
+            ControlType::CoolerEnable,
+            ControlType::TargetTemperature,
+            ControlType::CurrentTemperature,
+        ]
+        .into_iter()
+        .all(|c| camera.caps(c).is_some());
+        if !has_cooler {
+            return Err(Error::Unsupported("cooler".into()));
+        }
+        validate_config(&config)?;
+        Ok(Self {
+            camera,
+            config,
+            ambient: None,
+        })
+    }
+
+    /// Returns the manager's settings.
+    pub fn config(&self) -> &CoolerConfig {
+        &self.config
+    }
+
+    /// Sensor temperature recorded when [`cool_to`](Self::cool_to) switched
+    /// the cooler on.
+    pub fn ambient(&self) -> Option<Celsius> {
+        self.ambient
+    }
+
+    /// Ramps the set point from the current sensor temperature to `target`
+    /// and waits for the temperature to stabilise.
+    ///
+    /// Both ends of the ramp are clamped to the camera's set point range.
+    /// Switches the cooler on if needed. `on_status` is called after every
+    /// reading; returning an error from it stops the ramp and returns that
+    /// error, leaving the cooler at the current set point.
+    pub fn cool_to(
+        &mut self,
+        target: Celsius,
+        on_status: impl FnMut(&CoolerStatus) -> Result<()>,
+    ) -> Result<RampOutcome> {
+        let mut thermal = CameraThermal::new(self.camera);
+        let current = self.camera.sensor_temperature()?;
+        if !self.camera.cooler_enabled()? {
+            self.ambient = Some(current);
+            thermal.set_target(clamp(current, thermal.setpoint_range()))?;
+            self.camera.set_cooler_enabled(true)?;
+        }
+        let ramp = Ramp {
+            from: current,
+            to: target,
+            rate_per_min: self.config.rate_per_min,
+        };
+        drive(&mut thermal, ramp, &self.config, on_status)
+    }
+
+    /// Ramps the set point up to the warm-up target, waits for the sensor to
+    /// follow and switches the cooler off.
+    ///
+    /// As with [`cool_to`](Self::cool_to), the ramp is clamped to the
+    /// camera's set point range.
+    /// The cooler is switched off even if the temperature does not settle in
+    /// time; that case is reported as [`RampOutcome::TimedOut`]. Does nothing
+    /// but report the current temperature if the cooler is already off.
+    pub fn warm_up(
+        &mut self,
+        mut on_status: impl FnMut(&CoolerStatus) -> Result<()>,
+    ) -> Result<RampOutcome> {
+        let start = Instant::now();
+        let mut outcome = RampOutcome::Stable {
+            temperature: self.camera.sensor_temperature()?,
+            elapsed: Duration::ZERO,
+        };
+        if self.camera.cooler_enabled()? {
+            let target = self
+                .config
+                .warm_up_target
+                .or(self.ambient)
+                .unwrap_or(DEFAULT_WARM_UP_TARGET);
+            let ramp = Ramp {
+                from: self.camera.sensor_temperature()?,
+                to: target,
+                rate_per_min: self.config.rate_per_min,
+            };
+            outcome = drive(
+                &mut CameraThermal::new(self.camera),
+                ramp,
+                &self.config,
+                &mut on_status,
+            )?;
+            self.camera.set_cooler_enabled(false)?;
+        }
+        let temperature = self.camera.sensor_temperature()?;
+        on_status(&CoolerStatus {
+            phase: CoolerPhase::Off,
+            setpoint: temperature,
+            target: temperature,
+            temperature,
+            power: None,
+            elapsed: start.elapsed(),
+            stable_for: Duration::ZERO,
+        })?;
+        Ok(outcome)
+    }
+}
+
+fn validate_config(config: &CoolerConfig) -> Result<()> {
+    if !(config.rate_per_min > 0.0 && config.rate_per_min.is_finite()) {
+        return Err(Error::InvalidArgument(format!(
+            "cooler ramp rate must be positive, got {} °C/min",
+            config.rate_per_min
+        )));
+    }
+    if config.tolerance.is_nan() || config.tolerance < 0.0 {
+        return Err(Error::InvalidArgument(format!(
+            "cooler tolerance must not be negative, got {} °C",
+            config.tolerance
+        )));
+    }
+    if config.poll_interval.is_zero() {
+        return Err(Error::InvalidArgument(
+            "cooler poll interval must not be zero".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// The parts of a cooled camera [`drive`] needs, so the control loop can be
+/// tested against a simulated sensor and clock.
+trait Thermal {
+    fn now(&self) -> Duration;
+    fn sleep(&mut self, d: Duration);
+    fn temperature(&mut self) -> Result<Celsius>;
+    fn power(&mut self) -> Option<Percent>;
+    fn set_target(&mut self, target: Celsius) -> Result<()>;
+    /// Lowest and highest set point the camera accepts.
+    fn setpoint_range(&self) -> (Celsius, Celsius);
+}
+
+struct CameraThermal<'a> {
+    camera: &'a Camera,
+    epoch: Instant,
+}
+
+impl<'a> CameraThermal<'a> {
+    fn new(camera: &'a Camera) -> Self {
+        Self {
+            camera,
+            epoch: Instant::now(),
+        }
+    }
+}
+
+impl Thermal for CameraThermal<'_> {
+    fn now(&self) -> Duration {
+        self.epoch.elapsed()
+    }
+
+    fn sleep(&mut self, d: Duration) {
+        thread::sleep(d);
+    }
+
+    fn temperature(&mut self) -> Result<Celsius> {
+        self.camera.sensor_temperature()
+    }
+
+    fn power(&mut self) -> Option<Percent> {
+        self.camera.cooler_power().ok()
+    }
+
+    fn set_target(&mut self, target: Celsius) -> Result<()> {
+        self.camera.set_target_temperature(target)
+    }
+
+    fn setpoint_range(&self) -> (Celsius, Celsius) {
+        match self.camera.caps(ControlType::TargetTemperature) {
+            Some(caps) => (
+                Celsius::from_tenths(caps.min_value),
+                Celsius::from_tenths(caps.max_value),
+            ),
+            None => (Celsius(f64::NEG_INFINITY), Celsius(f64::INFINITY)),
+        }
+    }
+}
+
+/// Runs `ramp`, clamped to the device's set point range, and then waits for
+/// stability, polling every `config.poll_interval`.
+fn drive(
+    dev: &mut impl Thermal,
+    ramp: Ramp,
+    config: &CoolerConfig,
+    mut on_status: impl FnMut(&CoolerStatus) -> Result<()>,
+) -> Result<RampOutcome> {
+    let ramp = ramp.clamped(dev.setpoint_range());
+    let start = dev.now();
+    let deadline = ramp.duration() + config.settle_timeout;
+    let ramp_phase = if ramp.to.0 < ramp.from.0 {
+        CoolerPhase::CoolingDown
+    } else {
+        CoolerPhase::WarmingUp
+    };
+    let mut tracker = StabilityTracker::new(config.tolerance, config.dwell);
+    let mut written = None;
+    loop {
+        let elapsed = dev.now().saturating_sub(start);
+        // The camera only takes tenths of a degree; skip redundant writes.
+        let tenths = ramp.setpoint_at(elapsed).to_tenths()?;
+        let setpoint = Celsius::from_tenths(tenths);
+        if written != Some(tenths) {
+            dev.set_target(setpoint)?;
+            written = Some(tenths);
+        }
+
+        let temperature = dev.temperature()?;
+        let ramping = !ramp.is_complete(elapsed);
+        let stable = !ramping && tracker.update(elapsed, temperature, ramp.to);
+        let phase = if ramping {
+            ramp_phase
+        } else if stable {
+            CoolerPhase::Stable
+        } else {
+            CoolerPhase::Settling
+        };
+        on_status(&CoolerStatus {
+            phase,
+            setpoint,
+            target: ramp.to,
+            temperature,
+            power: dev.power(),
+            elapsed,
+            stable_for: tracker.stable_for(elapsed),
+        })?;
+
+        if stable {
+            return Ok(RampOutcome::Stable {
+                temperature,
+                elapsed,
+            });
+        }
+        if elapsed >= deadline {
+            return Ok(RampOutcome::TimedOut {
+                temperature,
+                elapsed,
+            });
+        }
+        dev.sleep(config.poll_interval);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sensor that follows the set point at a limited slew rate.
+    struct SimThermal {
+        now: Duration,
+        temperature: f64,
+        setpoint: f64,
+        slew_per_sec: f64,
+        writes: Vec<f64>,
+        range: (f64, f64),
+    }
+
+    impl SimThermal {
+        fn new(temperature: f64, slew_per_sec: f64) -> Self {
+            Self {
+                now: Duration::ZERO,
+                temperature,
+                setpoint: temperature,
+                slew_per_sec,
+                writes: Vec::new(),
+                range: (-50.0, 50.0),
+            }
+        }
+    }
+
+    impl Thermal for SimThermal {
+        fn now(&self) -> Duration {
+            self.now
+        }
+
+        fn sleep(&mut self, d: Duration) {
+            self.now += d;
+            let max = self.slew_per_sec * d.as_secs_f64();
+            self.temperature += (self.setpoint - self.temperature).clamp(-max, max);
+        }
+
+        fn temperature(&mut self) -> Result<Celsius> {
+            Ok(Celsius((self.temperature * 10.0).round() / 10.0))
+        }
+
+        fn power(&mut self) -> Option<Percent> {
+            None
+        }
+
+        fn set_target(&mut self, target: Celsius) -> Result<()> {
+            let (min, max) = self.range;
+            if !(min..=max).contains(&target.0) {
+                return Err(Error::InvalidArgument(format!("set point {target}")));
+            }
+            self.setpoint = target.0;
+            self.writes.push(target.0);
+            Ok(())
+        }
+
+        fn setpoint_range(&self) -> (Celsius, Celsius) {
+            (Celsius(self.range.0), Celsius(self.range.1))
+        }
+    }
+
+    fn config() -> CoolerConfig {
+        CoolerConfig {
+            rate_per_min: 5.0,
+            tolerance: 0.5,
+            dwell: Duration::from_secs(30),
+            poll_interval: Duration::from_secs(10),
+            settle_timeout: Duration::from_secs(300),
+            warm_up_target: None,
+        }
+    }
+
+    #[test]
+    fn ramp_setpoints() {
+        let down = Ramp {
+            from: Celsius(20.0),
+            to: Celsius(-10.0),
+            rate_per_min: 2.0,
+        };
+        assert_eq!(down.duration(), Duration::from_secs(15 * 60));
+        assert_eq!(down.setpoint_at(Duration::from_secs(5 * 60)), Celsius(10.0));
+        assert_eq!(
+            down.setpoint_at(Duration::from_secs(20 * 60)),
+            Celsius(-10.0)
+        );
+        assert!(!down.is_complete(Duration::from_secs(60)));
+        assert!(down.is_complete(Duration::from_secs(15 * 60)));
+
+        let up = Ramp {
+            from: Celsius(-10.0),
+            to: Celsius(20.0),
+            rate_per_min: 2.0,
+        };
+        assert_eq!(up.setpoint_at(Duration::from_secs(60)), Celsius(-8.0));
+        assert_eq!(up.setpoint_at(Duration::from_secs(3600)), Celsius(20.0));
+    }
+
+    #[test]
+    fn stability_needs_dwell_and_resets() {
+        let mut t = StabilityTracker::new(0.5, Duration::from_secs(60));
+        let target = Celsius(-10.0);
+        let s = Duration::from_secs;
+        assert!(!t.update(s(0), Celsius(-9.5), target));
+        assert!(!t.update(s(30), Celsius(-10.2), target));
+        assert_eq!(t.stable_for(s(30)), s(30));
+        assert!(!t.update(s(40), Celsius(-9.0), target));
+        assert_eq!(t.stable_for(s(40)), Duration::ZERO);
+        assert!(!t.update(s(50), Celsius(-10.0), target));
+        assert!(t.update(s(110), Celsius(-10.1), target));
+    }
+
+    #[test]
+    fn drive_ramps_then_settles() {
+        let mut sim = SimThermal::new(20.0, 0.2);
+        let ramp = Ramp {
+            from: Celsius(20.0),
+            to: Celsius(-10.0),
+            rate_per_min: 5.0,
+        };
+        let mut phases = Vec::new();
+        let outcome = drive(&mut sim, ramp, &config(), |s| {
+            phases.push(s.phase);
+            Ok(())
+        })
+        .unwrap();
+
+        let RampOutcome::Stable {
+            temperature,
+            elapsed,
+        } = outcome
+        else {
+            panic!("expected stable, got {outcome:?}");
+        };
+        assert!((temperature.0 + 10.0).abs() <= 0.5);
+        assert!(elapsed >= ramp.duration() + Duration::from_secs(30));
+
+        // Set points only move towards the target, by at most one poll's
+        // worth of ramp (plus rounding), and end on it.
+        let max_step = 5.0 * 10.0 / 60.0 + 0.1;
+        for w in sim.writes.windows(2) {
+            assert!(w[1] < w[0] && w[0] - w[1] <= max_step, "{w:?}");
+        }
+        assert_eq!(sim.writes.last(), Some(&-10.0));
+
+        assert_eq!(phases.first(), Some(&CoolerPhase::CoolingDown));
+        assert_eq!(phases.last(), Some(&CoolerPhase::Stable));
+        assert!(phases.contains(&CoolerPhase::Settling));
+    }
+
+    #[test]
+    fn drive_times_out_when_sensor_lags() {
+        let mut sim = SimThermal::new(20.0, 0.001);
+        let ramp = Ramp {
+            from: Celsius(20.0),
+            to: Celsius(10.0),
+            rate_per_min: 5.0,
+        };
+        let outcome = drive(&mut sim, ramp, &config(), |_| Ok(())).unwrap();
+        let RampOutcome::TimedOut { elapsed, .. } = outcome else {
+            panic!("expected timeout, got {outcome:?}");
+        };
+        assert!(elapsed >= ramp.duration() + config().settle_timeout);
+    }
+
+    #[test]
+    fn drive_warms_up_and_callback_can_abort() {
+        let mut sim = SimThermal::new(-10.0, 1.0);
+        let ramp = Ramp {
+            from: Celsius(-10.0),
+            to: Celsius(15.0),
+            rate_per_min: 5.0,
+        };
+        let mut polls = 0;
+        let err = drive(&mut sim, ramp, &config(), |s| {
+            assert_eq!(s.phase, CoolerPhase::WarmingUp);
+            polls += 1;
+            if polls == 3 {
+                Err(Error::InvalidSequence)
+            } else {
+                Ok(())
+            }
+        })
+        .unwrap_err();
+        assert_eq!(err, Error::InvalidSequence);
+        assert_eq!(sim.now, Duration::from_secs(20));
+        assert!(sim.writes.windows(2).all(|w| w[1] > w[0]));
+    }
+
+    #[test]
+    fn drive_clamps_ramp_to_setpoint_range() {
+        let mut sim = SimThermal::new(25.0, 1.0);
+        sim.range = (-20.0, 10.0);
+        let ramp = Ramp {
+            from: Celsius(25.0),
+            to: Celsius(-30.0),
+            rate_per_min: 5.0,
+        };
+        let mut targets = Vec::new();
+        let outcome = drive(&mut sim, ramp, &config(), |s| {
+            targets.push(s.target);
+            Ok(())
+        })
+        .unwrap();
+        assert!(matches!(outcome, RampOutcome::Stable { .. }), "{outcome:?}");
+        assert_eq!(sim.writes.first(), Some(&10.0));
+        assert_eq!(sim.writes.last(), Some(&-20.0));
+        assert!(targets.iter().all(|&t| t == Celsius(-20.0)));
+    }
+
+    #[test]
+    fn config_is_validated() {
+        assert!(validate_config(&CoolerConfig::default()).is_ok());
+        for config in [
+            CoolerConfig {
+                rate_per_min: 0.0,
+                ..config()
+            },
+            CoolerConfig {
+                tolerance: f64::NAN,
+                ..config()
+            },
+            CoolerConfig {
+                poll_interval: Duration::ZERO,
+                ..config()
+            },
+        ] {
+            assert!(matches!(
+                validate_config(&config),
+                Err(Error::InvalidArgument(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,945 @@
+//! Hot / cold pixel and bad column maps with software correction.
+//!
+//! [`ControlType::BadPixelCorrEnable`](crate::ControlType::BadPixelCorrEnable)
+//! is only available on some models and its behaviour is opaque. This module
+//! builds an explicit [`DefectMap`] from calibration frames instead:
+//!
+//! - a **dark** frame (shutter closed, long exposure) reveals hot and warm
+//!   pixels and bright columns;
+//! - a **flat** frame (evenly illuminated) reveals cold and dead pixels and
+//!   dark columns.
+//!
+//! Defects are stored in full-resolution sensor coordinates, so one map can be
+//! applied to frames captured with any ROI and binning. Maps can be persisted
+//! per camera serial number with [`DefectMap::save_for_serial`].
+//!
+//! ```no_run
+//! use svbony::*;
+//! use svbony::defect::{DefectMap, DefectThresholds};
+//!
+//! let cam = Camera::open(0)?;
+//! let prop = cam.property()?;
+//! // ... capture a full-frame, unbinned dark ...
+//! # let dark = cam.capture_frame(5000)?;
+//! let map = DefectMap::detect(&prop, Some(&dark), None, &DefectThresholds::default())?;
+//!
+//! let mut frame = cam.capture_frame(5000)?;
+//! map.correct(&mut frame);
+//! # Ok::<(), svbony::Error>(())
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use crate::stats::{mad, median, MAD_TO_SIGMA};
+use crate::{CameraProperty, Error, Frame, Result, RoiFormat};
+
+/// Classification of a defective pixel or column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum DefectKind {
+    /// Moderately elevated dark current.
+    Warm,
+    /// Strongly elevated dark current (or a stuck-high column).
+    Hot,
+    /// Reduced sensitivity in a flat frame.
+    Cold,
+    /// Little or no response to light.
+    Dead,
+}
+
+impl DefectKind {
+    /// Ranks kinds so the most damaging classification wins when a pixel is
+    /// flagged by both the dark and the flat analysis.
+    fn severity(self) -> u8 {
+        match self {
+            Self::Warm => 0,
+            Self::Cold => 1,
+            Self::Hot => 2,
+            Self::Dead => 3,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Warm => "warm",
+            Self::Hot => "hot",
+            Self::Cold => "cold",
+            Self::Dead => "dead",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "warm" => Some(Self::Warm),
+            "hot" => Some(Self::Hot),
+            "cold" => Some(Self::Cold),
+            "dead" => Some(Self::Dead),
+            _ => None,
+        }
+    }
+}
+
+/// A single defective pixel in full-resolution sensor coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Defect {
+    /// Sensor column.
+    pub x: u32,
+    /// Sensor row.
+    pub y: u32,
+    /// Defect classification.
+    pub kind: DefectKind,
+}
+
+/// A defective sensor column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BadColumn {
+    /// Sensor column.
+    pub x: u32,
+    /// [`Hot`](DefectKind::Hot) for bright columns found in darks,
+    /// [`Cold`](DefectKind::Cold) / [`Dead`](DefectKind::Dead) for dark
+    /// columns found in flats.
+    pub kind: DefectKind,
+}
+
+/// Detection thresholds used by [`DefectMap::detect`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DefectThresholds {
+    /// Dark pixels more than this many sigma above the median are hot.
+    pub hot_sigma: f64,
+    /// Dark pixels more than this many sigma above the median (but below
+    /// `hot_sigma`) are warm.
+    pub warm_sigma: f64,
+    /// Flat pixels below this fraction of the median are cold.
+    pub cold_fraction: f64,
+    /// Flat pixels below this fraction of the median are dead.
+    pub dead_fraction: f64,
+    /// Columns whose median deviates by more than this many sigma from the
+    /// other columns are flagged as bad.
+    pub column_sigma: f64,
+}
+
+impl Default for DefectThresholds {
+    fn default() -> Self {
+        Self {
+            hot_sigma: 10.0,
+            warm_sigma: 5.0,
+            cold_fraction: 0.7,
+            dead_fraction: 0.2,
+            column_sigma: 8.0,
+        }
+    }
+}
+
+/// Defects of a [`DefectMap`] translated into the coordinates of a frame.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FrameDefects {
+    /// `(x, y)` frame coordinates of defective pixels.
+    pub pixels: Vec<(usize, usize)>,
+    /// Frame columns that are defective.
+    pub columns: Vec<usize>,
+}
+
+/// Map of defective pixels and columns for one sensor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DefectMap {
+    /// Full sensor width the map was built for.
+    pub sensor_width: u32,
+    /// Full sensor height the map was built for.
+    pub sensor_height: u32,
+    /// `true` if the sensor has a Bayer filter; correction of raw frames then
+    /// only uses neighbours of the same color.
+    pub cfa: bool,
+    /// Defective pixels, sorted by row then column.
+    pub pixels: Vec<Defect>,
+    /// Defective columns, sorted by column.
+    pub columns: Vec<BadColumn>,
+}
+
+/// Floor for the robust sigma of dark frames, in ADU, so noiseless synthetic
+/// or heavily quantised data does not flag every non-median pixel.
+const MIN_DARK_SIGMA: f64 = 1.0;
+/// Floor for the robust sigma of column medians in darks, in ADU.
+const MIN_DARK_COLUMN_SIGMA: f64 = 0.5;
+/// Floor for the robust sigma of column medians in flats, as a fraction.
+const MIN_FLAT_COLUMN_SIGMA: f64 = 0.005;
+
+impl DefectMap {
+    /// Creates an empty map for the sensor described by `prop`.
+    pub fn new(prop: &CameraProperty) -> Self {
+        Self {
+            sensor_width: prop.max_width as u32,
+            sensor_height: prop.max_height as u32,
+            cfa: prop.is_color,
+            pixels: Vec::new(),
+            columns: Vec::new(),
+        }
+    }
+
+    /// Analyses a dark and/or flat frame and returns the resulting map.
+    ///
+    /// Both frames must be single-channel (`Raw*` or `Y*`) and unbinned. They
+    /// may be captured with a partial ROI; only the covered area is mapped.
+    /// Statistics for raw frames from color sensors are computed separately
+    /// for each of the four Bayer sites.
+    pub fn detect(
+        prop: &CameraProperty,
+        dark: Option<&Frame>,
+        flat: Option<&Frame>,
+        thresholds: &DefectThresholds,
+    ) -> Result<Self> {
+        let mut pixels = BTreeMap::new();
+        let mut columns = BTreeMap::new();
+        if let Some(dark) = dark {
+            let planes = Planes::new(prop, dark)?;
+            detect_dark(&planes, thresholds, &mut pixels, &mut columns);
+        }
+        if let Some(flat) = flat {
+            let planes = Planes::new(prop, flat)?;
+            detect_flat(&planes, thresholds, &mut pixels, &mut columns)?;
+        }
+
+        let mut map = Self::new(prop);
+        map.columns = columns
+            .into_iter()
+            .map(|(x, kind)| BadColumn { x, kind })
+            .collect();
+        let bad_columns: HashSet<u32> = map.columns.iter().map(|c| c.x).collect();
+        map.pixels = pixels
+            .into_iter()
+            .filter(|((_, x), _)| !bad_columns.contains(x))
+            .map(|((y, x), kind)| Defect { x, y, kind })
+            .collect();
+        Ok(map)
+    }
+
+    /// Adds the defects of `other` to this map, keeping the most severe
+    /// classification where both maps flag the same pixel or column.
+    pub fn merge(&mut self, other: &DefectMap) {
+        let mut pixels: BTreeMap<(u32, u32), DefectKind> = BTreeMap::new();
+        for d in self.pixels.iter().chain(&other.pixels) {
+            insert_worst(&mut pixels, (d.y, d.x), d.kind);
+        }
+        let mut columns: BTreeMap<u32, DefectKind> = BTreeMap::new();
+        for c in self.columns.iter().chain(&other.columns) {
+            insert_worst(&mut columns, c.x, c.kind);
+        }
+        self.pixels = pixels
+            .into_iter()
+            .map(|((y, x), kind)| Defect { x, y, kind })
+            .collect();
+        self.columns = columns
+            .into_iter()
+            .map(|(x, kind)| BadColumn { x, kind })
+            .collect();
+    }
+
+    /// Translates the map into the coordinates of a frame captured with `roi`.
+    ///
+    /// ROI start offsets are in binned pixels. A binned pixel is reported as
+    /// defective if any of the sensor pixels it combines is defective.
+    pub fn in_frame(&self, roi: &RoiFormat) -> FrameDefects {
+        let pixels: BTreeSet<(usize, usize)> = self
+            .pixels
+            .iter()
+            .filter_map(|d| {
+                let (x, y) = roi.sensor_to_frame(d.x, d.y)?;
+                Some((y, x))
+            })
+            .collect();
+        let columns: BTreeSet<usize> = self
+            .columns
+            .iter()
+            .filter_map(|c| roi.sensor_x_to_frame(c.x))
+            .collect();
+
+        FrameDefects {
+            pixels: pixels.into_iter().map(|(y, x)| (x, y)).collect(),
+            columns: columns.into_iter().collect(),
+        }
+    }
+
+    /// Replaces defective pixels and columns in `frame` in place.
+    ///
+    /// Each defective pixel is replaced with the median of its non-defective
+    /// neighbours. For raw frames from color sensors only neighbours two
+    /// pixels away (same Bayer color) are used; all other formats, including
+    /// `Rgb24` / `Rgb32`, use the adjacent pixels of each channel. Bad columns
+    /// are replaced with the mean of the nearest good columns on either side.
+    pub fn correct(&self, frame: &mut Frame) {
+        let defects = self.in_frame(&frame.roi);
+        if defects.pixels.is_empty() && defects.columns.is_empty() {
+            return;
+        }
+        let step = if self.cfa && frame.image_type.is_raw() {
+            2
+        } else {
+            1
+        };
+        let (w, h) = (frame.width(), frame.height());
+        let bad_columns: HashSet<usize> = defects.columns.iter().copied().collect();
+        let bad_pixels: HashSet<(usize, usize)> = defects.pixels.iter().copied().collect();
+
+        for &x in &defects.columns {
+            let left = (1..=4)
+                .map(|k| x.checked_sub(k * step))
+                .take_while(Option::is_some)
+                .flatten()
+                .find(|c| !bad_columns.contains(c));
+            let right = (1..=4)
+                .map(|k| x + k * step)
+                .take_while(|&c| c < w)
+                .find(|c| !bad_columns.contains(c));
+            let sources: Vec<usize> = left.into_iter().chain(right).collect();
+            if sources.is_empty() {
+                continue;
+            }
+            for y in 0..h {
+                for c in 0..frame.channels() {
+                    let sum: u32 = sources
+                        .iter()
+                        .map(|&sx| frame.sample(sx, y, c) as u32)
+                        .sum();
+                    frame.set_sample(x, y, c, (sum / sources.len() as u32) as u16);
+                }
+            }
+        }
+
+        let is_bad = |x: usize, y: usize| bad_pixels.contains(&(x, y)) || bad_columns.contains(&x);
+        let offsets = [-(step as isize), 0, step as isize];
+        for &(x, y) in &defects.pixels {
+            if bad_columns.contains(&x) {
+                continue;
+            }
+            for c in 0..frame.channels() {
+                let mut values: Vec<u16> = Vec::with_capacity(8);
+                for &dy in &offsets {
+                    for &dx in &offsets {
+                        if dx == 0 && dy == 0 {
+                            continue;
+                        }
+                        let nx = x as isize + dx;
+                        let ny = y as isize + dy;
+                        if nx < 0 || ny < 0 || nx as usize >= w || ny as usize >= h {
+                            continue;
+                        }
+                        let (nx, ny) = (nx as usize, ny as usize);
+                        if !is_bad(nx, ny) {
+                            values.push(frame.sample(nx, ny, c));
+                        }
+                    }
+                }
+                if values.is_empty() {
+                    continue;
+                }
+                values.sort_unstable();
+                let mid = values.len() / 2;
+                let v = if values.len() % 2 == 1 {
+                    values[mid]
+                } else {
+                    ((values[mid - 1] as u32 + values[mid] as u32) / 2) as u16
+                };
+                frame.set_sample(x, y, c, v);
+            }
+        }
+    }
+
+    // --- Persistence ---
+
+    /// Returns the file a map for camera `serial` is stored in under `dir`.
+    ///
+    /// Characters other than ASCII alphanumerics, `-` and `_` are replaced so
+    /// the serial is always a valid file name.
+    pub fn path_for_serial(dir: &Path, serial: &str) -> PathBuf {
+        let name: String = serial
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        dir.join(format!("{name}.defects"))
+    }
+
+    /// Saves the map under `dir` for camera `serial`, creating `dir` if needed.
+    pub fn save_for_serial(&self, dir: &Path, serial: &str) -> io::Result<()> {
+        fs::create_dir_all(dir)?;
+        self.save(Self::path_for_serial(dir, serial))
+    }
+
+    /// Loads the map stored under `dir` for camera `serial`.
+    pub fn load_for_serial(dir: &Path, serial: &str) -> io::Result<Self> {
+        Self::load(Self::path_for_serial(dir, serial))
+    }
+
+    /// Saves the map to `path` in a line-oriented text format.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut file = io::BufWriter::new(fs::File::create(path)?);
+        self.write_to(&mut file)?;
+        file.flush()
+    }
+
+    /// Loads a map previously written with [`save`](Self::save).
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::read_from(BufReader::new(fs::File::open(path)?))
+    }
+
+    /// Writes the map in its text format.
+    pub fn write_to<W: Write>(&self, mut w: W) -> io::Result<()> {
+        writeln!(w, "{FILE_HEADER}")?;
+        writeln!(w, "sensor {} {}", self.sensor_width, self.sensor_height)?;
+        writeln!(w, "cfa {}", self.cfa as u8)?;
+        for c in &self.columns {
+            writeln!(w, "column {} {}", c.x, c.kind.as_str())?;
+        }
+        for d in &self.pixels {
+            writeln!(w, "pixel {} {} {}", d.x, d.y, d.kind.as_str())?;
+        }
+        Ok(())
+    }
+
+    /// Reads a map in the format produced by [`write_to`](Self::write_to).
+    pub fn read_from<R: BufRead>(r: R) -> io::Result<Self> {
+        let invalid = |line: usize, msg: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("defect map line {line}: {msg}"),
+            )
+        };
+        let mut lines = r.lines().enumerate();
+        match lines.next() {
+            Some((_, Ok(header))) if header.trim() == FILE_HEADER => {}
+            Some((_, Err(e))) => return Err(e),
+            _ => return Err(invalid(1, "missing header")),
+        }
+
+        let mut map = Self {
+            sensor_width: 0,
+            sensor_height: 0,
+            cfa: false,
+            pixels: Vec::new(),
+            columns: Vec::new(),
+        };
+        for (i, line) in lines {
+            let line = line?;
+            let n = i + 1;
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let num = |idx: usize| -> io::Result<u32> {
+                fields
+                    .get(idx)
+                    .and_then(|f| f.parse().ok())
+                    .ok_or_else(|| invalid(n, "expected a number"))
+            };
+            let kind = |idx: usize| -> io::Result<DefectKind> {
+                fields
+                    .get(idx)
+                    .and_then(|f| DefectKind::parse(f))
+                    .ok_or_else(|| invalid(n, "unknown defect kind"))
+            };
+            match fields.first().copied() {
+                None => {}
+                Some(f) if f.starts_with('#') => {}
+                Some("sensor") => {
+                    map.sensor_width = num(1)?;
+                    map.sensor_height = num(2)?;
+                }
+                Some("cfa") => map.cfa = num(1)? != 0,
+                Some("column") => map.columns.push(BadColumn {
+                    x: num(1)?,
+                    kind: kind(2)?,
+                }),
+                Some("pixel") => map.pixels.push(Defect {
+                    x: num(1)?,
+                    y: num(2)?,
+                    kind: kind(3)?,
+                }),
+                Some(_) => return Err(invalid(n, "unknown record")),
+            }
+        }
+        Ok(map)
+    }
+}
+
+const FILE_HEADER: &str = "# svbony defect map v1";
+
+fn insert_worst<K: Ord>(map: &mut BTreeMap<K, DefectKind>, key: K, kind: DefectKind) {
+    let entry = map.entry(key).or_insert(kind);
+    if kind.severity() > entry.severity() {
+        *entry = kind;
+    }
+}
+
+/// A single-channel calibration frame split into its Bayer sites.
+struct Planes<'a> {
+    frame: &'a Frame,
+    cfa: bool,
+}
+
+impl<'a> Planes<'a> {
+    fn new(prop: &CameraProperty, frame: &'a Frame) -> Result<Self> {
+        if frame.channels() != 1 {
+            return Err(Error::InvalidImageType);
+        }
+        if frame.roi.bin > 1 {
+            return Err(Error::InvalidArgument(
+                "defect detection requires unbinned frames".into(),
+            ));
+        }
+        Ok(Self {
+            frame,
+            cfa: prop.is_color && frame.image_type.is_raw(),
+        })
+    }
+
+    fn count(&self) -> usize {
+        if self.cfa {
+            4
+        } else {
+            1
+        }
+    }
+
+    fn plane_of(&self, x: usize, y: usize) -> usize {
+        if self.cfa {
+            (y % 2) * 2 + x % 2
+        } else {
+            0
+        }
+    }
+
+    fn value(&self, x: usize, y: usize) -> f64 {
+        self.frame.sample(x, y, 0) as f64
+    }
+
+    /// Median of each plane.
+    fn medians(&self) -> Vec<f64> {
+        self.plane_values()
+            .iter_mut()
+            .map(|values| median(values))
+            .collect()
+    }
+
+    /// Median and robust sigma of each plane.
+    fn median_sigma(&self, min_sigma: f64) -> Vec<(f64, f64)> {
+        self.plane_values()
+            .iter_mut()
+            .map(|values| {
+                let m = median(values);
+                let s = (MAD_TO_SIGMA * mad(values, m)).max(min_sigma);
+                (m, s)
+            })
+            .collect()
+    }
+
+    fn plane_values(&self) -> Vec<Vec<f64>> {
+        let mut planes = vec![Vec::new(); self.count()];
+        for y in 0..self.frame.height() {
+            for x in 0..self.frame.width() {
+                planes[self.plane_of(x, y)].push(self.value(x, y));
+            }
+        }
+        planes
+    }
+
+    /// Returns the median of `f(x, y)` down each column.
+    fn column_medians(&self, f: impl Fn(usize, usize) -> f64) -> Vec<f64> {
+        (0..self.frame.width())
+            .map(|x| {
+                let mut col: Vec<f64> = (0..self.frame.height()).map(|y| f(x, y)).collect();
+                median(&mut col)
+            })
+            .collect()
+    }
+
+    fn sensor_x(&self, x: usize) -> u32 {
+        (self.frame.roi.start_x.max(0) as usize + x) as u32
+    }
+
+    fn sensor_y(&self, y: usize) -> u32 {
+        (self.frame.roi.start_y.max(0) as usize + y) as u32
+    }
+}
+
+/// Returns the indices of `values` that lie more than `n_sigma` robust sigma
+/// from the median of all values, in the direction selected by `high`.
+fn outliers(values: &[f64], n_sigma: f64, min_sigma: f64, high: bool) -> Vec<usize> {
+    let mut sorted = values.to_vec();
+    let m = median(&mut sorted);
+    let s = (MAD_TO_SIGMA * mad(values, m)).max(min_sigma);
+    values
+        .iter()
+        .enumerate()
+        .filter(|&(_, &v)| {
+            if high {
+                v - m > n_sigma * s
+            } else {
+                m - v > n_sigma * s
+            }
+        })
+        .map(|(i, _)| i)
+        .collect()
+}
+
+fn detect_dark(
+    planes: &Planes,
+    t: &DefectThresholds,
+    pixels: &mut BTreeMap<(u32, u32), DefectKind>,
+    columns: &mut BTreeMap<u32, DefectKind>,
+) {
+    let stats = planes.median_sigma(MIN_DARK_SIGMA);
+    for y in 0..planes.frame.height() {
+        for x in 0..planes.frame.width() {
+            let (m, s) = stats[planes.plane_of(x, y)];
+            let z = (planes.value(x, y) - m) / s;
+            let kind = if z > t.hot_sigma {
+                DefectKind::Hot
+            } else if z > t.warm_sigma {
+                DefectKind::Warm
+            } else {
+                continue;
+            };
+            insert_worst(pixels, (planes.sensor_y(y), planes.sensor_x(x)), kind);
+        }
+    }
+
+    let offsets = planes.column_medians(|x, y| planes.value(x, y) - stats[planes.plane_of(x, y)].0);
+    for x in outliers(&offsets, t.column_sigma, MIN_DARK_COLUMN_SIGMA, true) {
+        insert_worst(columns, planes.sensor_x(x), DefectKind::Hot);
+    }
+}
+
+fn detect_flat(
+    planes: &Planes,
+    t: &DefectThresholds,
+    pixels: &mut BTreeMap<(u32, u32), DefectKind>,
+    columns: &mut BTreeMap<u32, DefectKind>,
+) -> Result<()> {
+    let medians = planes.medians();
+    if medians.iter().any(|&m| m <= 0.0) {
+        return Err(Error::InvalidArgument(
+            "flat frame has a zero median; increase the exposure".into(),
+        ));
+    }
+    let ratio = |x: usize, y: usize| planes.value(x, y) / medians[planes.plane_of(x, y)];
+
+    for y in 0..planes.frame.height() {
+        for x in 0..planes.frame.width() {
+            let r = ratio(x, y);
+            let kind = if r < t.dead_fraction {
+                DefectKind::Dead
+            } else if r < t.cold_fraction {
+                DefectKind::Cold
+            } else {
+                continue;
+            };
+            insert_worst(pixels, (planes.sensor_y(y), planes.sensor_x(x)), kind);
+        }
+    }
+
+    let ratios = planes.column_medians(ratio);
+    for x in outliers(&ratios, t.column_sigma, MIN_FLAT_COLUMN_SIGMA, false) {
+        let kind = if ratios[x] < t.dead_fraction {
+            DefectKind::Dead
+        } else {
+            DefectKind::Cold
+        };
+        insert_worst(columns, planes.sensor_x(x), kind);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BayerPattern, ImageType};
+
+    fn prop(w: i64, h: i64, is_color: bool) -> CameraProperty {
+        CameraProperty {
+            max_width: w,
+            max_height: h,
+            is_color,
+            bayer_pattern: Some(BayerPattern::Rg),
+            supported_bins: vec![1, 2],
+            supported_formats: vec![ImageType::Raw16],
+            max_bit_depth: 12,
+            is_trigger_cam: false,
+        }
+    }
+
+    fn roi(w: i32, h: i32) -> RoiFormat {
+        RoiFormat {
+            start_x: 0,
+            start_y: 0,
+            width: w,
+            height: h,
+            bin: 1,
+        }
+    }
+
+    /// Mildly noisy frame with a deterministic pattern around `level`.
+    fn frame(w: i32, h: i32, level: u16) -> Frame {
+        let mut f = Frame::zeroed(roi(w, h), ImageType::Raw16);
+        for y in 0..h as usize {
+            for x in 0..w as usize {
+                f.set_sample(x, y, 0, level + ((x * 7 + y * 13) % 5) as u16);
+            }
+        }
+        f
+    }
+
+    #[test]
+    fn detects_hot_and_warm_pixels() {
+        let mut dark = frame(32, 16, 100);
+        dark.set_sample(5, 3, 0, 4000);
+        dark.set_sample(9, 10, 0, 112);
+        let map = DefectMap::detect(&prop(32, 16, false), Some(&dark), None, &Default::default())
+            .unwrap();
+        assert_eq!(
+            map.pixels,
+            vec![
+                Defect {
+                    x: 5,
+                    y: 3,
+                    kind: DefectKind::Hot
+                },
+                Defect {
+                    x: 9,
+                    y: 10,
+                    kind: DefectKind::Warm
+                },
+            ]
+        );
+        assert!(map.columns.is_empty());
+    }
+
+    #[test]
+    fn detects_cold_dead_and_bad_columns() {
+        let mut flat = frame(32, 16, 1000);
+        flat.set_sample(2, 2, 0, 500);
+        flat.set_sample(20, 7, 0, 10);
+        for y in 0..16 {
+            flat.set_sample(12, y, 0, 800);
+        }
+        let map = DefectMap::detect(&prop(32, 16, false), None, Some(&flat), &Default::default())
+            .unwrap();
+        assert_eq!(
+            map.pixels,
+            vec![
+                Defect {
+                    x: 2,
+                    y: 2,
+                    kind: DefectKind::Cold
+                },
+                Defect {
+                    x: 20,
+                    y: 7,
+                    kind: DefectKind::Dead
+                },
+            ]
+        );
+        assert_eq!(
+            map.columns,
+            vec![BadColumn {
+                x: 12,
+                kind: DefectKind::Cold
+            }]
+        );
+    }
+
+    #[test]
+    fn detection_offsets_by_roi_start() {
+        let mut dark = frame(16, 8, 100);
+        dark.roi.start_x = 64;
+        dark.roi.start_y = 32;
+        dark.set_sample(1, 2, 0, 5000);
+        let map = DefectMap::detect(
+            &prop(256, 128, false),
+            Some(&dark),
+            None,
+            &Default::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            map.pixels[0],
+            Defect {
+                x: 65,
+                y: 34,
+                kind: DefectKind::Hot
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_binned_and_color_frames() {
+        let p = prop(32, 16, false);
+        let mut binned = frame(16, 8, 100);
+        binned.roi.bin = 2;
+        assert!(DefectMap::detect(&p, Some(&binned), None, &Default::default()).is_err());
+        let rgb = Frame::zeroed(roi(8, 8), ImageType::Rgb24);
+        assert_eq!(
+            DefectMap::detect(&p, Some(&rgb), None, &Default::default()).unwrap_err(),
+            Error::InvalidImageType
+        );
+    }
+
+    #[test]
+    fn in_frame_translates_roi_and_binning() {
+        let mut map = DefectMap::new(&prop(64, 64, false));
+        map.pixels = vec![
+            Defect {
+                x: 20,
+                y: 11,
+                kind: DefectKind::Hot,
+            },
+            Defect {
+                x: 21,
+                y: 10,
+                kind: DefectKind::Hot,
+            },
+            Defect {
+                x: 2,
+                y: 2,
+                kind: DefectKind::Hot,
+            },
+        ];
+        map.columns = vec![BadColumn {
+            x: 30,
+            kind: DefectKind::Hot,
+        }];
+        let r = RoiFormat {
+            start_x: 8,
+            start_y: 4,
+            width: 16,
+            height: 8,
+            bin: 2,
+        };
+        let d = map.in_frame(&r);
+        assert_eq!(d.pixels, vec![(2, 1)]);
+        assert_eq!(d.columns, vec![7]);
+    }
+
+    #[test]
+    fn correct_uses_same_color_neighbours() {
+        let mut map = DefectMap::new(&prop(8, 8, true));
+        map.pixels = vec![Defect {
+            x: 4,
+            y: 4,
+            kind: DefectKind::Hot,
+        }];
+        // Checkerboard of two Bayer colors: same-color neighbours are 2 px away.
+        let mut f = Frame::zeroed(roi(8, 8), ImageType::Raw8);
+        for y in 0..8 {
+            for x in 0..8 {
+                f.set_sample(x, y, 0, if (x + y) % 2 == 0 { 50 } else { 200 });
+            }
+        }
+        f.set_sample(4, 4, 0, 255);
+        map.correct(&mut f);
+        assert_eq!(f.sample(4, 4, 0), 50);
+
+        // Debayered output uses direct neighbours.
+        let mut y8 = Frame::zeroed(roi(8, 8), ImageType::Y8);
+        y8.data.fill(80);
+        y8.set_sample(4, 4, 0, 255);
+        map.correct(&mut y8);
+        assert_eq!(y8.sample(4, 4, 0), 80);
+    }
+
+    #[test]
+    fn correct_rgb_and_columns() {
+        let mut map = DefectMap::new(&prop(8, 4, true));
+        map.pixels = vec![Defect {
+            x: 1,
+            y: 1,
+            kind: DefectKind::Dead,
+        }];
+        map.columns = vec![BadColumn {
+            x: 5,
+            kind: DefectKind::Hot,
+        }];
+        let mut f = Frame::zeroed(roi(8, 4), ImageType::Rgb24);
+        for px in f.data.chunks_exact_mut(3) {
+            px.copy_from_slice(&[10, 20, 30]);
+        }
+        f.set_sample(1, 1, 0, 0);
+        f.set_sample(1, 1, 1, 0);
+        for y in 0..4 {
+            f.set_sample(5, y, 2, 255);
+        }
+        map.correct(&mut f);
+        assert_eq!(
+            [f.sample(1, 1, 0), f.sample(1, 1, 1), f.sample(1, 1, 2)],
+            [10, 20, 30]
+        );
+        assert_eq!(f.sample(5, 2, 2), 30);
+    }
+
+    #[test]
+    fn merge_keeps_most_severe() {
+        let mut a = DefectMap::new(&prop(8, 8, false));
+        a.pixels = vec![Defect {
+            x: 1,
+            y: 1,
+            kind: DefectKind::Warm,
+        }];
+        let mut b = a.clone();
+        b.pixels = vec![
+            Defect {
+                x: 1,
+                y: 1,
+                kind: DefectKind::Hot,
+            },
+            Defect {
+                x: 0,
+                y: 2,
+                kind: DefectKind::Cold,
+            },
+        ];
+        a.merge(&b);
+        assert_eq!(
+            a.pixels,
+            vec![
+                Defect {
+                    x: 1,
+                    y: 1,
+                    kind: DefectKind::Hot
+                },
+                Defect {
+                    x: 0,
+                    y: 2,
+                    kind: DefectKind::Cold
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let mut map = DefectMap::new(&prop(1920, 1080, true));
+        map.pixels = vec![Defect {
+            x: 3,
+            y: 4,
+            kind: DefectKind::Warm,
+        }];
+        map.columns = vec![BadColumn {
+            x: 77,
+            kind: DefectKind::Dead,
+        }];
+        let mut buf = Vec::new();
+        map.write_to(&mut buf).unwrap();
+        let back = DefectMap::read_from(buf.as_slice()).unwrap();
+        assert_eq!(back, map);
+
+        assert!(DefectMap::read_from("pixel 1 2 hot\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn serial_path_is_sanitised() {
+        let p = DefectMap::path_for_serial(Path::new("/maps"), "SV/305 #1");
+        assert_eq!(p, Path::new("/maps/SV_305__1.defects"));
+    }
+}
@@ -3,11 +3,15 @@
 use std::os::raw::c_int;
 use svbony_sys::*;
 
-/// Errors returned by the SVBony camera SDK.
+use crate::ControlType;
+
+/// Errors returned by the SVBony camera SDK or by this crate's validation.
 ///
-/// Each variant corresponds to a `SVB_ERROR_*` code from the C SDK.
+/// Most variants correspond to a `SVB_ERROR_*` code from the C SDK.
 /// [`Unknown`](Error::Unknown) wraps any code not recognised by this crate
-/// (e.g. from a newer SDK version).
+/// (e.g. from a newer SDK version). [`InvalidArgument`](Error::InvalidArgument)
+/// and the control validation variants are raised by the crate itself before
+/// anything is sent to the camera.
 #[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
 pub enum Error {
     /// No camera connected or index value out of range.
@@ -70,6 +74,32 @@ pub enum Error {
     /// An error code not mapped by this crate (possibly from a newer SDK).
     #[error("unknown error code: {0}")]
     Unknown(i32),
+    /// An argument was rejected by this crate before reaching the SDK.
+    #[error("invalid argument: {0}")]
+    InvalidArgument(String),
+    /// The control is read-only on this camera.
+    #[error("control {0:?} is read-only")]
+    ReadOnlyControl(ControlType),
+    /// Auto mode was requested for a control that does not support it.
+    #[error("control {0:?} does not support auto mode")]
+    AutoNotSupported(ControlType),
+    /// The value is outside the range reported by the control's caps.
+    #[error("value {value} for control {control:?} is outside {min}..={max}")]
+    OutOfRange {
+        control: ControlType,
+        value: i64,
+        min: i64,
+        max: i64,
+    },
+    /// The camera does not support the requested feature.
+    #[error("not supported by this camera: {0}")]
+    Unsupported(String),
+    /// A settings profile could not be read, written or decoded.
+    #[error("profile error: {0}")]
+    Profile(String),
+    /// No settings profile with this name is saved for the camera.
+    #[error("no profile named {0:?}")]
+    ProfileNotFound(String),
 }
 
 /// Convenience alias used throughout the `svbony` crate.
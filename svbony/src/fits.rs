@@ -0,0 +1,471 @@
+//! Minimal FITS writer for captured frames.
+//!
+//! Writes a single primary HDU: 8-bit formats as `BITPIX = 8`, 10- to 16-bit
+//! formats as unsigned 16-bit (`BITPIX = 16` with `BZERO = 32768`). Color
+//! frames become a three-plane cube in red, green, blue order; the padding
+//! channel of `Rgb32` is dropped. Rows are written in capture order and
+//! marked with `ROWORDER = 'TOP-DOWN'`.
+//!
+//! ```no_run
+//! use std::fs::File;
+//! use svbony::fits::{self, Card};
+//! use svbony::Camera;
+//!
+//! let cam = Camera::open(0)?;
+//! cam.start_capture()?;
+//! let frame = cam.capture_frame(5000)?;
+//! cam.stop_capture()?;
+//!
+//! let cards = [Card::new("EXPTIME", 0.5).comment("seconds")];
+//! fits::write_frame(&mut File::create("light.fits")?, &frame, &cards)?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::io::{self, Write};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::{BayerPattern, Frame};
+
+/// FITS files are written in blocks of this many bytes.
+pub const BLOCK_SIZE: usize = 2880;
+
+const CARD_SIZE: usize = 80;
+
+/// Keywords the writer produces itself and rejects in caller cards.
+const RESERVED: &[&str] = &[
+    "SIMPLE", "BITPIX", "NAXIS", "NAXIS1", "NAXIS2", "NAXIS3", "EXTEND", "BZERO", "BSCALE",
+    "ROWORDER", "END",
+];
+
+/// Value of a header card.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// Logical value, written as `T` or `F`.
+    Bool(bool),
+    /// Integer value.
+    Int(i64),
+    /// Real value; must be finite.
+    Float(f64),
+    /// Printable ASCII string.
+    Str(String),
+}
+
+impl From<bool> for Value {
+    fn from(v: bool) -> Self {
+        Self::Bool(v)
+    }
+}
+
+impl From<i32> for Value {
+    fn from(v: i32) -> Self {
+        Self::Int(v.into())
+    }
+}
+
+impl From<i64> for Value {
+    fn from(v: i64) -> Self {
+        Self::Int(v)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(v: f64) -> Self {
+        Self::Float(v)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(v: &str) -> Self {
+        Self::Str(v.into())
+    }
+}
+
+impl From<String> for Value {
+    fn from(v: String) -> Self {
+        Self::Str(v)
+    }
+}
+
+/// A `KEYWORD = value / comment` header card.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Card {
+    /// Up to 8 upper-case letters, digits, `-` or `_`.
+    pub keyword: String,
+    /// The card's value.
+    pub value: Value,
+    /// Text written after the value.
+    pub comment: Option<String>,
+}
+
+impl Card {
+    /// Creates a card without a comment.
+    pub fn new(keyword: impl Into<String>, value: impl Into<Value>) -> Self {
+        Self {
+            keyword: keyword.into(),
+            value: value.into(),
+            comment: None,
+        }
+    }
+
+    /// Sets the card's comment.
+    pub fn comment(mut self, comment: impl Into<String>) -> Self {
+        self.comment = Some(comment.into());
+        self
+    }
+
+    /// Formats the card as 80 ASCII bytes in fixed format.
+    fn format(&self) -> io::Result<Vec<u8>> {
+        let key = &self.keyword;
+        if key.is_empty()
+            || key.len() > 8
+            || !key
+                .bytes()
+                .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit() || b == b'-' || b == b'_')
+        {
+            return Err(invalid(format!("invalid FITS keyword {key:?}")));
+        }
+        let value = match &self.value {
+            Value::Bool(b) => format!("{:>20}", if *b { "T" } else { "F" }),
+            Value::Int(i) => format!("{i:>20}"),
+            Value::Float(f) => format!("{:>20}", format_float(*f)?),
+            Value::Str(s) => {
+                if !s.bytes().all(|b| (b' '..=b'~').contains(&b)) {
+                    return Err(invalid(format!("non-ASCII FITS string {s:?}")));
+                }
+                // Strings are padded to at least 8 characters inside the quotes.
+                format!("'{:<8}'", s.replace('\'', "''"))
+            }
+        };
+        let mut card = format!("{key:<8}= {value}");
+        if let Some(comment) = &self.comment {
+            card.push_str(" / ");
+            card.push_str(comment);
+        }
+        if card.len() > CARD_SIZE || !card.is_ascii() {
+            return Err(invalid(format!("FITS card for {key} is too long")));
+        }
+        let mut bytes = card.into_bytes();
+        bytes.resize(CARD_SIZE, b' ');
+        Ok(bytes)
+    }
+}
+
+/// Formats `v` as a FITS real: always with a decimal point and an upper-case
+/// exponent.
+fn format_float(v: f64) -> io::Result<String> {
+    if !v.is_finite() {
+        return Err(invalid(format!("FITS values must be finite, got {v}")));
+    }
+    let s = format!("{v:?}").to_uppercase();
+    Ok(match s.split_once('E') {
+        Some((mantissa, exp)) if !mantissa.contains('.') => format!("{mantissa}.0E{exp}"),
+        _ => s,
+    })
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+fn pad_to_block(out: &mut Vec<u8>, fill: u8) {
+    let rem = out.len() % BLOCK_SIZE;
+    if rem != 0 {
+        out.resize(out.len() + BLOCK_SIZE - rem, fill);
+    }
+}
+
+/// Encodes `frame` as a FITS file with `cards` appended to the header.
+///
+/// Returns an [`InvalidInput`](io::ErrorKind::InvalidInput) error for
+/// malformed cards or cards that repeat a structural keyword such as
+/// `BITPIX`.
+pub fn encode_frame(frame: &Frame, cards: &[Card]) -> io::Result<Vec<u8>> {
+    let sixteen = frame.image_type.bytes_per_pixel() == 2;
+    // Buffer channel for each output plane, in R, G, B order.
+    let planes: &[usize] = match frame.channels() {
+        1 => &[0],
+        _ => &[2, 1, 0],
+    };
+    let (w, h) = (frame.width(), frame.height());
+
+    let mut header = vec![
+        Card::new("SIMPLE", true).comment("file conforms to FITS standard"),
+        Card::new("BITPIX", if sixteen { 16 } else { 8 }),
+        Card::new("NAXIS", if planes.len() == 1 { 2 } else { 3 }),
+        Card::new("NAXIS1", w as i64),
+        Card::new("NAXIS2", h as i64),
+    ];
+    if planes.len() > 1 {
+        header.push(Card::new("NAXIS3", planes.len() as i64));
+    }
+    header.push(Card::new("EXTEND", true));
+    if sixteen {
+        header.push(Card::new("BZERO", 32768).comment("data are unsigned 16-bit"));
+        header.push(Card::new("BSCALE", 1));
+    }
+    header.push(Card::new("ROWORDER", "TOP-DOWN"));
+
+    let mut out = Vec::with_capacity(BLOCK_SIZE * 2 + w * h * planes.len() * 2);
+    for card in &header {
+        out.extend(card.format()?);
+    }
+    for card in cards {
+        if RESERVED.contains(&card.keyword.as_str()) {
+            return Err(invalid(format!(
+                "{} is written by the FITS writer",
+                card.keyword
+            )));
+        }
+        out.extend(card.format()?);
+    }
+    let mut end = b"END".to_vec();
+    end.resize(CARD_SIZE, b' ');
+    out.extend(end);
+    pad_to_block(&mut out, b' ');
+
+    for &c in planes {
+        for y in 0..h {
+            for x in 0..w {
+                let v = frame.sample(x, y, c);
+                if sixteen {
+                    // Unsigned to signed with BZERO = 32768.
+                    out.extend((v ^ 0x8000).to_be_bytes());
+                } else {
+                    out.push(v as u8);
+                }
+            }
+        }
+    }
+    pad_to_block(&mut out, 0);
+    Ok(out)
+}
+
+/// Formats `t` as a FITS date-time, `YYYY-MM-DDThh:mm:ss.sss` in UTC, for
+/// `DATE-OBS`.
+pub fn timestamp(t: SystemTime) -> String {
+    let since = t.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since.as_secs();
+    let (days, rem) = ((secs / 86_400) as i64, secs % 86_400);
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm).
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}",
+        rem / 3600,
+        rem / 60 % 60,
+        rem % 60,
+        since.subsec_millis()
+    )
+}
+
+/// Capture details for the common observation header cards; see
+/// [`cards`](Self::cards).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Observation<'a> {
+    /// Camera name, written as `INSTRUME`.
+    pub instrument: &'a str,
+    /// Exposure time, written as `EXPTIME` in seconds.
+    pub exposure: Duration,
+    /// Start of the exposure, written as `DATE-OBS`.
+    pub started: SystemTime,
+    /// Unbinned pixel size in microns; `XPIXSZ` / `YPIXSZ` hold the binned
+    /// size.
+    pub pixel_size_um: Option<f32>,
+    /// Gain control value, written as `GAIN`.
+    pub gain: Option<i64>,
+    /// Sensor temperature in °C, written as `CCD-TEMP`.
+    pub temperature: Option<f64>,
+    /// Written as `BAYERPAT` for raw frames.
+    pub bayer_pattern: Option<BayerPattern>,
+}
+
+impl Observation<'_> {
+    /// Returns the header cards describing `frame`: `INSTRUME`, `EXPTIME`,
+    /// `DATE-OBS`, `XBINNING` / `YBINNING` and, where known, `XPIXSZ` /
+    /// `YPIXSZ`, `CCD-TEMP`, `GAIN` and, for raw frames, `BAYERPAT` with
+    /// zero `XBAYROFF` / `YBAYROFF`.
+    pub fn cards(&self, frame: &Frame) -> Vec<Card> {
+        let bin = i64::from(frame.roi.bin.max(1));
+        let mut cards = vec![
+            Card::new("INSTRUME", self.instrument),
+            Card::new("EXPTIME", self.exposure.as_secs_f64()).comment("seconds"),
+            Card::new("DATE-OBS", timestamp(self.started)).comment("UTC start"),
+            Card::new("XBINNING", bin),
+            Card::new("YBINNING", bin),
+        ];
+        if let Some(pixel) = self.pixel_size_um {
+            let size = f64::from(pixel) * bin as f64;
+            cards.push(Card::new("XPIXSZ", size).comment("microns, binned"));
+            cards.push(Card::new("YPIXSZ", size).comment("microns, binned"));
+        }
+        if let Some(temp) = self.temperature {
+            cards.push(Card::new("CCD-TEMP", temp).comment("C"));
+        }
+        if let Some(gain) = self.gain {
+            cards.push(Card::new("GAIN", gain));
+        }
+        let pattern = self.bayer_pattern.and_then(|p| p.name());
+        if let (true, Some(pattern)) = (frame.image_type.is_raw(), pattern) {
+            cards.push(Card::new("BAYERPAT", pattern));
+            cards.push(Card::new("XBAYROFF", 0));
+            cards.push(Card::new("YBAYROFF", 0));
+        }
+        cards
+    }
+}
+
+/// Writes `frame` as a FITS file to `out`; see [`encode_frame`].
+pub fn write_frame(out: &mut impl Write, frame: &Frame, cards: &[Card]) -> io::Result<()> {
+    out.write_all(&encode_frame(frame, cards)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ImageType, RoiFormat};
+
+    fn frame(w: i32, h: i32, ty: ImageType) -> Frame {
+        let roi = RoiFormat {
+            start_x: 0,
+            start_y: 0,
+            width: w,
+            height: h,
+            bin: 1,
+        };
+        Frame::zeroed(roi, ty)
+    }
+
+    fn card(fits: &[u8], i: usize) -> &str {
+        std::str::from_utf8(&fits[i * CARD_SIZE..(i + 1) * CARD_SIZE]).unwrap()
+    }
+
+    #[test]
+    fn mono_16bit_layout() {
+        let mut f = frame(8, 2, ImageType::Raw16);
+        f.set_sample(0, 0, 0, 0);
+        f.set_sample(1, 0, 0, 65535);
+        f.set_sample(0, 1, 0, 32768);
+        let fits = encode_frame(&f, &[Card::new("EXPTIME", 1.5)]).unwrap();
+        assert_eq!(fits.len(), 2 * BLOCK_SIZE);
+        assert_eq!(
+            card(&fits, 0),
+            format!(
+                "{:<80}",
+                "SIMPLE  =                    T / file conforms to FITS standard"
+            )
+        );
+        assert!(card(&fits, 1).starts_with("BITPIX  =                   16"));
+        assert!(card(&fits, 2).starts_with("NAXIS   =                    2"));
+        assert!(card(&fits, 3).starts_with("NAXIS1  =                    8"));
+        assert!(card(&fits, 6).starts_with("BZERO   =                32768"));
+        assert!(card(&fits, 8).starts_with("ROWORDER= 'TOP-DOWN'"));
+        assert!(card(&fits, 9).starts_with("EXPTIME =                  1.5"));
+        assert_eq!(card(&fits, 10).trim_end(), "END");
+
+        let data = &fits[BLOCK_SIZE..];
+        assert_eq!(&data[0..2], &[0x80, 0x00]);
+        assert_eq!(&data[2..4], &[0x7f, 0xff]);
+        assert_eq!(&data[16..18], &[0x00, 0x00]);
+    }
+
+    #[test]
+    fn color_planes_are_rgb() {
+        let mut f = frame(8, 2, ImageType::Rgb32);
+        // Buffer order is B, G, R, A.
+        f.set_sample(0, 0, 0, 10);
+        f.set_sample(0, 0, 1, 20);
+        f.set_sample(0, 0, 2, 30);
+        let fits = encode_frame(&f, &[]).unwrap();
+        assert!(card(&fits, 1).starts_with("BITPIX  =                    8"));
+        assert!(card(&fits, 2).starts_with("NAXIS   =                    3"));
+        assert!(card(&fits, 5).starts_with("NAXIS3  =                    3"));
+        let data = &fits[BLOCK_SIZE..];
+        let plane = 8 * 2;
+        assert_eq!([data[0], data[plane], data[2 * plane]], [30, 20, 10]);
+        assert_eq!(fits.len(), 2 * BLOCK_SIZE);
+    }
+
+    #[test]
+    fn card_formatting() {
+        let c = Card::new("OBJECT", "M31 'core'").format().unwrap();
+        assert!(std::str::from_utf8(&c)
+            .unwrap()
+            .starts_with("OBJECT  = 'M31 ''core'''"));
+        let c = Card::new("INSTRUME", "SV").format().unwrap();
+        assert!(std::str::from_utf8(&c)
+            .unwrap()
+            .starts_with("INSTRUME= 'SV      '"));
+        assert_eq!(format_float(1e-7).unwrap(), "1.0E-7");
+        assert_eq!(format_float(-20.5).unwrap(), "-20.5");
+        assert!(format_float(f64::NAN).is_err());
+    }
+
+    #[test]
+    fn timestamps_are_utc() {
+        use std::time::Duration;
+        assert_eq!(timestamp(UNIX_EPOCH), "1970-01-01T00:00:00.000");
+        let t = UNIX_EPOCH + Duration::from_millis(951_827_696_789);
+        assert_eq!(timestamp(t), "2000-02-29T12:34:56.789");
+        let t = UNIX_EPOCH + Duration::from_secs(1_790_000_000);
+        assert_eq!(timestamp(t), "2026-09-21T14:13:20.000");
+    }
+
+    #[test]
+    fn observation_cards() {
+        let mut obs = Observation {
+            instrument: "SV405CC",
+            exposure: Duration::from_millis(1500),
+            started: UNIX_EPOCH,
+            pixel_size_um: Some(3.76),
+            gain: None,
+            temperature: Some(-10.0),
+            bayer_pattern: Some(BayerPattern::Gb),
+        };
+        let mut f = frame(8, 2, ImageType::Raw16);
+        f.roi.bin = 2;
+        let keywords =
+            |cards: Vec<Card>| -> Vec<String> { cards.into_iter().map(|c| c.keyword).collect() };
+        let cards = obs.cards(&f);
+        assert_eq!(cards[1].value, Value::Float(1.5));
+        assert_eq!(cards[3].value, Value::Int(2));
+        assert_eq!(cards[5].value, Value::Float(f64::from(3.76_f32) * 2.0));
+        assert!(cards.contains(&Card::new("BAYERPAT", "GBRG")));
+        assert!(!keywords(cards).contains(&"GAIN".to_owned()));
+
+        obs.bayer_pattern = Some(BayerPattern::Other(7));
+        assert!(!keywords(obs.cards(&f)).contains(&"BAYERPAT".to_owned()));
+        obs.bayer_pattern = Some(BayerPattern::Rg);
+        let cards = obs.cards(&frame(8, 2, ImageType::Y16));
+        assert_eq!(
+            keywords(cards),
+            [
+                "INSTRUME", "EXPTIME", "DATE-OBS", "XBINNING", "YBINNING", "XPIXSZ", "YPIXSZ",
+                "CCD-TEMP"
+            ]
+        );
+    }
+
+    #[test]
+    fn bad_cards_are_rejected() {
+        let f = frame(8, 2, ImageType::Y8);
+        for bad in [
+            Card::new("exptime", 1),
+            Card::new("TOOLONGKEY", 1),
+            Card::new("BITPIX", 8),
+            Card::new("OBJECT", "x".repeat(80)),
+            Card::new("OBJECT", "caf\u{e9}"),
+        ] {
+            let err = encode_frame(&f, std::slice::from_ref(&bad)).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{bad:?}");
+        }
+    }
+}
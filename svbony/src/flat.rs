@@ -0,0 +1,456 @@
+//! Flat-frame acquisition with automatic exposure search.
+//!
+//! [`FlatWizard`] searches for the exposure that brings the mean frame level
+//! to a target ADU, reports when the light source is too bright or too dim
+//! for the camera's exposure range, and then captures a set of flats followed
+//! by matching dark-flats.
+//!
+//! ```no_run
+//! use svbony::Camera;
+//! use svbony::flat::{ExposureSearch, FlatConfig, FlatWizard};
+//!
+//! let cam = Camera::open(0)?;
+//! let wizard = FlatWizard::new(&cam, FlatConfig::default());
+//! match wizard.find_exposure()? {
+//!     ExposureSearch::Found { exposure, .. } => {
+//!         let set = wizard.capture(exposure, || {
+//!             println!("Cover the telescope, then press enter");
+//!             let _ = std::io::stdin().read_line(&mut String::new());
+//!             Ok(())
+//!         })?;
+//!         for flat in &set.flats {
+//!             println!("mean {:.0} ADU", flat.stats.mean);
+//!         }
+//!     }
+//!     other => println!("cannot take flats: {other:?}"),
+//! }
+//! # Ok::<(), svbony::Error>(())
+//! ```
+
+use std::time::Duration;
+
+use crate::{Camera, ControlType, Error, Frame, FrameStats, ImageType, Result};
+
+/// Target level for the flat exposure search.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FlatTarget {
+    /// Fraction of the full-scale range implied by
+    /// [`CameraProperty::max_bit_depth`](crate::CameraProperty::max_bit_depth)
+    /// (or 255 for 8-bit output formats).
+    Fraction(f64),
+    /// Absolute mean level in ADU, for cameras whose 16-bit output is scaled
+    /// differently from the ADC bit depth.
+    Adu(f64),
+}
+
+/// Settings for [`FlatWizard`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlatConfig {
+    /// Mean level to aim for.
+    pub target: FlatTarget,
+    /// Accepted relative deviation from the target (e.g. `0.05` = ±5 %).
+    pub tolerance: f64,
+    /// Exposure of the first test frame.
+    pub initial_exposure: Duration,
+    /// Maximum number of test exposures before giving up.
+    pub max_iterations: usize,
+    /// Number of flats to capture.
+    pub flat_count: usize,
+    /// Number of dark-flats to capture (0 to skip).
+    pub dark_count: usize,
+    /// Frames discarded after each exposure change, so that no frame exposed
+    /// with the previous setting is measured.
+    pub settle_frames: usize,
+}
+
+impl Default for FlatConfig {
+    fn default() -> Self {
+        Self {
+            target: FlatTarget::Fraction(0.5),
+            tolerance: 0.05,
+            initial_exposure: Duration::from_millis(10),
+            max_iterations: 12,
+            flat_count: 20,
+            dark_count: 20,
+            settle_frames: 1,
+        }
+    }
+}
+
+/// Outcome of [`FlatWizard::find_exposure`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExposureSearch {
+    /// An exposure within tolerance of the target was found.
+    Found {
+        /// Exposure to use for the flats.
+        exposure: Duration,
+        /// Mean level measured at that exposure.
+        mean: f64,
+    },
+    /// Even the shortest exposure the camera supports overshoots the target.
+    TooBright {
+        /// Minimum exposure from the control caps.
+        min_exposure: Duration,
+        /// Mean level measured at the minimum exposure.
+        mean: f64,
+    },
+    /// Even the longest exposure the camera supports falls short of the
+    /// target.
+    TooDim {
+        /// Maximum exposure from the control caps.
+        max_exposure: Duration,
+        /// Mean level measured at the maximum exposure.
+        mean: f64,
+    },
+    /// The search ran out of iterations; the closest measurement is returned.
+    NotConverged {
+        /// Exposure that came closest to the target.
+        exposure: Duration,
+        /// Mean level measured at that exposure.
+        mean: f64,
+    },
+}
+
+/// One captured calibration frame with its statistics.
+#[derive(Debug, Clone)]
+pub struct CalibrationFrame {
+    /// The frame data.
+    pub frame: Frame,
+    /// Summary statistics of the frame.
+    pub stats: FrameStats,
+    /// Fraction of samples at or above the full-scale level.
+    pub saturated_fraction: f64,
+}
+
+/// Result of [`FlatWizard::capture`].
+#[derive(Debug, Clone)]
+pub struct FlatSet {
+    /// Exposure used for both flats and dark-flats.
+    pub exposure: Duration,
+    /// Captured flats.
+    pub flats: Vec<CalibrationFrame>,
+    /// Captured dark-flats.
+    pub darks: Vec<CalibrationFrame>,
+}
+
+/// Exposure search and flat / dark-flat capture for one camera.
+pub struct FlatWizard<'a> {
+    camera: &'a Camera,
+    config: FlatConfig,
+}
+
+impl<'a> FlatWizard<'a> {
+    /// Creates a wizard for `camera`. The current ROI, binning and output
+    /// format are used unchanged.
+    pub fn new(camera: &'a Camera, config: FlatConfig) -> Self {
+        Self { camera, config }
+    }
+
+    /// Returns the configuration in use.
+    pub fn config(&self) -> &FlatConfig {
+        &self.config
+    }
+
+    /// Returns the target mean level in ADU for the current output format.
+    pub fn target_adu(&self) -> Result<f64> {
+        Ok(match self.config.target {
+            FlatTarget::Adu(adu) => adu,
+            FlatTarget::Fraction(f) => f * self.full_scale()?,
+        })
+    }
+
+    /// Searches for the exposure that produces the target mean level.
+    ///
+    /// Starts continuous capture for the duration of the search. The exposure
+    /// control is left at the last tested value.
+    pub fn find_exposure(&self) -> Result<ExposureSearch> {
+        let caps = self.exposure_caps()?;
+        let target = self.target_adu()?;
+        let full_scale = self.full_scale()?;
+
+        self.camera.start_capture()?;
+        let result = search_exposure(
+            &SearchParams {
+                min_us: caps.0,
+                max_us: caps.1,
+                initial_us: duration_to_us(self.config.initial_exposure),
+                target,
+                tolerance: self.config.tolerance,
+                full_scale,
+                max_iterations: self.config.max_iterations,
+            },
+            |us| Ok(self.expose(us)?.mean),
+        );
+        let stopped = self.camera.stop_capture();
+        let result = result?;
+        stopped?;
+        Ok(result)
+    }
+
+    /// Captures the configured number of flats at `exposure`, calls
+    /// `cover_for_darks` (e.g. to prompt the user to cover the optics or
+    /// switch off the panel), then captures the dark-flats.
+    ///
+    /// `cover_for_darks` is not called when
+    /// [`dark_count`](FlatConfig::dark_count) is 0.
+    pub fn capture(
+        &self,
+        exposure: Duration,
+        cover_for_darks: impl FnOnce() -> Result<()>,
+    ) -> Result<FlatSet> {
+        let full_scale = self.full_scale()?;
+        let us = duration_to_us(exposure);
+        self.camera.set_control(ControlType::Exposure, us, false)?;
+
+        let flats = self.capture_series(us, self.config.flat_count, full_scale)?;
+        let darks = if self.config.dark_count > 0 {
+            cover_for_darks()?;
+            self.capture_series(us, self.config.dark_count, full_scale)?
+        } else {
+            Vec::new()
+        };
+        Ok(FlatSet {
+            exposure,
+            flats,
+            darks,
+        })
+    }
+
+    fn capture_series(
+        &self,
+        exposure_us: i64,
+        count: usize,
+        full_scale: f64,
+    ) -> Result<Vec<CalibrationFrame>> {
+        self.camera.start_capture()?;
+        let result = (|| {
+            let wait = wait_ms(exposure_us);
+            for _ in 0..self.config.settle_frames {
+                self.camera.capture_frame(wait)?;
+            }
+            (0..count)
+                .map(|_| {
+                    let frame = self.camera.capture_frame(wait)?;
+                    Ok(calibration_frame(frame, full_scale))
+                })
+                .collect::<Result<Vec<_>>>()
+        })();
+        let stopped = self.camera.stop_capture();
+        let frames = result?;
+        stopped?;
+        Ok(frames)
+    }
+
+    /// Sets the exposure, discards settle frames and measures one frame.
+    fn expose(&self, us: i64) -> Result<FrameStats> {
+        self.camera.set_control(ControlType::Exposure, us, false)?;
+        let wait = wait_ms(us);
+        for _ in 0..self.config.settle_frames {
+            self.camera.capture_frame(wait)?;
+        }
+        Ok(self.camera.capture_frame(wait)?.stats())
+    }
+
+    fn exposure_caps(&self) -> Result<(i64, i64)> {
+        let caps = self
+            .camera
+            .caps(ControlType::Exposure)
+            .ok_or(Error::InvalidControlType)?;
+        Ok((caps.min_value.max(1), caps.max_value))
+    }
+
+    fn full_scale(&self) -> Result<f64> {
+        let ty = self.camera.output_image_type()?;
+        let depth = self.camera.property()?.max_bit_depth;
+        Ok(full_scale(ty, depth))
+    }
+}
+
+/// Full-scale level for `ty` given the ADC bit depth.
+pub fn full_scale(ty: ImageType, max_bit_depth: i32) -> f64 {
+    if ty.bytes_per_pixel() == 2 {
+        ((1u32 << max_bit_depth.clamp(1, 16)) - 1) as f64
+    } else {
+        u8::MAX as f64
+    }
+}
+
+fn calibration_frame(frame: Frame, full_scale: f64) -> CalibrationFrame {
+    let stats = frame.stats();
+    let color_channels = frame.channels().min(3);
+    let total = frame.width() * frame.height() * color_channels;
+    let saturated = (0..color_channels)
+        .flat_map(|c| frame.channel_samples(c))
+        .filter(|&v| v as f64 >= full_scale)
+        .count();
+    CalibrationFrame {
+        frame,
+        stats,
+        saturated_fraction: if total == 0 {
+            0.0
+        } else {
+            saturated as f64 / total as f64
+        },
+    }
+}
+
+fn duration_to_us(d: Duration) -> i64 {
+    d.as_micros().min(i64::MAX as u128) as i64
+}
+
+/// Frame timeout following the SDK recommendation of `exposure * 2 + 500 ms`.
+fn wait_ms(exposure_us: i64) -> i32 {
+    (exposure_us / 1000 * 2 + 500).min(i32::MAX as i64) as i32
+}
+
+struct SearchParams {
+    min_us: i64,
+    max_us: i64,
+    initial_us: i64,
+    target: f64,
+    tolerance: f64,
+    full_scale: f64,
+    max_iterations: usize,
+}
+
+/// Fraction of full scale above which the response is treated as clipped and
+/// the exposure is cut back instead of extrapolated.
+const CLIPPED_FRACTION: f64 = 0.95;
+
+/// Finds the exposure that yields `target`, assuming a mean level that grows
+/// linearly with exposure plus an unknown offset.
+///
+/// The first step scales the exposure proportionally; later steps use the
+/// secant through the last two measurements, which accounts for the bias
+/// level.
+fn search_exposure(
+    p: &SearchParams,
+    mut measure: impl FnMut(i64) -> Result<f64>,
+) -> Result<ExposureSearch> {
+    let us = |v: i64| Duration::from_micros(v as u64);
+    let mut exposure = p.initial_us.clamp(p.min_us, p.max_us);
+    let mut prev: Option<(i64, f64)> = None;
+    let mut best = (exposure, f64::NAN);
+
+    for _ in 0..p.max_iterations.max(1) {
+        let mean = measure(exposure)?;
+        if best.1.is_nan() || (mean - p.target).abs() < (best.1 - p.target).abs() {
+            best = (exposure, mean);
+        }
+        if (mean - p.target).abs() <= p.tolerance * p.target {
+            return Ok(ExposureSearch::Found {
+                exposure: us(exposure),
+                mean,
+            });
+        }
+        if exposure <= p.min_us && mean > p.target {
+            return Ok(ExposureSearch::TooBright {
+                min_exposure: us(p.min_us),
+                mean,
+            });
+        }
+        if exposure >= p.max_us && mean < p.target {
+            return Ok(ExposureSearch::TooDim {
+                max_exposure: us(p.max_us),
+                mean,
+            });
+        }
+
+        let next = if mean >= CLIPPED_FRACTION * p.full_scale {
+            exposure as f64 / 4.0
+        } else {
+            let secant = prev.and_then(|(t0, m0)| {
+                let slope = (mean - m0) / (exposure - t0) as f64;
+                (exposure != t0 && slope > 0.0 && slope.is_finite())
+                    .then(|| exposure as f64 + (p.target - mean) / slope)
+            });
+            secant.unwrap_or_else(|| exposure as f64 * p.target / mean.max(1.0))
+        };
+        prev = Some((exposure, mean));
+        exposure = (next.round() as i64).clamp(p.min_us, p.max_us);
+    }
+
+    Ok(ExposureSearch::NotConverged {
+        exposure: us(best.0),
+        mean: best.1,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(min_us: i64, max_us: i64) -> SearchParams {
+        SearchParams {
+            min_us,
+            max_us,
+            initial_us: 10_000,
+            target: 2048.0,
+            tolerance: 0.02,
+            full_scale: 4095.0,
+            max_iterations: 10,
+        }
+    }
+
+    /// Simulated 12-bit sensor: 200 ADU bias plus `rate` ADU per ms, clipped.
+    fn sensor(rate: f64) -> impl FnMut(i64) -> Result<f64> {
+        move |us| Ok((200.0 + rate * us as f64 / 1000.0).min(4095.0))
+    }
+
+    #[test]
+    fn finds_exposure_with_bias() {
+        let mut calls = 0;
+        let mut s = sensor(12.5);
+        let r = search_exposure(&params(32, 2_000_000_000), |us| {
+            calls += 1;
+            s(us)
+        })
+        .unwrap();
+        match r {
+            ExposureSearch::Found { exposure, mean } => {
+                assert!((mean - 2048.0).abs() <= 0.02 * 2048.0);
+                assert!((exposure.as_micros() as i64 - 147_840).abs() < 3_000);
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+        assert!(calls <= 4, "took {calls} iterations");
+    }
+
+    #[test]
+    fn recovers_from_saturation() {
+        let r = search_exposure(&params(32, 2_000_000_000), sensor(5000.0)).unwrap();
+        assert!(matches!(r, ExposureSearch::Found { .. }), "{r:?}");
+    }
+
+    #[test]
+    fn reports_too_dim_and_too_bright() {
+        let r = search_exposure(&params(32, 50_000), sensor(1.0)).unwrap();
+        assert!(matches!(r, ExposureSearch::TooDim { max_exposure, .. }
+            if max_exposure == Duration::from_micros(50_000)));
+
+        let r = search_exposure(&params(1_000, 1_000_000), sensor(1e6)).unwrap();
+        assert!(matches!(r, ExposureSearch::TooBright { .. }), "{r:?}");
+    }
+
+    #[test]
+    fn full_scale_follows_format() {
+        assert_eq!(full_scale(ImageType::Raw16, 12), 4095.0);
+        assert_eq!(full_scale(ImageType::Y8, 12), 255.0);
+        assert_eq!(full_scale(ImageType::Raw16, 16), 65535.0);
+    }
+
+    #[test]
+    fn calibration_frame_counts_saturation() {
+        let roi = crate::RoiFormat {
+            start_x: 0,
+            start_y: 0,
+            width: 4,
+            height: 1,
+            bin: 1,
+        };
+        let f = Frame::new(roi, ImageType::Y8, vec![10, 255, 255, 20]).unwrap();
+        let cf = calibration_frame(f, 255.0);
+        assert_eq!(cf.saturated_fraction, 0.5);
+        assert_eq!(cf.stats.max, 255);
+    }
+}
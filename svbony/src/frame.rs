@@ -0,0 +1,366 @@
+//! Owned frame buffers tagged with the ROI and pixel format they were
+//! captured with.
+
+use crate::stats::median;
+use crate::{ChannelOrder, Error, ImageType, Result, RoiFormat};
+
+/// Summary statistics of the samples in a [`Frame`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameStats {
+    /// Arithmetic mean.
+    pub mean: f64,
+    /// Median.
+    pub median: f64,
+    /// Population standard deviation.
+    pub std_dev: f64,
+    /// Smallest sample.
+    pub min: u16,
+    /// Largest sample.
+    pub max: u16,
+}
+
+/// A single frame as delivered by [`Camera::get_frame`](crate::Camera::get_frame),
+/// together with the ROI and output format it was captured with.
+///
+/// Pixel data is kept exactly as the SDK delivers it: one byte per sample for
+/// 8-bit formats, two little-endian bytes per sample for the 10- to 16-bit
+/// formats, and interleaved channels for `Rgb24` / `Rgb32` in the SDK's
+/// blue-first order (see [`ImageType::channel_order`]). Channel indices passed
+/// to [`sample`](Self::sample) refer to that buffer order.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    /// ROI and binning the frame was captured with.
+    pub roi: RoiFormat,
+    /// Pixel format of `data`.
+    pub image_type: ImageType,
+    /// Raw frame bytes.
+    pub data: Vec<u8>,
+}
+
+impl Frame {
+    /// Wraps an existing buffer.
+    ///
+    /// Returns [`Error::InvalidSize`] if `data` is not exactly
+    /// `width * height * bytes_per_pixel` bytes, and
+    /// [`Error::InvalidImageType`] for [`ImageType::Other`] formats.
+    pub fn new(roi: RoiFormat, image_type: ImageType, data: Vec<u8>) -> Result<Self> {
+        if let ImageType::Other(_) = image_type {
+            return Err(Error::InvalidImageType);
+        }
+        if roi.width < 0 || roi.height < 0 || data.len() != Self::buffer_size(&roi, image_type) {
+            return Err(Error::InvalidSize);
+        }
+        Ok(Self {
+            roi,
+            image_type,
+            data,
+        })
+    }
+
+    /// Allocates a zero-filled frame for the given ROI and format.
+    pub fn zeroed(roi: RoiFormat, image_type: ImageType) -> Self {
+        let data = vec![0u8; Self::buffer_size(&roi, image_type)];
+        Self {
+            roi,
+            image_type,
+            data,
+        }
+    }
+
+    /// Returns the buffer size in bytes needed for a frame with this ROI and
+    /// format.
+    pub fn buffer_size(roi: &RoiFormat, image_type: ImageType) -> usize {
+        roi.width.max(0) as usize * roi.height.max(0) as usize * image_type.bytes_per_pixel()
+    }
+
+    /// Frame width in pixels.
+    pub fn width(&self) -> usize {
+        self.roi.width as usize
+    }
+
+    /// Frame height in pixels.
+    pub fn height(&self) -> usize {
+        self.roi.height as usize
+    }
+
+    /// Number of interleaved channels per pixel.
+    pub fn channels(&self) -> usize {
+        self.image_type.channels()
+    }
+
+    /// Reads channel `c` of the pixel at `(x, y)`.
+    ///
+    /// 8-bit samples are returned unscaled (0-255).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the coordinates or channel are out of range.
+    pub fn sample(&self, x: usize, y: usize, c: usize) -> u16 {
+        let i = self.sample_index(x, y, c);
+        if self.is_16bit() {
+            u16::from_le_bytes([self.data[2 * i], self.data[2 * i + 1]])
+        } else {
+            self.data[i] as u16
+        }
+    }
+
+    /// Writes channel `c` of the pixel at `(x, y)`.
+    ///
+    /// For 8-bit formats the value is saturated to 255.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the coordinates or channel are out of range.
+    pub fn set_sample(&mut self, x: usize, y: usize, c: usize, value: u16) {
+        let i = self.sample_index(x, y, c);
+        if self.is_16bit() {
+            self.data[2 * i..2 * i + 2].copy_from_slice(&value.to_le_bytes());
+        } else {
+            self.data[i] = value.min(u8::MAX as u16) as u8;
+        }
+    }
+
+    /// Returns every sample of channel `c` in row-major order.
+    pub fn channel_samples(&self, c: usize) -> Vec<u16> {
+        let (w, h) = (self.width(), self.height());
+        let mut out = Vec::with_capacity(w * h);
+        for y in 0..h {
+            for x in 0..w {
+                out.push(self.sample(x, y, c));
+            }
+        }
+        out
+    }
+
+    /// Returns the pixels in red-green-blue order, 3 bytes per pixel.
+    ///
+    /// Only valid for `Rgb24` and `Rgb32`; the alpha channel of `Rgb32` is
+    /// dropped. Returns [`Error::InvalidImageType`] for other formats.
+    pub fn to_rgb(&self) -> Result<Vec<u8>> {
+        let order = self
+            .image_type
+            .channel_order()
+            .ok_or(Error::InvalidImageType)?;
+        let [r, g, b] = order.rgb_indices();
+        Ok(self
+            .data
+            .chunks_exact(self.channels())
+            .flat_map(|px| [px[r], px[g], px[b]])
+            .collect())
+    }
+
+    /// Returns the pixels in red-green-blue-alpha order, 4 bytes per pixel.
+    ///
+    /// Only valid for `Rgb24` (alpha set to 255) and `Rgb32` (alpha copied).
+    /// Returns [`Error::InvalidImageType`] for other formats.
+    pub fn to_rgba(&self) -> Result<Vec<u8>> {
+        let order = self
+            .image_type
+            .channel_order()
+            .ok_or(Error::InvalidImageType)?;
+        let [r, g, b] = order.rgb_indices();
+        let alpha = order.alpha_index();
+        Ok(self
+            .data
+            .chunks_exact(self.channels())
+            .flat_map(|px| [px[r], px[g], px[b], alpha.map_or(u8::MAX, |a| px[a])])
+            .collect())
+    }
+
+    /// Borrows an `Rgb24` frame as packed BGR bytes, as expected by OpenCV's
+    /// `CV_8UC3`. Returns [`Error::InvalidImageType`] for other formats.
+    pub fn as_bgr(&self) -> Result<&[u8]> {
+        match self.image_type.channel_order() {
+            Some(ChannelOrder::Bgr) => Ok(&self.data),
+            _ => Err(Error::InvalidImageType),
+        }
+    }
+
+    /// Borrows an `Rgb32` frame as packed BGRA bytes, as expected by OpenCV's
+    /// `CV_8UC4`. Returns [`Error::InvalidImageType`] for other formats.
+    pub fn as_bgra(&self) -> Result<&[u8]> {
+        match self.image_type.channel_order() {
+            Some(ChannelOrder::Bgra) => Ok(&self.data),
+            _ => Err(Error::InvalidImageType),
+        }
+    }
+
+    /// Computes summary statistics over all color samples.
+    ///
+    /// For `Rgb32` the fourth (alpha / padding) channel is ignored.
+    pub fn stats(&self) -> FrameStats {
+        let color_channels = self.channels().min(3);
+        let mut values: Vec<f64> = (0..color_channels)
+            .flat_map(|c| self.channel_samples(c))
+            .map(f64::from)
+            .collect();
+        if values.is_empty() {
+            return FrameStats {
+                mean: 0.0,
+                median: 0.0,
+                std_dev: 0.0,
+                min: 0,
+                max: 0,
+            };
+        }
+        let n = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / n;
+        let var = values.iter().map(|v| (v - mean) * (v - mean)).sum::<f64>() / n;
+        let min = values.iter().copied().fold(f64::INFINITY, f64::min) as u16;
+        let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max) as u16;
+        FrameStats {
+            mean,
+            median: median(&mut values),
+            std_dev: var.sqrt(),
+            min,
+            max,
+        }
+    }
+
+    /// Converts the frame into an [`image::DynamicImage`].
+    ///
+    /// See [`Camera::get_image`](crate::Camera::get_image) for the pixel
+    /// format mapping. Color frames are reordered from the SDK's blue-first
+    /// layout into RGB / RGBA.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `data` does not match the ROI, or for [`ImageType::Other`]
+    /// formats; frames from [`Frame::new`] or
+    /// [`Camera::capture_frame`](crate::Camera::capture_frame) never do.
+    #[cfg(feature = "image")]
+    pub fn into_image(self) -> image::DynamicImage {
+        use image::{DynamicImage, GrayImage, ImageBuffer, Rgb, Rgba};
+
+        let w = self.roi.width as u32;
+        let h = self.roi.height as u32;
+        match self.image_type {
+            ImageType::Raw8 | ImageType::Y8 => {
+                DynamicImage::ImageLuma8(GrayImage::from_raw(w, h, self.data).unwrap())
+            }
+            ImageType::Raw10
+            | ImageType::Raw12
+            | ImageType::Raw14
+            | ImageType::Raw16
+            | ImageType::Y10
+            | ImageType::Y12
+            | ImageType::Y14
+            | ImageType::Y16 => {
+                let pixels: Vec<u16> = self
+                    .data
+                    .chunks_exact(2)
+                    .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                    .collect();
+                DynamicImage::ImageLuma16(ImageBuffer::from_raw(w, h, pixels).unwrap())
+            }
+            ImageType::Rgb24 => {
+                let rgb = self.to_rgb().unwrap();
+                DynamicImage::ImageRgb8(ImageBuffer::<Rgb<u8>, _>::from_raw(w, h, rgb).unwrap())
+            }
+            ImageType::Rgb32 => {
+                let rgba = self.to_rgba().unwrap();
+                DynamicImage::ImageRgba8(ImageBuffer::<Rgba<u8>, _>::from_raw(w, h, rgba).unwrap())
+            }
+            ImageType::Other(raw) => panic!("cannot convert unknown image type {raw}"),
+        }
+    }
+
+    fn is_16bit(&self) -> bool {
+        self.image_type.bytes_per_pixel() == 2
+    }
+
+    fn sample_index(&self, x: usize, y: usize, c: usize) -> usize {
+        let channels = self.channels();
+        assert!(
+            x < self.width() && y < self.height() && c < channels,
+            "sample ({x}, {y}, {c}) out of range"
+        );
+        (y * self.width() + x) * channels + c
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roi(w: i32, h: i32) -> RoiFormat {
+        RoiFormat {
+            start_x: 0,
+            start_y: 0,
+            width: w,
+            height: h,
+            bin: 1,
+        }
+    }
+
+    #[test]
+    fn new_checks_buffer_size() {
+        assert!(Frame::new(roi(4, 2), ImageType::Raw16, vec![0; 16]).is_ok());
+        assert_eq!(
+            Frame::new(roi(4, 2), ImageType::Raw16, vec![0; 8]).unwrap_err(),
+            Error::InvalidSize
+        );
+    }
+
+    #[test]
+    fn samples_are_little_endian() {
+        let mut f = Frame::zeroed(roi(2, 1), ImageType::Y16);
+        f.set_sample(1, 0, 0, 0x1234);
+        assert_eq!(&f.data[2..], &[0x34, 0x12]);
+        assert_eq!(f.sample(1, 0, 0), 0x1234);
+
+        let mut f8 = Frame::zeroed(roi(2, 1), ImageType::Y8);
+        f8.set_sample(0, 0, 0, 1000);
+        assert_eq!(f8.sample(0, 0, 0), 255);
+    }
+
+    #[test]
+    fn stats_ignore_rgb32_padding() {
+        let mut f = Frame::zeroed(roi(2, 1), ImageType::Rgb32);
+        f.data.copy_from_slice(&[10, 20, 30, 255, 10, 20, 30, 255]);
+        let s = f.stats();
+        assert_eq!(s.mean, 20.0);
+        assert_eq!(s.median, 20.0);
+        assert_eq!((s.min, s.max), (10, 30));
+    }
+
+    #[test]
+    fn rgb24_is_bgr_in_buffer() {
+        // One pure-red and one pure-blue pixel as the SDK delivers them.
+        let f = Frame::new(roi(2, 1), ImageType::Rgb24, vec![0, 0, 255, 255, 0, 0]).unwrap();
+        assert_eq!(f.to_rgb().unwrap(), vec![255, 0, 0, 0, 0, 255]);
+        assert_eq!(f.to_rgba().unwrap(), vec![255, 0, 0, 255, 0, 0, 255, 255]);
+        assert_eq!(f.as_bgr().unwrap(), &[0, 0, 255, 255, 0, 0]);
+        assert_eq!(f.as_bgra().unwrap_err(), Error::InvalidImageType);
+    }
+
+    #[test]
+    fn rgb32_is_bgra_in_buffer() {
+        let f = Frame::new(roi(1, 1), ImageType::Rgb32, vec![10, 20, 30, 40]).unwrap();
+        assert_eq!(f.to_rgb().unwrap(), vec![30, 20, 10]);
+        assert_eq!(f.to_rgba().unwrap(), vec![30, 20, 10, 40]);
+        assert_eq!(f.as_bgra().unwrap(), &[10, 20, 30, 40]);
+        assert_eq!(f.as_bgr().unwrap_err(), Error::InvalidImageType);
+    }
+
+    #[test]
+    fn mono_has_no_rgb_conversion() {
+        let f = Frame::zeroed(roi(2, 2), ImageType::Y8);
+        assert_eq!(f.to_rgb().unwrap_err(), Error::InvalidImageType);
+        assert_eq!(ImageType::Y16.channel_order(), None);
+        assert_eq!(ChannelOrder::Bgra.rgb_indices(), [2, 1, 0]);
+        assert_eq!(ChannelOrder::Bgra.alpha_index(), Some(3));
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn into_image_swaps_to_rgb() {
+        let f = Frame::new(roi(1, 1), ImageType::Rgb24, vec![1, 2, 3]).unwrap();
+        let img = f.into_image();
+        assert_eq!(img.as_rgb8().unwrap()[(0, 0)].0, [3, 2, 1]);
+
+        let f = Frame::new(roi(1, 1), ImageType::Rgb32, vec![1, 2, 3, 4]).unwrap();
+        let img = f.into_image();
+        assert_eq!(img.as_rgba8().unwrap()[(0, 0)].0, [3, 2, 1, 4]);
+    }
+}
@@ -0,0 +1,945 @@
+//! Asynchronous ST4 pulse guiding.
+//!
+//! [`Camera::pulse_guide`] blocks for the whole pulse and drives a single
+//! direction. A [`Guider`] runs one worker thread per axis, so a right
+//! ascension (East/West) and a declination (North/South) correction can be
+//! in flight together while the caller carries on. Every request returns a
+//! handle to wait for or cancel the pulse, and durations are validated before
+//! anything reaches the camera.
+//!
+//! The SDK call blocks for the length of the pulse and cannot be
+//! interrupted, so pulses are sent in slices of at most
+//! [`GuiderConfig::slice`]; cancelling takes effect at the next slice
+//! boundary. The camera's [`St4Port`] serializes SDK calls from the two
+//! axes, which means that while both axes are busy their slices alternate
+//! and each pulse takes up to twice its duration in wall-clock time. The
+//! total time each direction is driven is unchanged.
+//!
+//! The guider drives any [`GuidePort`], so mount drivers can use a camera's
+//! ST4 port through the trait alone, and a [`RecordingPort`] logs every
+//! pulse so guiding can be unit-tested and [replayed](replay).
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use std::time::Duration;
+//! use svbony::guide::{GuidePulse, Guider, GuiderConfig};
+//! use svbony::{Camera, GuideDirection};
+//!
+//! let cam = Arc::new(Camera::open(0)?);
+//! let guider = Guider::new(cam, GuiderConfig::default())?;
+//! let handle = guider.guide(
+//!     Some(GuidePulse::new(GuideDirection::East, Duration::from_millis(350))),
+//!     Some(GuidePulse::new(GuideDirection::North, Duration::from_millis(120))),
+//! )?;
+//! // ... measure the next guide star position ...
+//! let outcome = handle.wait()?;
+//! println!("RA {:?}, Dec {:?}", outcome.ra, outcome.dec);
+//! # Ok::<(), svbony::Error>(())
+//! ```
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::{Camera, Error, GuideDirection, Result};
+
+/// Mount axis moved by a [`GuideDirection`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Axis {
+    /// Right ascension: [`GuideDirection::East`] and [`GuideDirection::West`].
+    Ra,
+    /// Declination: [`GuideDirection::North`] and [`GuideDirection::South`].
+    Dec,
+}
+
+impl Axis {
+    /// Returns the axis `dir` moves, or `None` for
+    /// [`GuideDirection::Other`].
+    pub fn of(dir: GuideDirection) -> Option<Self> {
+        match dir {
+            GuideDirection::East | GuideDirection::West => Some(Self::Ra),
+            GuideDirection::North | GuideDirection::South => Some(Self::Dec),
+            GuideDirection::Other(_) => None,
+        }
+    }
+}
+
+/// A single guide pulse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GuidePulse {
+    /// Direction to move the mount.
+    pub direction: GuideDirection,
+    /// Pulse length; a whole number of milliseconds.
+    pub duration: Duration,
+}
+
+impl GuidePulse {
+    /// Creates a pulse without checking it; see [`validate`](Self::validate).
+    pub fn new(direction: GuideDirection, duration: Duration) -> Self {
+        Self {
+            direction,
+            duration,
+        }
+    }
+
+    /// Checks the direction and that the duration is a whole number of
+    /// milliseconds in `1 ms..=max`, returning the axis it moves.
+    pub fn validate(&self, max: Duration) -> Result<Axis> {
+        let axis = Axis::of(self.direction).ok_or(Error::InvalidDirection)?;
+        let d = self.duration;
+        if d < Duration::from_millis(1) || d > max || d.subsec_nanos() % 1_000_000 != 0 {
+            return Err(Error::InvalidArgument(format!(
+                "guide pulse {d:?} is not a whole number of milliseconds in 1 ms..={max:?}"
+            )));
+        }
+        Ok(axis)
+    }
+}
+
+/// Guider settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GuiderConfig {
+    /// Longest single SDK call. Bounds how long a cancelled pulse keeps
+    /// running and how finely two busy axes interleave.
+    pub slice: Duration,
+    /// Longest pulse accepted.
+    pub max_pulse: Duration,
+}
+
+impl Default for GuiderConfig {
+    fn default() -> Self {
+        Self {
+            slice: Duration::from_millis(50),
+            max_pulse: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Result of a pulse that ran to completion or was cancelled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PulseOutcome {
+    /// The pulse as requested.
+    pub pulse: GuidePulse,
+    /// Time the direction was actually driven.
+    pub delivered: Duration,
+    /// The pulse was cancelled before all of it was delivered.
+    pub cancelled: bool,
+}
+
+impl PulseOutcome {
+    /// Returns `true` if the full duration was delivered.
+    pub fn is_complete(&self) -> bool {
+        self.delivered == self.pulse.duration
+    }
+}
+
+/// Sends `pulse` in slices of at most `slice` through `send`, checking
+/// `cancelled` before each one.
+fn deliver(
+    pulse: GuidePulse,
+    slice: Duration,
+    cancelled: impl Fn() -> bool,
+    mut send: impl FnMut(GuideDirection, Duration) -> Result<()>,
+) -> Result<PulseOutcome> {
+    let mut delivered = Duration::ZERO;
+    while delivered < pulse.duration {
+        if cancelled() {
+            return Ok(PulseOutcome {
+                pulse,
+                delivered,
+                cancelled: true,
+            });
+        }
+        let step = slice.min(pulse.duration - delivered);
+        send(pulse.direction, step)?;
+        delivered += step;
+    }
+    Ok(PulseOutcome {
+        pulse,
+        delivered,
+        cancelled: false,
+    })
+}
+
+/// What a [`GuidePort`] can drive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GuidePortCapabilities {
+    /// East/West pulses are accepted.
+    pub ra: bool,
+    /// North/South pulses are accepted.
+    pub dec: bool,
+    /// Longest single [`GuidePort::pulse`] call.
+    pub max_pulse: Duration,
+    /// Calls from different threads run one at a time rather than
+    /// overlapping.
+    pub serialized: bool,
+}
+
+impl GuidePortCapabilities {
+    /// Returns `true` if pulses on `axis` are accepted.
+    pub fn supports(&self, axis: Axis) -> bool {
+        match axis {
+            Axis::Ra => self.ra,
+            Axis::Dec => self.dec,
+        }
+    }
+}
+
+/// An ST4-style guide port: anything that can drive a mount in a
+/// [`GuideDirection`] for a given time.
+pub trait GuidePort: Send + Sync {
+    /// Drives `direction` for `duration`, returning when the pulse has
+    /// ended.
+    fn pulse(&self, direction: GuideDirection, duration: Duration) -> Result<()>;
+
+    /// Returns `true` while a pulse is being driven.
+    fn is_pulsing(&self) -> bool;
+
+    /// Returns what the port can drive; [`Guider`] checks pulses against it.
+    fn capabilities(&self) -> GuidePortCapabilities;
+}
+
+impl<P: GuidePort + ?Sized> GuidePort for Arc<P> {
+    fn pulse(&self, direction: GuideDirection, duration: Duration) -> Result<()> {
+        (**self).pulse(direction, duration)
+    }
+
+    fn is_pulsing(&self) -> bool {
+        (**self).is_pulsing()
+    }
+
+    fn capabilities(&self) -> GuidePortCapabilities {
+        (**self).capabilities()
+    }
+}
+
+/// Longest pulse [`Camera::pulse_guide`] can express.
+pub const ST4_MAX_PULSE: Duration = Duration::from_millis(i32::MAX as u64);
+
+/// The ST4 port of a camera.
+///
+/// Pulse calls are serialized, as the SDK requires; create one port per
+/// camera and share it.
+pub struct St4Port {
+    camera: Arc<Camera>,
+    lock: Mutex<()>,
+    pulsing: AtomicBool,
+}
+
+impl St4Port {
+    /// Returns [`Error::Unsupported`] if the camera has no ST4 port.
+    pub fn new(camera: Arc<Camera>) -> Result<Self> {
+        if !camera.can_pulse_guide()? {
+            return Err(Error::Unsupported("pulse guiding".into()));
+        }
+        Ok(Self {
+            camera,
+            lock: Mutex::new(()),
+            pulsing: AtomicBool::new(false),
+        })
+    }
+
+    /// Returns the camera whose ST4 port is driven.
+    pub fn camera(&self) -> &Arc<Camera> {
+        &self.camera
+    }
+}
+
+impl GuidePort for St4Port {
+    /// Validates the pulse like [`GuidePulse::validate`] with
+    /// [`ST4_MAX_PULSE`], then waits for any other pulse to end before
+    /// sending it.
+    fn pulse(&self, direction: GuideDirection, duration: Duration) -> Result<()> {
+        GuidePulse::new(direction, duration).validate(ST4_MAX_PULSE)?;
+        let _guard = self.lock.lock().unwrap();
+        self.pulsing.store(true, Ordering::Relaxed);
+        let result = self
+            .camera
+            .pulse_guide(direction, duration.as_millis() as i32);
+        self.pulsing.store(false, Ordering::Relaxed);
+        result
+    }
+
+    fn is_pulsing(&self) -> bool {
+        self.pulsing.load(Ordering::Relaxed)
+    }
+
+    fn capabilities(&self) -> GuidePortCapabilities {
+        GuidePortCapabilities {
+            ra: true,
+            dec: true,
+            max_pulse: ST4_MAX_PULSE,
+            serialized: true,
+        }
+    }
+}
+
+/// A pulse logged by a [`RecordingPort`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PulseRecord {
+    /// Direction the pulse was sent in.
+    pub direction: GuideDirection,
+    /// Length of the pulse.
+    pub duration: Duration,
+    /// Time from the creation of the port to the start of the pulse.
+    pub started: Duration,
+}
+
+/// A [`GuidePort`] that logs every pulse.
+///
+/// On its own it stands in for a mount in tests, either returning at once
+/// ([`new`](Self::new)) or taking as long as each pulse
+/// ([`realtime`](Self::realtime)). [`wrapping`](Self::wrapping) puts it in
+/// front of a real port to capture a guiding session.
+pub struct RecordingPort {
+    inner: Option<Box<dyn GuidePort>>,
+    realtime: bool,
+    capabilities: GuidePortCapabilities,
+    epoch: Instant,
+    active: AtomicUsize,
+    records: Mutex<Vec<PulseRecord>>,
+}
+
+impl RecordingPort {
+    /// A port that records pulses and returns immediately.
+    pub fn new() -> Self {
+        Self {
+            inner: None,
+            realtime: false,
+            capabilities: GuidePortCapabilities {
+                ra: true,
+                dec: true,
+                max_pulse: ST4_MAX_PULSE,
+                serialized: false,
+            },
+            epoch: Instant::now(),
+            active: AtomicUsize::new(0),
+            records: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// A port that records pulses and sleeps for each one.
+    pub fn realtime() -> Self {
+        Self {
+            realtime: true,
+            ..Self::new()
+        }
+    }
+
+    /// Forwards every pulse to `inner`, recording those that succeed.
+    pub fn wrapping(inner: impl GuidePort + 'static) -> Self {
+        Self {
+            capabilities: inner.capabilities(),
+            inner: Some(Box::new(inner)),
+            ..Self::new()
+        }
+    }
+
+    /// Overrides the reported capabilities. Pulses on an axis that is not
+    /// supported are rejected with [`Error::Unsupported`].
+    pub fn with_capabilities(mut self, capabilities: GuidePortCapabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Returns the pulses recorded so far, in the order they started.
+    pub fn records(&self) -> Vec<PulseRecord> {
+        self.records.lock().unwrap().clone()
+    }
+
+    /// Returns and clears the recorded pulses.
+    pub fn take_records(&self) -> Vec<PulseRecord> {
+        std::mem::take(&mut *self.records.lock().unwrap())
+    }
+
+    /// Total time `direction` was driven.
+    pub fn total(&self, direction: GuideDirection) -> Duration {
+        self.records
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|r| r.direction == direction)
+            .map(|r| r.duration)
+            .sum()
+    }
+}
+
+impl Default for RecordingPort {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GuidePort for RecordingPort {
+    fn pulse(&self, direction: GuideDirection, duration: Duration) -> Result<()> {
+        let axis = Axis::of(direction).ok_or(Error::InvalidDirection)?;
+        if !self.capabilities.supports(axis) {
+            return Err(Error::Unsupported(format!("{axis:?} guiding")));
+        }
+        let started = self.epoch.elapsed();
+        self.active.fetch_add(1, Ordering::Relaxed);
+        let result = match &self.inner {
+            Some(inner) => inner.pulse(direction, duration),
+            None => {
+                if self.realtime {
+                    thread::sleep(duration);
+                }
+                Ok(())
+            }
+        };
+        self.active.fetch_sub(1, Ordering::Relaxed);
+        if result.is_ok() {
+            let mut records = self.records.lock().unwrap();
+            // Keep start order when pulses overlap.
+            let at = records.partition_point(|r| r.started <= started);
+            records.insert(
+                at,
+                PulseRecord {
+                    direction,
+                    duration,
+                    started,
+                },
+            );
+        }
+        result
+    }
+
+    fn is_pulsing(&self) -> bool {
+        self.active.load(Ordering::Relaxed) > 0
+    }
+
+    fn capabilities(&self) -> GuidePortCapabilities {
+        self.capabilities
+    }
+}
+
+/// Sends `records` to `port` in order.
+///
+/// With `timed`, each pulse starts at the same offset from the first as
+/// when it was recorded (later, if the previous pulse is still running);
+/// otherwise pulses are sent back to back.
+pub fn replay(records: &[PulseRecord], port: &dyn GuidePort, timed: bool) -> Result<()> {
+    let start = Instant::now();
+    let first = records.first().map_or(Duration::ZERO, |r| r.started);
+    for record in records {
+        if timed {
+            let due = start + record.started.saturating_sub(first);
+            thread::sleep(due.saturating_duration_since(Instant::now()));
+        }
+        port.pulse(record.direction, record.duration)?;
+    }
+    Ok(())
+}
+
+struct Job {
+    pulse: GuidePulse,
+    cancel: Arc<AtomicBool>,
+    done: mpsc::Sender<Result<PulseOutcome>>,
+}
+
+/// Worker thread delivering the pulses of one axis in order.
+struct Worker {
+    jobs: Option<mpsc::Sender<Job>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Worker {
+    fn spawn<P: GuidePort + 'static>(
+        port: Arc<P>,
+        slice: Duration,
+        shutdown: Arc<AtomicBool>,
+    ) -> Self {
+        let (jobs, queue) = mpsc::channel::<Job>();
+        let thread = thread::spawn(move || {
+            for job in queue {
+                let cancelled =
+                    || job.cancel.load(Ordering::Relaxed) || shutdown.load(Ordering::Relaxed);
+                let outcome = deliver(job.pulse, slice, cancelled, |dir, d| port.pulse(dir, d));
+                // The handle may have been dropped.
+                let _ = job.done.send(outcome);
+            }
+        });
+        Self {
+            jobs: Some(jobs),
+            thread: Some(thread),
+        }
+    }
+
+    fn submit(&self, pulse: GuidePulse) -> PulseHandle {
+        let cancel = Arc::new(AtomicBool::new(false));
+        let (done, result) = mpsc::channel();
+        if let Some(jobs) = &self.jobs {
+            let _ = jobs.send(Job {
+                pulse,
+                cancel: Arc::clone(&cancel),
+                done,
+            });
+        }
+        PulseHandle {
+            pulse,
+            cancel,
+            result,
+            outcome: None,
+        }
+    }
+
+    fn shutdown(&mut self) {
+        self.jobs.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Non-blocking pulse guider; see the [module docs](self).
+///
+/// Pulses on the same axis are delivered one after another in the order
+/// they were requested. Dropping the guider cancels in-flight and queued
+/// pulses and waits for the current slice to finish.
+pub struct Guider<P: GuidePort + 'static = St4Port> {
+    port: Arc<P>,
+    config: GuiderConfig,
+    shutdown: Arc<AtomicBool>,
+    ra: Worker,
+    dec: Worker,
+}
+
+impl Guider {
+    /// Starts a guider on the [`St4Port`] of `camera`.
+    ///
+    /// Returns [`Error::Unsupported`] if the camera has no ST4 port; see
+    /// also [`with_port`](Self::with_port).
+    pub fn new(camera: Arc<Camera>, config: GuiderConfig) -> Result<Self> {
+        Self::with_port(St4Port::new(camera)?, config)
+    }
+}
+
+impl<P: GuidePort + 'static> Guider<P> {
+    /// Starts the axis workers for `port`.
+    ///
+    /// Returns [`Error::InvalidArgument`] if `config.slice` is not a whole
+    /// number of milliseconds between 1 ms and the port's longest pulse.
+    pub fn with_port(port: P, config: GuiderConfig) -> Result<Self> {
+        let slice = config.slice;
+        if slice < Duration::from_millis(1)
+            || slice > port.capabilities().max_pulse
+            || slice.subsec_nanos() % 1_000_000 != 0
+        {
+            return Err(Error::InvalidArgument(format!(
+                "guide slice {slice:?} is not a whole number of milliseconds \
+                 within the port's pulse range"
+            )));
+        }
+        let port = Arc::new(port);
+        let shutdown = Arc::new(AtomicBool::new(false));
+        Ok(Self {
+            ra: Worker::spawn(Arc::clone(&port), slice, Arc::clone(&shutdown)),
+            dec: Worker::spawn(Arc::clone(&port), slice, Arc::clone(&shutdown)),
+            port,
+            config,
+            shutdown,
+        })
+    }
+
+    /// Returns the guider's settings.
+    pub fn config(&self) -> &GuiderConfig {
+        &self.config
+    }
+
+    /// Returns the port pulses are sent to.
+    pub fn port(&self) -> &P {
+        &self.port
+    }
+
+    /// Returns `true` while the port is driving a pulse.
+    pub fn is_pulsing(&self) -> bool {
+        self.port.is_pulsing()
+    }
+
+    /// Queues `pulse` on its axis and returns without waiting.
+    ///
+    /// Returns [`Error::Unsupported`] if the port cannot drive that axis.
+    pub fn pulse(&self, pulse: GuidePulse) -> Result<PulseHandle> {
+        let axis = self.check(pulse)?;
+        Ok(self.worker(axis).submit(pulse))
+    }
+
+    /// Queues an RA and a Dec correction to run concurrently.
+    ///
+    /// `ra` must be East or West and `dec` North or South. Both are
+    /// validated before either is queued.
+    pub fn guide(&self, ra: Option<GuidePulse>, dec: Option<GuidePulse>) -> Result<GuideHandle> {
+        for (pulse, axis) in [(ra, Axis::Ra), (dec, Axis::Dec)] {
+            if let Some(pulse) = pulse {
+                if self.check(pulse)? != axis {
+                    return Err(Error::InvalidArgument(format!(
+                        "{:?} does not move the {axis:?} axis",
+                        pulse.direction
+                    )));
+                }
+            }
+        }
+        Ok(GuideHandle {
+            ra: ra.map(|p| self.ra.submit(p)),
+            dec: dec.map(|p| self.dec.submit(p)),
+        })
+    }
+
+    fn check(&self, pulse: GuidePulse) -> Result<Axis> {
+        let axis = pulse.validate(self.config.max_pulse)?;
+        if !self.port.capabilities().supports(axis) {
+            return Err(Error::Unsupported(format!("{axis:?} guiding")));
+        }
+        Ok(axis)
+    }
+
+    fn worker(&self, axis: Axis) -> &Worker {
+        match axis {
+            Axis::Ra => &self.ra,
+            Axis::Dec => &self.dec,
+        }
+    }
+}
+
+impl<P: GuidePort + 'static> Drop for Guider<P> {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::Relaxed);
+        self.ra.shutdown();
+        self.dec.shutdown();
+    }
+}
+
+/// Handle to a queued or in-flight pulse.
+///
+/// Dropping the handle does not cancel the pulse.
+pub struct PulseHandle {
+    pulse: GuidePulse,
+    cancel: Arc<AtomicBool>,
+    result: mpsc::Receiver<Result<PulseOutcome>>,
+    outcome: Option<Result<PulseOutcome>>,
+}
+
+impl PulseHandle {
+    /// Returns the pulse this handle tracks.
+    pub fn pulse(&self) -> GuidePulse {
+        self.pulse
+    }
+
+    /// Requests cancellation. A pulse that has not started is skipped; one
+    /// in flight stops after the current slice.
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns `true` once the pulse has completed, been cancelled or failed.
+    pub fn is_finished(&mut self) -> bool {
+        if self.outcome.is_none() {
+            match self.result.try_recv() {
+                Ok(outcome) => self.outcome = Some(outcome),
+                Err(TryRecvError::Empty) => {}
+                Err(TryRecvError::Disconnected) => self.outcome = Some(Ok(self.abandoned())),
+            }
+        }
+        self.outcome.is_some()
+    }
+
+    /// Waits up to `timeout` for the pulse to finish. Returns `None` if it
+    /// is still running.
+    pub fn wait_timeout(&mut self, timeout: Duration) -> Option<Result<PulseOutcome>> {
+        if self.outcome.is_none() {
+            match self.result.recv_timeout(timeout) {
+                Ok(outcome) => self.outcome = Some(outcome),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => self.outcome = Some(Ok(self.abandoned())),
+            }
+        }
+        self.outcome.clone()
+    }
+
+    /// Blocks until the pulse has finished.
+    pub fn wait(mut self) -> Result<PulseOutcome> {
+        match self.outcome.take() {
+            Some(outcome) => outcome,
+            None => self.result.recv().unwrap_or_else(|_| Ok(self.abandoned())),
+        }
+    }
+
+    /// Outcome for a pulse whose worker went away without reporting.
+    fn abandoned(&self) -> PulseOutcome {
+        PulseOutcome {
+            pulse: self.pulse,
+            delivered: Duration::ZERO,
+            cancelled: true,
+        }
+    }
+}
+
+/// Outcomes of the pulses started by [`Guider::guide`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GuideOutcome {
+    /// Outcome of the RA pulse, if one was requested.
+    pub ra: Option<PulseOutcome>,
+    /// Outcome of the Dec pulse, if one was requested.
+    pub dec: Option<PulseOutcome>,
+}
+
+/// Handles to an RA and a Dec pulse started together.
+pub struct GuideHandle {
+    /// The RA pulse, if one was requested.
+    pub ra: Option<PulseHandle>,
+    /// The Dec pulse, if one was requested.
+    pub dec: Option<PulseHandle>,
+}
+
+impl GuideHandle {
+    /// Cancels both pulses.
+    pub fn cancel(&self) {
+        self.ra
+            .iter()
+            .chain(&self.dec)
+            .for_each(PulseHandle::cancel);
+    }
+
+    /// Returns `true` once both pulses have finished.
+    pub fn is_finished(&mut self) -> bool {
+        self.ra.as_mut().map_or(true, PulseHandle::is_finished)
+            & self.dec.as_mut().map_or(true, PulseHandle::is_finished)
+    }
+
+    /// Blocks until both pulses have finished. Returns the first error if
+    /// either failed.
+    pub fn wait(self) -> Result<GuideOutcome> {
+        let ra = self.ra.map(PulseHandle::wait).transpose();
+        let dec = self.dec.map(PulseHandle::wait).transpose();
+        Ok(GuideOutcome { ra: ra?, dec: dec? })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    #[test]
+    fn axes() {
+        assert_eq!(Axis::of(GuideDirection::East), Some(Axis::Ra));
+        assert_eq!(Axis::of(GuideDirection::West), Some(Axis::Ra));
+        assert_eq!(Axis::of(GuideDirection::North), Some(Axis::Dec));
+        assert_eq!(Axis::of(GuideDirection::South), Some(Axis::Dec));
+        assert_eq!(Axis::of(GuideDirection::Other(7)), None);
+    }
+
+    #[test]
+    fn durations_are_validated() {
+        let max = ms(1000);
+        let pulse = |d| GuidePulse::new(GuideDirection::West, d);
+        assert_eq!(pulse(ms(1)).validate(max), Ok(Axis::Ra));
+        assert_eq!(pulse(max).validate(max), Ok(Axis::Ra));
+        for bad in [Duration::ZERO, ms(1001), Duration::from_micros(1500)] {
+            assert!(
+                matches!(pulse(bad).validate(max), Err(Error::InvalidArgument(_))),
+                "{bad:?}"
+            );
+        }
+        assert_eq!(
+            GuidePulse::new(GuideDirection::Other(9), ms(10)).validate(max),
+            Err(Error::InvalidDirection)
+        );
+    }
+
+    #[test]
+    fn pulses_are_sliced() {
+        let mut sent = Vec::new();
+        let pulse = GuidePulse::new(GuideDirection::North, ms(120));
+        let outcome = deliver(
+            pulse,
+            ms(50),
+            || false,
+            |dir, d| {
+                sent.push((dir, d));
+                Ok(())
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            sent,
+            [
+                (GuideDirection::North, ms(50)),
+                (GuideDirection::North, ms(50)),
+                (GuideDirection::North, ms(20)),
+            ]
+        );
+        assert!(outcome.is_complete());
+        assert!(!outcome.cancelled);
+    }
+
+    #[test]
+    fn cancel_stops_at_slice_boundary() {
+        let sent = std::cell::Cell::new(0);
+        let pulse = GuidePulse::new(GuideDirection::East, ms(500));
+        let outcome = deliver(
+            pulse,
+            ms(50),
+            || sent.get() == 2,
+            |_, _| {
+                sent.set(sent.get() + 1);
+                Ok(())
+            },
+        )
+        .unwrap();
+        assert_eq!(outcome.delivered, ms(100));
+        assert!(outcome.cancelled);
+        assert!(!outcome.is_complete());
+    }
+
+    #[test]
+    fn send_errors_end_the_pulse() {
+        let mut calls = 0;
+        let pulse = GuidePulse::new(GuideDirection::South, ms(200));
+        let result = deliver(
+            pulse,
+            ms(50),
+            || false,
+            |_, _| {
+                calls += 1;
+                if calls == 2 {
+                    Err(Error::Timeout)
+                } else {
+                    Ok(())
+                }
+            },
+        );
+        assert_eq!(result, Err(Error::Timeout));
+        assert_eq!(calls, 2);
+    }
+
+    fn config(slice_ms: u64) -> GuiderConfig {
+        GuiderConfig {
+            slice: ms(slice_ms),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn guider_drives_both_axes() {
+        let port = Arc::new(RecordingPort::new());
+        let guider = Guider::with_port(Arc::clone(&port), config(50)).unwrap();
+        let outcome = guider
+            .guide(
+                Some(GuidePulse::new(GuideDirection::West, ms(120))),
+                Some(GuidePulse::new(GuideDirection::South, ms(70))),
+            )
+            .unwrap()
+            .wait()
+            .unwrap();
+        assert!(outcome.ra.unwrap().is_complete());
+        assert!(outcome.dec.unwrap().is_complete());
+        assert_eq!(port.total(GuideDirection::West), ms(120));
+        assert_eq!(port.total(GuideDirection::South), ms(70));
+        assert_eq!(port.records().len(), 5);
+        assert!(!guider.is_pulsing());
+    }
+
+    #[test]
+    fn guider_rejects_bad_requests() {
+        let port = RecordingPort::new().with_capabilities(GuidePortCapabilities {
+            ra: true,
+            dec: false,
+            max_pulse: ms(100),
+            serialized: false,
+        });
+        assert!(Guider::with_port(RecordingPort::new(), config(0)).is_err());
+        let guider = Guider::with_port(port, config(50)).unwrap();
+        let east = GuidePulse::new(GuideDirection::East, ms(10));
+        let north = GuidePulse::new(GuideDirection::North, ms(10));
+        assert!(matches!(
+            guider.guide(None, Some(east)),
+            Err(Error::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            guider.guide(Some(east), Some(north)),
+            Err(Error::Unsupported(_))
+        ));
+        assert!(matches!(guider.pulse(north), Err(Error::Unsupported(_))));
+        assert!(guider.port().records().is_empty());
+    }
+
+    #[test]
+    fn in_flight_pulse_can_be_cancelled() {
+        let guider = Guider::with_port(RecordingPort::realtime(), config(5)).unwrap();
+        let handle = guider
+            .pulse(GuidePulse::new(
+                GuideDirection::East,
+                Duration::from_secs(5),
+            ))
+            .unwrap();
+        thread::sleep(ms(30));
+        handle.cancel();
+        let outcome = handle.wait().unwrap();
+        assert!(outcome.cancelled);
+        assert!(outcome.delivered < Duration::from_secs(1));
+        assert_eq!(guider.port().total(GuideDirection::East), outcome.delivered);
+    }
+
+    #[test]
+    fn dropping_guider_cancels_queued_pulses() {
+        let guider = Guider::with_port(RecordingPort::realtime(), config(5)).unwrap();
+        let first = guider
+            .pulse(GuidePulse::new(
+                GuideDirection::North,
+                Duration::from_secs(5),
+            ))
+            .unwrap();
+        let queued = guider
+            .pulse(GuidePulse::new(GuideDirection::South, ms(10)))
+            .unwrap();
+        drop(guider);
+        assert!(first.wait().unwrap().cancelled);
+        let queued = queued.wait().unwrap();
+        assert!(queued.cancelled);
+        assert_eq!(queued.delivered, Duration::ZERO);
+    }
+
+    #[test]
+    fn records_replay_onto_another_port() {
+        let recorded = RecordingPort::new();
+        recorded.pulse(GuideDirection::East, ms(40)).unwrap();
+        recorded.pulse(GuideDirection::North, ms(15)).unwrap();
+        recorded.pulse(GuideDirection::East, ms(5)).unwrap();
+        assert!(recorded.pulse(GuideDirection::Other(4), ms(5)).is_err());
+
+        let target = RecordingPort::new();
+        replay(&recorded.records(), &target, false).unwrap();
+        let sent: Vec<_> = target
+            .take_records()
+            .iter()
+            .map(|r| (r.direction, r.duration))
+            .collect();
+        assert_eq!(
+            sent,
+            [
+                (GuideDirection::East, ms(40)),
+                (GuideDirection::North, ms(15)),
+                (GuideDirection::East, ms(5)),
+            ]
+        );
+        assert!(target.records().is_empty());
+        assert_eq!(recorded.total(GuideDirection::East), ms(45));
+    }
+
+    #[test]
+    fn wrapping_forwards_and_records() {
+        let inner = Arc::new(RecordingPort::new());
+        let outer = RecordingPort::wrapping(Arc::clone(&inner));
+        outer.pulse(GuideDirection::West, ms(20)).unwrap();
+        assert_eq!(inner.total(GuideDirection::West), ms(20));
+        assert_eq!(outer.total(GuideDirection::West), ms(20));
+        assert_eq!(outer.capabilities(), inner.capabilities());
+    }
+}
@@ -0,0 +1,360 @@
+//! INDI server for SVBony cameras (requires the `indi` feature).
+//!
+//! [`IndiServer`] speaks the INDI protocol (XML over TCP, default port
+//! [`DEFAULT_PORT`]) so INDI clients such as KStars/Ekos can drive a camera
+//! directly. The camera is published as a single CCD device with the
+//! standard properties:
+//!
+//! - `CONNECTION`, `DRIVER_INFO`
+//! - `CCD_EXPOSURE`, `CCD_ABORT_EXPOSURE` and the `CCD1` image BLOB, sent as
+//!   FITS (see [`fits`](crate::fits))
+//! - `CCD_FRAME` and `CCD_BINNING` (in unbinned sensor pixels, as INDI
+//!   expects), `CCD_CAPTURE_FORMAT` with one switch per [`ImageType`],
+//!   `CCD_INFO` and, on colour sensors, `CCD_CFA`
+//! - `CCD_CONTROLS`, one number per writable control from
+//!   [`ControlCaps`](crate::ControlCaps)
+//! - `CCD_TEMPERATURE`, `CCD_COOLER` and `CCD_COOLER_POWER` when the camera
+//!   has a sensor / cooler
+//! - `TELESCOPE_TIMED_GUIDE_NS` / `_WE` on cameras with an ST4 port, driven
+//!   by a [`Guider`](crate::guide::Guider)
+//!
+//! Like other INDI drivers the device starts disconnected and defines its
+//! camera properties once a client switches `CONNECTION` to `CONNECT`.
+//! Clients receive images after sending `enableBLOB` with `Also` or `Only`.
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use svbony::indi::{IndiConfig, IndiServer, DEFAULT_PORT};
+//! use svbony::Camera;
+//!
+//! let cam = Arc::new(Camera::open(0)?);
+//! let server = IndiServer::bind(("0.0.0.0", DEFAULT_PORT), cam, IndiConfig::default())?;
+//! println!("serving {} on {}", server.device_name(), server.local_addr()?);
+//! server.run()?;
+//! # Ok::<(), svbony::indi::IndiError>(())
+//! ```
+//!
+//! [`ImageType`]: crate::ImageType
+
+mod driver;
+mod protocol;
+
+use std::io::{self, BufReader, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::server::{self, ServerThread};
+use crate::{Camera, Error};
+use driver::{Driver, Outgoing, Task};
+use protocol::{message_xml, BlobMode, ClientMessage, ElementReader, NewVector};
+
+/// The standard INDI server port.
+pub const DEFAULT_PORT: u16 = 7624;
+
+/// Server settings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndiConfig {
+    /// INDI device name; defaults to the camera's model name.
+    pub device_name: Option<String>,
+    /// Interval of exposure countdown, temperature and cooler updates.
+    pub poll_interval: Duration,
+    /// Writes to a client taking longer than this drop the client.
+    pub write_timeout: Duration,
+}
+
+impl Default for IndiConfig {
+    fn default() -> Self {
+        Self {
+            device_name: None,
+            poll_interval: Duration::from_secs(1),
+            write_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Errors from setting up or running an INDI server.
+#[derive(Debug, thiserror::Error)]
+pub enum IndiError {
+    /// Reading the camera's properties failed.
+    #[error(transparent)]
+    Camera(#[from] Error),
+    /// Binding the listening socket failed.
+    #[error("INDI server: {0}")]
+    Io(#[from] io::Error),
+}
+
+struct Client {
+    id: u64,
+    /// Shut down to disconnect the client when the server stops.
+    stream: TcpStream,
+    /// Messages for the client's writer thread.
+    queue: mpsc::Sender<Arc<str>>,
+    blobs: BlobMode,
+}
+
+/// State shared by the accept loop, client threads and background work.
+struct Shared {
+    driver: Mutex<Driver>,
+    clients: Mutex<Vec<Client>>,
+    shutdown: Arc<AtomicBool>,
+    next_id: AtomicU64,
+    config: IndiConfig,
+}
+
+impl Shared {
+    /// Sends `out` to every client, honouring their BLOB mode.
+    fn broadcast(&self, out: &[Outgoing]) {
+        self.send(None, out);
+    }
+
+    /// Queues `out` for one client or all of them. The writes happen on each
+    /// client's writer thread, so a slow client only delays itself; clients
+    /// whose writer has stopped are dropped.
+    fn send(&self, only: Option<u64>, out: &[Outgoing]) {
+        if out.is_empty() {
+            return;
+        }
+        // Shared between clients, so large BLOBs are not copied per client.
+        let out: Vec<_> = out
+            .iter()
+            .map(|msg| match msg {
+                Outgoing::Xml(xml) => (Arc::<str>::from(xml.as_str()), false),
+                Outgoing::Blob(xml) => (Arc::<str>::from(xml.as_str()), true),
+            })
+            .collect();
+        let mut clients = self.clients.lock().unwrap();
+        clients.retain(|client| {
+            if only.map_or(false, |id| id != client.id) {
+                return true;
+            }
+            out.iter()
+                .filter(|(_, blob)| {
+                    if *blob {
+                        client.blobs != BlobMode::Never
+                    } else {
+                        client.blobs != BlobMode::Only
+                    }
+                })
+                .all(|(xml, _)| client.queue.send(Arc::clone(xml)).is_ok())
+        });
+    }
+
+    fn handle(self: &Arc<Self>, client: u64, msg: ClientMessage) {
+        match msg {
+            ClientMessage::GetProperties { device, name } => {
+                let driver = self.driver.lock().unwrap();
+                if device.as_deref().map_or(true, |d| d == driver.device()) {
+                    let defs: Vec<_> = driver
+                        .visible()
+                        .filter(|p| name.as_deref().map_or(true, |n| n == p.name))
+                        .map(|p| Outgoing::Xml(p.def_xml()))
+                        .collect();
+                    drop(driver);
+                    self.send(Some(client), &defs);
+                }
+            }
+            ClientMessage::EnableBlob { device, mode, .. } => {
+                if device == self.driver.lock().unwrap().device() {
+                    let mut clients = self.clients.lock().unwrap();
+                    if let Some(c) = clients.iter_mut().find(|c| c.id == client) {
+                        c.blobs = mode;
+                    }
+                }
+            }
+            ClientMessage::NewNumber(NewVector { ref device, .. })
+            | ClientMessage::NewSwitch(NewVector { ref device, .. })
+            | ClientMessage::NewText(NewVector { ref device, .. }) => {
+                let (reply, camera) = {
+                    let mut driver = self.driver.lock().unwrap();
+                    if device != driver.device() {
+                        return;
+                    }
+                    (driver.handle(&msg), Arc::clone(driver.camera()))
+                };
+                self.broadcast(&reply.out);
+                for task in reply.tasks {
+                    self.run_task(task, Arc::clone(&camera));
+                }
+            }
+        }
+    }
+
+    fn run_task(self: &Arc<Self>, task: Task, camera: Arc<Camera>) {
+        let shared = Arc::clone(self);
+        thread::spawn(move || {
+            let out = match task {
+                Task::Expose { duration, cancel } => {
+                    let result = camera.expose(duration, &cancel);
+                    shared.driver.lock().unwrap().finish_exposure(result)
+                }
+                Task::Guide { property, handle } => {
+                    let result = handle.wait();
+                    shared.driver.lock().unwrap().finish_guide(property, result)
+                }
+            };
+            shared.broadcast(&out);
+        });
+    }
+
+    /// Reads and handles messages from one client until it disconnects.
+    fn serve(self: &Arc<Self>, id: u64, stream: TcpStream) {
+        let mut reader = ElementReader::new(BufReader::new(stream));
+        // Ends on disconnect, malformed XML or server shutdown.
+        while let Ok(Some(element)) = reader.next_element() {
+            match ClientMessage::from_element(&element) {
+                Ok(Some(msg)) => self.handle(id, msg),
+                Ok(None) => {}
+                Err(e) => {
+                    let device = self.driver.lock().unwrap().device().to_owned();
+                    self.send(
+                        Some(id),
+                        &[Outgoing::Xml(message_xml(&device, &e.to_string()))],
+                    );
+                }
+            }
+        }
+        self.clients.lock().unwrap().retain(|c| c.id != id);
+    }
+
+    fn poll(&self) {
+        let mut next = Instant::now();
+        while !self.shutdown.load(Ordering::Relaxed) {
+            next += self.config.poll_interval;
+            // Sleep in short steps so shutdown is noticed promptly.
+            while !self.shutdown.load(Ordering::Relaxed) {
+                let left = next.saturating_duration_since(Instant::now());
+                if left.is_zero() {
+                    break;
+                }
+                thread::sleep(left.min(Duration::from_millis(100)));
+            }
+            let out = self.driver.lock().unwrap().poll();
+            self.broadcast(&out);
+        }
+    }
+}
+
+/// Writes queued messages to a client until the queue is dropped or a
+/// write fails.
+fn write_queue(mut stream: TcpStream, queued: mpsc::Receiver<Arc<str>>) {
+    for xml in queued {
+        if stream.write_all(xml.as_bytes()).is_err() {
+            // Also ends the client's reader, which removes the client.
+            let _ = stream.shutdown(Shutdown::Both);
+            return;
+        }
+    }
+}
+
+/// An INDI server for one camera; see the [module docs](self).
+pub struct IndiServer {
+    listener: TcpListener,
+    shared: Arc<Shared>,
+}
+
+impl IndiServer {
+    /// Binds to `addr` and prepares the camera's properties.
+    ///
+    /// Port 0 picks a free port; see [`local_addr`](Self::local_addr).
+    pub fn bind(
+        addr: impl ToSocketAddrs,
+        camera: Arc<Camera>,
+        config: IndiConfig,
+    ) -> Result<Self, IndiError> {
+        let device = match &config.device_name {
+            Some(name) => name.clone(),
+            None => camera.info()?.name,
+        };
+        let driver = Driver::new(camera, device)?;
+        let listener = TcpListener::bind(addr)?;
+        Ok(Self {
+            listener,
+            shared: Arc::new(Shared {
+                driver: Mutex::new(driver),
+                clients: Mutex::new(Vec::new()),
+                shutdown: Arc::new(AtomicBool::new(false)),
+                next_id: AtomicU64::new(0),
+                config,
+            }),
+        })
+    }
+
+    /// Address the server listens on.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// The INDI device name clients see.
+    pub fn device_name(&self) -> String {
+        self.shared.driver.lock().unwrap().device().to_owned()
+    }
+
+    /// Serves clients on the current thread until the server is stopped
+    /// through an [`IndiServerHandle`], or forever.
+    pub fn run(self) -> io::Result<()> {
+        let shared = Arc::clone(&self.shared);
+        let poller = thread::spawn(move || shared.poll());
+        self.accept_loop();
+
+        self.shared.shutdown.store(true, Ordering::Relaxed);
+        for client in self.shared.clients.lock().unwrap().drain(..) {
+            let _ = client.stream.shutdown(Shutdown::Both);
+        }
+        let _ = poller.join();
+        Ok(())
+    }
+
+    fn accept_loop(&self) {
+        let shared = &self.shared;
+        server::accept_loop(
+            &self.listener,
+            &shared.shutdown,
+            shared.config.write_timeout,
+            |stream| {
+                let id = shared.next_id.fetch_add(1, Ordering::Relaxed);
+                let (queue, queued) = mpsc::channel();
+                let writer = stream.try_clone()?;
+                shared.clients.lock().unwrap().push(Client {
+                    id,
+                    stream: stream.try_clone()?,
+                    queue,
+                    blobs: BlobMode::Never,
+                });
+                thread::spawn(move || write_queue(writer, queued));
+                let shared = Arc::clone(shared);
+                thread::spawn(move || shared.serve(id, stream));
+                Ok(())
+            },
+        )
+    }
+
+    /// Runs the server on a background thread.
+    pub fn spawn(self) -> io::Result<IndiServerHandle> {
+        let addr = self.local_addr()?;
+        let shutdown = Arc::clone(&self.shared.shutdown);
+        Ok(IndiServerHandle {
+            server: ServerThread::spawn("INDI", addr, shutdown, move || self.run()),
+        })
+    }
+}
+
+/// A server running on a background thread. Stops the server when dropped.
+pub struct IndiServerHandle {
+    server: ServerThread,
+}
+
+impl IndiServerHandle {
+    /// Address the server listens on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.server.local_addr()
+    }
+
+    /// Disconnects all clients, stops the server and waits for it to finish.
+    /// Fails only if the server thread panicked.
+    pub fn stop(mut self) -> io::Result<()> {
+        self.server.stop()
+    }
+}
@@ -0,0 +1,979 @@
+//! Mapping of a camera onto standard INDI CCD properties.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use super::protocol::{
+    del_property_xml, message_xml, set_blob_xml, Blob, BlobElement, ClientMessage, Elements,
+    NewVector, NumberElement, Permission, Property, PropertyState, SwitchElement, SwitchRule,
+    TextElement,
+};
+use crate::fits::{self, Card};
+use crate::guide::{GuidePulse, Guider, GuiderConfig, PulseHandle, PulseOutcome};
+use crate::{
+    Camera, Capabilities, Celsius, ControlType, Error, Frame, GuideDirection, ImageType, Result,
+    RoiFormat,
+};
+
+const MAIN: &str = "Main Control";
+const IMAGE_SETTINGS: &str = "Image Settings";
+const IMAGE_INFO: &str = "Image Info";
+const CONTROLS: &str = "Controls";
+const GUIDER: &str = "Guider Control";
+const GENERAL: &str = "General Info";
+
+/// INDI `DRIVER_INTERFACE` bits.
+const CCD_INTERFACE: u32 = 1 << 1;
+const GUIDER_INTERFACE: u32 = 1 << 2;
+
+/// Longest timed guide pulse accepted, in milliseconds.
+const MAX_GUIDE_MS: f64 = 60_000.0;
+
+/// A set temperature counts as reached within this many degrees.
+const TEMPERATURE_TOLERANCE: f64 = 0.5;
+
+/// Controls with dedicated properties, left out of `CCD_CONTROLS`.
+const DEDICATED: &[ControlType] = &[
+    ControlType::Exposure,
+    ControlType::TargetTemperature,
+    ControlType::CoolerEnable,
+];
+
+/// A message for connected clients.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) enum Outgoing {
+    Xml(String),
+    /// A `setBLOBVector`, only sent to clients that enabled BLOBs.
+    Blob(String),
+}
+
+/// Work started by a client request that finishes later.
+pub(super) enum Task {
+    Expose {
+        duration: Duration,
+        cancel: Arc<AtomicBool>,
+    },
+    Guide {
+        property: &'static str,
+        handle: PulseHandle,
+    },
+}
+
+#[derive(Default)]
+pub(super) struct Reply {
+    pub out: Vec<Outgoing>,
+    pub tasks: Vec<Task>,
+}
+
+struct Exposure {
+    cancel: Arc<AtomicBool>,
+    duration: Duration,
+    started: Instant,
+    started_at: SystemTime,
+}
+
+/// Property state of one camera.
+pub(super) struct Driver {
+    camera: Arc<Camera>,
+    device: String,
+    caps: Capabilities,
+    connected: bool,
+    props: Vec<Property>,
+    /// Controls in the order of the `CCD_CONTROLS` elements.
+    controls: Vec<ControlType>,
+    /// Formats in the order of the `CCD_CAPTURE_FORMAT` switches.
+    formats: Vec<ImageType>,
+    guider: Option<Guider>,
+    exposure: Option<Exposure>,
+    target: Option<Celsius>,
+}
+
+impl Driver {
+    pub fn new(camera: Arc<Camera>, device: String) -> Result<Self> {
+        let caps = camera.capabilities()?;
+        let mut driver = Self {
+            guider: None,
+            camera,
+            device,
+            caps,
+            connected: false,
+            props: Vec::new(),
+            controls: Vec::new(),
+            formats: Vec::new(),
+            exposure: None,
+            target: None,
+        };
+        driver.build_properties()?;
+        Ok(driver)
+    }
+
+    pub fn device(&self) -> &str {
+        &self.device
+    }
+
+    pub fn camera(&self) -> &Arc<Camera> {
+        &self.camera
+    }
+
+    /// Properties currently defined for clients.
+    pub fn visible(&self) -> impl Iterator<Item = &Property> {
+        let connected = self.connected;
+        self.props
+            .iter()
+            .filter(move |p| connected || is_base(&p.name))
+    }
+
+    fn build_properties(&mut self) -> Result<()> {
+        let dev = self.device.clone();
+        let prop = &self.caps.property;
+        let sw = |name: &str, label: &str, on| SwitchElement {
+            name: name.into(),
+            label: label.into(),
+            on,
+        };
+        let text = |name: &str, label: &str, value: String| TextElement {
+            name: name.into(),
+            label: label.into(),
+            value,
+        };
+
+        let mut props = vec![
+            Property::new(
+                &dev,
+                "CONNECTION",
+                "Connection",
+                MAIN,
+                Permission::ReadWrite,
+                Elements::Switch(
+                    SwitchRule::OneOfMany,
+                    vec![
+                        sw("CONNECT", "Connect", false),
+                        sw("DISCONNECT", "Disconnect", true),
+                    ],
+                ),
+            ),
+            Property::new(
+                &dev,
+                "DRIVER_INFO",
+                "Driver Info",
+                GENERAL,
+                Permission::ReadOnly,
+                Elements::Text(vec![
+                    text("DRIVER_NAME", "Name", "SVBony CCD".into()),
+                    text("DRIVER_EXEC", "Exec", env!("CARGO_PKG_NAME").into()),
+                    text(
+                        "DRIVER_VERSION",
+                        "Version",
+                        env!("CARGO_PKG_VERSION").into(),
+                    ),
+                    text(
+                        "DRIVER_INTERFACE",
+                        "Interface",
+                        (CCD_INTERFACE
+                            | if self.caps.has_st4() {
+                                GUIDER_INTERFACE
+                            } else {
+                                0
+                            })
+                        .to_string(),
+                    ),
+                ]),
+            ),
+        ];
+
+        let (min_s, max_s) = self
+            .caps
+            .control(ControlType::Exposure)
+            .map_or((1e-6, 3600.0), |c| {
+                (c.min_value as f64 / 1e6, c.max_value as f64 / 1e6)
+            });
+        props.push(Property::new(
+            &dev,
+            "CCD_EXPOSURE",
+            "Expose",
+            MAIN,
+            Permission::ReadWrite,
+            Elements::Number(vec![number(
+                "CCD_EXPOSURE_VALUE",
+                "Duration (s)",
+                "%5.3f",
+                min_s,
+                max_s,
+                1.0,
+                1.0,
+            )]),
+        ));
+        props.push(Property::new(
+            &dev,
+            "CCD_ABORT_EXPOSURE",
+            "Abort",
+            MAIN,
+            Permission::ReadWrite,
+            Elements::Switch(SwitchRule::AtMostOne, vec![sw("ABORT", "Abort", false)]),
+        ));
+
+        let (w, h) = (prop.max_width as f64, prop.max_height as f64);
+        props.push(Property::new(
+            &dev,
+            "CCD_FRAME",
+            "Frame",
+            IMAGE_SETTINGS,
+            Permission::ReadWrite,
+            Elements::Number(vec![
+                number("X", "Left", "%4.0f", 0.0, w - 1.0, 1.0, 0.0),
+                number("Y", "Top", "%4.0f", 0.0, h - 1.0, 1.0, 0.0),
+                number("WIDTH", "Width", "%4.0f", 1.0, w, 1.0, w),
+                number("HEIGHT", "Height", "%4.0f", 1.0, h, 1.0, h),
+            ]),
+        ));
+        let max_bin = self.caps.max_bin() as f64;
+        props.push(Property::new(
+            &dev,
+            "CCD_BINNING",
+            "Binning",
+            IMAGE_SETTINGS,
+            Permission::ReadWrite,
+            Elements::Number(vec![
+                number("HOR_BIN", "X", "%2.0f", 1.0, max_bin, 1.0, 1.0),
+                number("VER_BIN", "Y", "%2.0f", 1.0, max_bin, 1.0, 1.0),
+            ]),
+        ));
+
+        self.formats = prop
+            .supported_formats
+            .iter()
+            .copied()
+            .filter(|f| !matches!(f, ImageType::Other(_)))
+            .collect();
+        let current = self.camera.output_image_type()?;
+        props.push(Property::new(
+            &dev,
+            "CCD_CAPTURE_FORMAT",
+            "Format",
+            IMAGE_SETTINGS,
+            Permission::ReadWrite,
+            Elements::Switch(
+                SwitchRule::OneOfMany,
+                self.formats
+                    .iter()
+                    .map(|&f| sw(&format_name(f), &format_name(f), f == current))
+                    .collect(),
+            ),
+        ));
+
+        let pixel = f64::from(self.caps.pixel_size_um.unwrap_or(0.0));
+        let info = |name: &str, label: &str, value: f64| {
+            number(name, label, "%4.2f", value, value, 0.0, value)
+        };
+        props.push(Property::new(
+            &dev,
+            "CCD_INFO",
+            "CCD Information",
+            IMAGE_INFO,
+            Permission::ReadOnly,
+            Elements::Number(vec![
+                info("CCD_MAX_X", "Max. Width", w),
+                info("CCD_MAX_Y", "Max. Height", h),
+                info("CCD_PIXEL_SIZE", "Pixel size (um)", pixel),
+                info("CCD_PIXEL_SIZE_X", "Pixel size X", pixel),
+                info("CCD_PIXEL_SIZE_Y", "Pixel size Y", pixel),
+                info(
+                    "CCD_BITSPERPIXEL",
+                    "Bits per pixel",
+                    f64::from(prop.max_bit_depth),
+                ),
+            ]),
+        ));
+        if let Some(pattern) = prop.bayer_pattern.and_then(|p| p.name()) {
+            props.push(Property::new(
+                &dev,
+                "CCD_CFA",
+                "Bayer Info",
+                IMAGE_INFO,
+                Permission::ReadOnly,
+                Elements::Text(vec![
+                    text("CFA_OFFSET_X", "X Offset", "0".into()),
+                    text("CFA_OFFSET_Y", "Y Offset", "0".into()),
+                    text("CFA_TYPE", "Filter", pattern.into()),
+                ]),
+            ));
+        }
+        props.push(Property::new(
+            &dev,
+            "CCD1",
+            "Image Data",
+            IMAGE_INFO,
+            Permission::ReadOnly,
+            Elements::Blob(vec![BlobElement {
+                name: "CCD1".into(),
+                label: "Image".into(),
+            }]),
+        ));
+
+        let mut controls = Vec::new();
+        let mut elements = Vec::new();
+        for c in self
+            .caps
+            .controls
+            .iter()
+            .filter(|c| c.is_writable && !DEDICATED.contains(&c.control_type))
+        {
+            let value = self
+                .camera
+                .get_control(c.control_type)
+                .map_or(c.default_value, |(v, _)| v);
+            controls.push(c.control_type);
+            elements.push(number(
+                &c.name,
+                &c.name,
+                "%.0f",
+                c.min_value as f64,
+                c.max_value as f64,
+                1.0,
+                value as f64,
+            ));
+        }
+        self.controls = controls;
+        if !elements.is_empty() {
+            props.push(Property::new(
+                &dev,
+                "CCD_CONTROLS",
+                "Controls",
+                CONTROLS,
+                Permission::ReadWrite,
+                Elements::Number(elements),
+            ));
+        }
+
+        if self.caps.has_temperature_sensor() {
+            let temp = self.camera.sensor_temperature().map_or(0.0, |t| t.0);
+            let (min, max) =
+                self.caps
+                    .control(ControlType::TargetTemperature)
+                    .map_or((-50.0, 50.0), |c| {
+                        (
+                            Celsius::from_tenths(c.min_value).0,
+                            Celsius::from_tenths(c.max_value).0,
+                        )
+                    });
+            let mut temperature = Property::new(
+                &dev,
+                "CCD_TEMPERATURE",
+                "Temperature",
+                MAIN,
+                if self.caps.has_cooler() {
+                    Permission::ReadWrite
+                } else {
+                    Permission::ReadOnly
+                },
+                Elements::Number(vec![number(
+                    "CCD_TEMPERATURE_VALUE",
+                    "Temperature (C)",
+                    "%5.2f",
+                    min,
+                    max,
+                    0.5,
+                    temp,
+                )]),
+            );
+            temperature.state = PropertyState::Ok;
+            props.push(temperature);
+        }
+        if self.caps.has_cooler() {
+            let on = self.camera.cooler_enabled().unwrap_or(false);
+            props.push(Property::new(
+                &dev,
+                "CCD_COOLER",
+                "Cooler",
+                MAIN,
+                Permission::ReadWrite,
+                Elements::Switch(
+                    SwitchRule::OneOfMany,
+                    vec![sw("COOLER_ON", "On", on), sw("COOLER_OFF", "Off", !on)],
+                ),
+            ));
+            props.push(Property::new(
+                &dev,
+                "CCD_COOLER_POWER",
+                "Cooling Power",
+                MAIN,
+                Permission::ReadOnly,
+                Elements::Number(vec![number(
+                    "CCD_COOLER_VALUE",
+                    "Power (%)",
+                    "%3.0f",
+                    0.0,
+                    100.0,
+                    1.0,
+                    0.0,
+                )]),
+            ));
+        }
+
+        if self.caps.has_st4() {
+            let guide = |name: &str, label: &str| {
+                number(name, label, "%.0f", 0.0, MAX_GUIDE_MS, 100.0, 0.0)
+            };
+            props.push(Property::new(
+                &dev,
+                "TELESCOPE_TIMED_GUIDE_NS",
+                "Guide N/S",
+                GUIDER,
+                Permission::ReadWrite,
+                Elements::Number(vec![
+                    guide("TIMED_GUIDE_N", "North (ms)"),
+                    guide("TIMED_GUIDE_S", "South (ms)"),
+                ]),
+            ));
+            props.push(Property::new(
+                &dev,
+                "TELESCOPE_TIMED_GUIDE_WE",
+                "Guide E/W",
+                GUIDER,
+                Permission::ReadWrite,
+                Elements::Number(vec![
+                    guide("TIMED_GUIDE_W", "West (ms)"),
+                    guide("TIMED_GUIDE_E", "East (ms)"),
+                ]),
+            ));
+            self.guider = Some(Guider::new(
+                Arc::clone(&self.camera),
+                GuiderConfig::default(),
+            )?);
+        }
+
+        self.props = props;
+        self.sync_roi()?;
+        Ok(())
+    }
+
+    fn prop_mut(&mut self, name: &str) -> Option<&mut Property> {
+        self.props.iter_mut().find(|p| p.name == name)
+    }
+
+    /// Updates a property and returns its `set*Vector`.
+    fn update(
+        &mut self,
+        name: &str,
+        state: PropertyState,
+        message: Option<&str>,
+        f: impl FnOnce(&mut Property),
+    ) -> Outgoing {
+        let prop = self
+            .prop_mut(name)
+            .expect("driver updated a property it never defined");
+        f(prop);
+        prop.state = state;
+        Outgoing::Xml(prop.set_xml(message))
+    }
+
+    fn alert(&mut self, name: &str, message: &str) -> Outgoing {
+        self.update(name, PropertyState::Alert, Some(message), |_| {})
+    }
+
+    /// Handles a client request for one of this device's properties.
+    pub fn handle(&mut self, msg: &ClientMessage) -> Reply {
+        let mut reply = Reply::default();
+        let name = match msg {
+            ClientMessage::NewNumber(v) => &v.name,
+            ClientMessage::NewSwitch(v) => &v.name,
+            ClientMessage::NewText(v) => &v.name,
+            _ => return reply,
+        };
+        let Some(prop) = self.visible().find(|p| &p.name == name) else {
+            return reply;
+        };
+        if prop.perm == Permission::ReadOnly {
+            reply.out.push(Outgoing::Xml(message_xml(
+                &self.device,
+                &format!("{name} is read-only"),
+            )));
+            return reply;
+        }
+        match msg {
+            ClientMessage::NewSwitch(v) => self.new_switch(v, &mut reply),
+            ClientMessage::NewNumber(v) => self.new_number(v, &mut reply),
+            _ => {}
+        }
+        reply
+    }
+
+    fn new_switch(&mut self, v: &NewVector<bool>, reply: &mut Reply) {
+        let on: Vec<&str> = v
+            .values
+            .iter()
+            .filter(|(_, on)| *on)
+            .map(|(n, _)| n.as_str())
+            .collect();
+        match (v.name.as_str(), on.first().copied()) {
+            ("CONNECTION", Some("CONNECT")) => self.connect(reply),
+            ("CONNECTION", Some("DISCONNECT")) => self.disconnect(reply),
+            ("CCD_ABORT_EXPOSURE", Some("ABORT")) => {
+                if let Some(exposure) = &self.exposure {
+                    exposure.cancel.store(true, Ordering::Relaxed);
+                }
+                reply.out.push(
+                    self.update("CCD_ABORT_EXPOSURE", PropertyState::Ok, None, |p| {
+                        p.select_switch("ABORT");
+                    }),
+                );
+            }
+            ("CCD_CAPTURE_FORMAT", Some(name)) => {
+                let Some(&format) = self.formats.iter().find(|&&f| format_name(f) == name) else {
+                    return;
+                };
+                let out = match self
+                    .busy_check()
+                    .and_then(|_| self.camera.set_output_image_type(format))
+                {
+                    Ok(()) => self.update("CCD_CAPTURE_FORMAT", PropertyState::Ok, None, |p| {
+                        p.select_switch(name);
+                    }),
+                    Err(e) => self.alert("CCD_CAPTURE_FORMAT", &e.to_string()),
+                };
+                reply.out.push(out);
+            }
+            ("CCD_COOLER", Some(name)) => {
+                let on = name == "COOLER_ON";
+                let out = match self.camera.set_cooler_enabled(on) {
+                    Ok(()) => {
+                        if !on {
+                            self.target = None;
+                        }
+                        self.update("CCD_COOLER", PropertyState::Ok, None, |p| {
+                            p.select_switch(name);
+                        })
+                    }
+                    Err(e) => self.alert("CCD_COOLER", &e.to_string()),
+                };
+                reply.out.push(out);
+            }
+            _ => {}
+        }
+    }
+
+    fn new_number(&mut self, v: &NewVector<f64>, reply: &mut Reply) {
+        match v.name.as_str() {
+            "CCD_EXPOSURE" => {
+                if let Some(seconds) = v.get("CCD_EXPOSURE_VALUE") {
+                    self.start_exposure(seconds, reply);
+                }
+            }
+            "CCD_FRAME" | "CCD_BINNING" => {
+                let out = match self.set_roi(v) {
+                    Ok(()) => {
+                        let frame = self.update("CCD_FRAME", PropertyState::Ok, None, |_| {});
+                        reply.out.push(frame);
+                        self.update("CCD_BINNING", PropertyState::Ok, None, |_| {})
+                    }
+                    Err(e) => {
+                        let msg = e.to_string();
+                        self.alert(&v.name, &msg)
+                    }
+                };
+                reply.out.push(out);
+            }
+            "CCD_CONTROLS" => {
+                let mut errors = Vec::new();
+                let mut applied = Vec::new();
+                for (name, value) in &v.values {
+                    let Some(i) = self.control_index(name) else {
+                        continue;
+                    };
+                    match self.camera.set_control_clamped(
+                        self.controls[i],
+                        value.round() as i64,
+                        false,
+                    ) {
+                        Ok(set) => applied.push((name.clone(), set as f64)),
+                        Err(e) => errors.push(format!("{name}: {e}")),
+                    }
+                }
+                let (state, message) = if errors.is_empty() {
+                    (PropertyState::Ok, None)
+                } else {
+                    (PropertyState::Alert, Some(errors.join("; ")))
+                };
+                reply
+                    .out
+                    .push(self.update("CCD_CONTROLS", state, message.as_deref(), |p| {
+                        for (name, value) in applied {
+                            p.set_number(&name, value);
+                        }
+                    }));
+            }
+            "CCD_TEMPERATURE" => {
+                if let Some(t) = v.get("CCD_TEMPERATURE_VALUE") {
+                    let target = Celsius(t);
+                    let result = self
+                        .camera
+                        .set_target_temperature(target)
+                        .and_then(|_| self.camera.set_cooler_enabled(true));
+                    let out = match result {
+                        Ok(()) => {
+                            self.target = Some(target);
+                            reply.out.push(self.update(
+                                "CCD_COOLER",
+                                PropertyState::Ok,
+                                None,
+                                |p| {
+                                    p.select_switch("COOLER_ON");
+                                },
+                            ));
+                            self.update("CCD_TEMPERATURE", PropertyState::Busy, None, |_| {})
+                        }
+                        Err(e) => self.alert("CCD_TEMPERATURE", &e.to_string()),
+                    };
+                    reply.out.push(out);
+                }
+            }
+            "TELESCOPE_TIMED_GUIDE_NS" => self.guide(
+                "TELESCOPE_TIMED_GUIDE_NS",
+                v,
+                ("TIMED_GUIDE_N", GuideDirection::North),
+                ("TIMED_GUIDE_S", GuideDirection::South),
+                reply,
+            ),
+            "TELESCOPE_TIMED_GUIDE_WE" => self.guide(
+                "TELESCOPE_TIMED_GUIDE_WE",
+                v,
+                ("TIMED_GUIDE_W", GuideDirection::West),
+                ("TIMED_GUIDE_E", GuideDirection::East),
+                reply,
+            ),
+            _ => {}
+        }
+    }
+
+    fn control_index(&self, name: &str) -> Option<usize> {
+        self.controls
+            .iter()
+            .position(|&c| self.caps.control(c).map_or(false, |caps| caps.name == name))
+    }
+
+    fn busy_check(&self) -> Result<()> {
+        match self.exposure {
+            Some(_) => Err(Error::InvalidArgument("an exposure is in progress".into())),
+            None => Ok(()),
+        }
+    }
+
+    fn connect(&mut self, reply: &mut Reply) {
+        if !self.connected {
+            self.connected = true;
+            for p in self.props.iter().filter(|p| !is_base(&p.name)) {
+                reply.out.push(Outgoing::Xml(p.def_xml()));
+            }
+        }
+        reply
+            .out
+            .push(self.update("CONNECTION", PropertyState::Ok, None, |p| {
+                p.select_switch("CONNECT");
+            }));
+    }
+
+    fn disconnect(&mut self, reply: &mut Reply) {
+        if let Some(exposure) = &self.exposure {
+            exposure.cancel.store(true, Ordering::Relaxed);
+        }
+        if self.connected {
+            self.connected = false;
+            for p in self.props.iter().filter(|p| !is_base(&p.name)) {
+                reply
+                    .out
+                    .push(Outgoing::Xml(del_property_xml(&self.device, Some(&p.name))));
+            }
+        }
+        reply
+            .out
+            .push(self.update("CONNECTION", PropertyState::Idle, None, |p| {
+                p.select_switch("DISCONNECT");
+            }));
+    }
+
+    fn start_exposure(&mut self, seconds: f64, reply: &mut Reply) {
+        if self.exposure.is_some() {
+            reply
+                .out
+                .push(self.alert("CCD_EXPOSURE", "an exposure is already in progress"));
+            return;
+        }
+        let duration = match Duration::try_from_secs_f64(seconds) {
+            Ok(d) if !d.is_zero() => d,
+            _ => {
+                let msg = format!("invalid exposure time {seconds} s");
+                reply.out.push(self.alert("CCD_EXPOSURE", &msg));
+                return;
+            }
+        };
+        if let Err(e) = self.camera.set_exposure(duration) {
+            reply.out.push(self.alert("CCD_EXPOSURE", &e.to_string()));
+            return;
+        }
+        let cancel = Arc::new(AtomicBool::new(false));
+        self.exposure = Some(Exposure {
+            cancel: Arc::clone(&cancel),
+            duration,
+            started: Instant::now(),
+            started_at: SystemTime::now(),
+        });
+        reply
+            .out
+            .push(self.update("CCD_EXPOSURE", PropertyState::Busy, None, |p| {
+                p.set_number("CCD_EXPOSURE_VALUE", seconds);
+            }));
+        reply.tasks.push(Task::Expose { duration, cancel });
+    }
+
+    /// Reports the result of an [`Task::Expose`] and sends the image.
+    pub fn finish_exposure(&mut self, result: Result<Option<Frame>>) -> Vec<Outgoing> {
+        let Some(exposure) = self.exposure.take() else {
+            return Vec::new();
+        };
+        let frame = match result {
+            Ok(Some(frame)) => frame,
+            Ok(None) => {
+                return vec![self.update(
+                    "CCD_EXPOSURE",
+                    PropertyState::Idle,
+                    Some("exposure aborted"),
+                    |p| {
+                        p.set_number("CCD_EXPOSURE_VALUE", 0.0);
+                    },
+                )]
+            }
+            Err(e) => return vec![self.alert("CCD_EXPOSURE", &format!("exposure failed: {e}"))],
+        };
+        let fits = match fits::encode_frame(&frame, &self.fits_cards(&frame, &exposure)) {
+            Ok(fits) => fits,
+            Err(e) => {
+                return vec![self.alert("CCD_EXPOSURE", &format!("FITS encoding failed: {e}"))]
+            }
+        };
+        let done = self.update("CCD_EXPOSURE", PropertyState::Ok, None, |p| {
+            p.set_number("CCD_EXPOSURE_VALUE", 0.0);
+        });
+        let blob = set_blob_xml(
+            &self.device,
+            "CCD1",
+            PropertyState::Ok,
+            &[Blob {
+                name: "CCD1".into(),
+                format: ".fits".into(),
+                data: fits,
+            }],
+        );
+        // Clients expect the exposure to finish before the image arrives.
+        vec![done, Outgoing::Blob(blob)]
+    }
+
+    fn fits_cards(&self, frame: &Frame, exposure: &Exposure) -> Vec<Card> {
+        fits::Observation {
+            instrument: &self.device,
+            exposure: exposure.duration,
+            started: exposure.started_at,
+            pixel_size_um: self.caps.pixel_size_um,
+            gain: self
+                .camera
+                .get_control(ControlType::Gain)
+                .ok()
+                .map(|(g, _)| g),
+            temperature: self.camera.sensor_temperature().ok().map(|t| t.0),
+            bayer_pattern: self.caps.property.bayer_pattern,
+        }
+        .cards(frame)
+    }
+
+    fn guide(
+        &mut self,
+        property: &'static str,
+        v: &NewVector<f64>,
+        (pos_name, pos_dir): (&str, GuideDirection),
+        (neg_name, neg_dir): (&str, GuideDirection),
+        reply: &mut Reply,
+    ) {
+        let pos = v.get(pos_name).unwrap_or(0.0);
+        let neg = v.get(neg_name).unwrap_or(0.0);
+        let (dir, ms) = if pos >= neg {
+            (pos_dir, pos - neg)
+        } else {
+            (neg_dir, neg - pos)
+        };
+        let ms = ms.round();
+        if ms < 1.0 {
+            reply
+                .out
+                .push(self.update(property, PropertyState::Ok, None, |_| {}));
+            return;
+        }
+        let pulse = GuidePulse::new(dir, Duration::from_millis(ms.min(MAX_GUIDE_MS) as u64));
+        let started = match &self.guider {
+            Some(guider) => guider.pulse(pulse),
+            None => Err(Error::Unsupported("pulse guiding".into())),
+        };
+        match started {
+            Ok(handle) => {
+                reply
+                    .out
+                    .push(self.update(property, PropertyState::Busy, None, |p| {
+                        p.set_number(pos_name, if dir == pos_dir { ms } else { 0.0 });
+                        p.set_number(neg_name, if dir == neg_dir { ms } else { 0.0 });
+                    }));
+                reply.tasks.push(Task::Guide { property, handle });
+            }
+            Err(e) => reply.out.push(self.alert(property, &e.to_string())),
+        }
+    }
+
+    /// Reports the result of a [`Task::Guide`].
+    pub fn finish_guide(&mut self, property: &str, result: Result<PulseOutcome>) -> Vec<Outgoing> {
+        let (state, message) = match result {
+            Ok(outcome) if outcome.cancelled => (
+                PropertyState::Idle,
+                Some("guide pulse cancelled".to_owned()),
+            ),
+            Ok(_) => (PropertyState::Ok, None),
+            Err(e) => (
+                PropertyState::Alert,
+                Some(format!("guide pulse failed: {e}")),
+            ),
+        };
+        vec![self.update(property, state, message.as_deref(), |p| {
+            if let Elements::Number(e) = &mut p.elements {
+                e.iter_mut().for_each(|e| e.value = 0.0);
+            }
+        })]
+    }
+
+    fn set_roi(&mut self, v: &NewVector<f64>) -> Result<()> {
+        self.busy_check()?;
+        let current = |prop: &str, name: &str| {
+            self.props
+                .iter()
+                .find(|p| p.name == prop)
+                .and_then(|p| p.number(name))
+                .unwrap_or(0.0)
+        };
+        let get = |prop: &str, name: &str| {
+            if v.name == prop {
+                v.get(name).unwrap_or_else(|| current(prop, name))
+            } else {
+                current(prop, name)
+            }
+        };
+        let bin = get("CCD_BINNING", "HOR_BIN").round() as i32;
+        let b = f64::from(bin.max(1));
+        let roi = RoiFormat::builder(&self.caps.property)
+            .bin(bin)
+            .at(
+                (get("CCD_FRAME", "X") / b) as i32,
+                (get("CCD_FRAME", "Y") / b) as i32,
+                (get("CCD_FRAME", "WIDTH") / b) as i32,
+                (get("CCD_FRAME", "HEIGHT") / b) as i32,
+            )
+            .build()?;
+        self.camera.set_roi(&roi)?;
+        self.sync_roi()
+    }
+
+    /// Copies the camera's ROI into `CCD_FRAME` and `CCD_BINNING`.
+    fn sync_roi(&mut self) -> Result<()> {
+        let roi = self.camera.roi()?;
+        let (x, y, w, h) = roi.sensor_rect();
+        if let Some(p) = self.prop_mut("CCD_FRAME") {
+            p.set_number("X", f64::from(x));
+            p.set_number("Y", f64::from(y));
+            p.set_number("WIDTH", f64::from(w));
+            p.set_number("HEIGHT", f64::from(h));
+        }
+        if let Some(p) = self.prop_mut("CCD_BINNING") {
+            p.set_number("HOR_BIN", f64::from(roi.bin));
+            p.set_number("VER_BIN", f64::from(roi.bin));
+        }
+        Ok(())
+    }
+
+    /// Periodic updates: exposure countdown, temperature and cooler power.
+    pub fn poll(&mut self) -> Vec<Outgoing> {
+        let mut out = Vec::new();
+        if !self.connected {
+            return out;
+        }
+        if let Some(exposure) = &self.exposure {
+            let left = exposure
+                .duration
+                .saturating_sub(exposure.started.elapsed())
+                .as_secs_f64();
+            out.push(self.update("CCD_EXPOSURE", PropertyState::Busy, None, |p| {
+                p.set_number("CCD_EXPOSURE_VALUE", left);
+            }));
+        }
+        if self.caps.has_temperature_sensor() {
+            if let Ok(temp) = self.camera.sensor_temperature() {
+                let state = match self.target {
+                    Some(target) if (temp.0 - target.0).abs() > TEMPERATURE_TOLERANCE => {
+                        PropertyState::Busy
+                    }
+                    _ => PropertyState::Ok,
+                };
+                out.push(self.update("CCD_TEMPERATURE", state, None, |p| {
+                    p.set_number("CCD_TEMPERATURE_VALUE", temp.0);
+                }));
+            }
+        }
+        if self.caps.has_cooler() {
+            if let Ok(power) = self.camera.cooler_power() {
+                out.push(
+                    self.update("CCD_COOLER_POWER", PropertyState::Ok, None, |p| {
+                        p.set_number("CCD_COOLER_VALUE", f64::from(power.0));
+                    }),
+                );
+            }
+        }
+        out
+    }
+}
+
+/// Properties defined while disconnected.
+fn is_base(name: &str) -> bool {
+    matches!(name, "CONNECTION" | "DRIVER_INFO")
+}
+
+fn number(
+    name: &str,
+    label: &str,
+    format: &str,
+    min: f64,
+    max: f64,
+    step: f64,
+    value: f64,
+) -> NumberElement {
+    NumberElement {
+        name: name.into(),
+        label: label.into(),
+        format: format.into(),
+        min,
+        max,
+        step,
+        value,
+    }
+}
+
+/// Switch name of `format` in `CCD_CAPTURE_FORMAT`, e.g. `RAW16`.
+fn format_name(format: ImageType) -> String {
+    format!("{format:?}").to_uppercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names() {
+        assert_eq!(format_name(ImageType::Raw16), "RAW16");
+        assert_eq!(format_name(ImageType::Rgb24), "RGB24");
+        assert!(is_base("CONNECTION"));
+        assert!(!is_base("CCD_EXPOSURE"));
+    }
+}
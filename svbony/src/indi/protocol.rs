@@ -0,0 +1,887 @@
+//! INDI property model and XML wire format.
+
+use std::fmt::Write as _;
+use std::io::{self, BufRead};
+
+use base64::Engine as _;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+/// State light of a property vector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub(super) enum PropertyState {
+    #[default]
+    Idle,
+    Ok,
+    Busy,
+    Alert,
+}
+
+impl PropertyState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Idle => "Idle",
+            Self::Ok => "Ok",
+            Self::Busy => "Busy",
+            Self::Alert => "Alert",
+        }
+    }
+}
+
+/// Client access to a property vector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(super) enum Permission {
+    ReadOnly,
+    ReadWrite,
+}
+
+impl Permission {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::ReadOnly => "ro",
+            Self::ReadWrite => "rw",
+        }
+    }
+}
+
+/// How many switches of a vector may be on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(super) enum SwitchRule {
+    OneOfMany,
+    AtMostOne,
+    AnyOfMany,
+}
+
+impl SwitchRule {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::OneOfMany => "OneOfMany",
+            Self::AtMostOne => "AtMostOne",
+            Self::AnyOfMany => "AnyOfMany",
+        }
+    }
+}
+
+/// Which messages a client receives, set with `enableBLOB`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub(super) enum BlobMode {
+    /// No BLOBs, everything else (the initial mode).
+    #[default]
+    Never,
+    /// BLOBs and everything else.
+    Also,
+    /// BLOBs only.
+    Only,
+}
+
+impl BlobMode {
+    /// Parses the text of an `enableBLOB` element.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim() {
+            "Never" => Some(Self::Never),
+            "Also" => Some(Self::Also),
+            "Only" => Some(Self::Only),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct NumberElement {
+    pub name: String,
+    pub label: String,
+    /// printf-style format clients use for display.
+    pub format: String,
+    pub min: f64,
+    pub max: f64,
+    pub step: f64,
+    pub value: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct SwitchElement {
+    pub name: String,
+    pub label: String,
+    pub on: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct TextElement {
+    pub name: String,
+    pub label: String,
+    pub value: String,
+}
+
+/// A BLOB slot; its contents are only ever sent with [`set_blob_xml`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct BlobElement {
+    pub name: String,
+    pub label: String,
+}
+
+/// Members of a property vector.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Elements {
+    Number(Vec<NumberElement>),
+    Switch(SwitchRule, Vec<SwitchElement>),
+    Text(Vec<TextElement>),
+    Blob(Vec<BlobElement>),
+}
+
+impl Elements {
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::Number(_) => "Number",
+            Self::Switch(..) => "Switch",
+            Self::Text(_) => "Text",
+            Self::Blob(_) => "BLOB",
+        }
+    }
+}
+
+/// A device property vector.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Property {
+    pub device: String,
+    pub name: String,
+    pub label: String,
+    pub group: String,
+    pub state: PropertyState,
+    pub perm: Permission,
+    /// Seconds a client should expect a change to take.
+    pub timeout: f64,
+    pub elements: Elements,
+}
+
+impl Property {
+    /// Creates an idle read-write property with no timeout.
+    pub fn new(
+        device: &str,
+        name: &str,
+        label: &str,
+        group: &str,
+        perm: Permission,
+        elements: Elements,
+    ) -> Self {
+        Self {
+            device: device.into(),
+            name: name.into(),
+            label: label.into(),
+            group: group.into(),
+            state: PropertyState::Idle,
+            perm,
+            timeout: 0.0,
+            elements,
+        }
+    }
+
+    /// Returns the value of number element `name`.
+    pub fn number(&self, name: &str) -> Option<f64> {
+        match &self.elements {
+            Elements::Number(e) => e.iter().find(|e| e.name == name).map(|e| e.value),
+            _ => None,
+        }
+    }
+
+    /// Sets number element `name`; returns `false` if there is none.
+    pub fn set_number(&mut self, name: &str, value: f64) -> bool {
+        match &mut self.elements {
+            Elements::Number(e) => match e.iter_mut().find(|e| e.name == name) {
+                Some(e) => {
+                    e.value = value;
+                    true
+                }
+                None => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// Returns the state of switch element `name`.
+    #[cfg(test)]
+    pub fn switch(&self, name: &str) -> Option<bool> {
+        match &self.elements {
+            Elements::Switch(_, e) => e.iter().find(|e| e.name == name).map(|e| e.on),
+            _ => None,
+        }
+    }
+
+    /// Turns on switch `name` and, for one-of-many and at-most-one vectors,
+    /// turns the others off. Returns `false` if there is no such switch.
+    pub fn select_switch(&mut self, name: &str) -> bool {
+        match &mut self.elements {
+            Elements::Switch(rule, e) => {
+                if !e.iter().any(|e| e.name == name) {
+                    return false;
+                }
+                let exclusive = *rule != SwitchRule::AnyOfMany;
+                for e in e.iter_mut() {
+                    if e.name == name {
+                        e.on = true;
+                    } else if exclusive {
+                        e.on = false;
+                    }
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the name of the first switch that is on.
+    #[cfg(test)]
+    pub fn selected_switch(&self) -> Option<&str> {
+        match &self.elements {
+            Elements::Switch(_, e) => e.iter().find(|e| e.on).map(|e| e.name.as_str()),
+            _ => None,
+        }
+    }
+
+    /// Serializes the `def*Vector` message that announces the property.
+    pub fn def_xml(&self) -> String {
+        let kind = self.elements.kind();
+        let mut out = format!(
+            "<def{kind}Vector device=\"{}\" name=\"{}\" label=\"{}\" group=\"{}\" state=\"{}\" \
+             perm=\"{}\"",
+            escape(&self.device),
+            escape(&self.name),
+            escape(&self.label),
+            escape(&self.group),
+            self.state.as_str(),
+            self.perm.as_str(),
+        );
+        if let Elements::Switch(rule, _) = &self.elements {
+            let _ = write!(out, " rule=\"{}\"", rule.as_str());
+        }
+        let _ = writeln!(out, " timeout=\"{}\">", self.timeout);
+        match &self.elements {
+            Elements::Number(e) => {
+                for e in e {
+                    let _ = writeln!(
+                        out,
+                        "  <defNumber name=\"{}\" label=\"{}\" format=\"{}\" min=\"{}\" \
+                         max=\"{}\" step=\"{}\">{}</defNumber>",
+                        escape(&e.name),
+                        escape(&e.label),
+                        escape(&e.format),
+                        e.min,
+                        e.max,
+                        e.step,
+                        e.value
+                    );
+                }
+            }
+            Elements::Switch(_, e) => {
+                for e in e {
+                    let _ = writeln!(
+                        out,
+                        "  <defSwitch name=\"{}\" label=\"{}\">{}</defSwitch>",
+                        escape(&e.name),
+                        escape(&e.label),
+                        on_off(e.on)
+                    );
+                }
+            }
+            Elements::Text(e) => {
+                for e in e {
+                    let _ = writeln!(
+                        out,
+                        "  <defText name=\"{}\" label=\"{}\">{}</defText>",
+                        escape(&e.name),
+                        escape(&e.label),
+                        escape(&e.value)
+                    );
+                }
+            }
+            Elements::Blob(e) => {
+                for e in e {
+                    let _ = writeln!(
+                        out,
+                        "  <defBLOB name=\"{}\" label=\"{}\"/>",
+                        escape(&e.name),
+                        escape(&e.label)
+                    );
+                }
+            }
+        }
+        let _ = writeln!(out, "</def{kind}Vector>");
+        out
+    }
+
+    /// Serializes a `set*Vector` message with the current state and values,
+    /// optionally carrying a log `message`.
+    ///
+    /// Not meaningful for BLOB vectors; use [`set_blob_xml`].
+    pub fn set_xml(&self, message: Option<&str>) -> String {
+        let kind = self.elements.kind();
+        let mut out = format!(
+            "<set{kind}Vector device=\"{}\" name=\"{}\" state=\"{}\" timeout=\"{}\"",
+            escape(&self.device),
+            escape(&self.name),
+            self.state.as_str(),
+            self.timeout,
+        );
+        if let Some(message) = message {
+            let _ = write!(out, " message=\"{}\"", escape(message));
+        }
+        out.push_str(">\n");
+        match &self.elements {
+            Elements::Number(e) => {
+                for e in e {
+                    let _ = writeln!(
+                        out,
+                        "  <oneNumber name=\"{}\">{}</oneNumber>",
+                        escape(&e.name),
+                        e.value
+                    );
+                }
+            }
+            Elements::Switch(_, e) => {
+                for e in e {
+                    let _ = writeln!(
+                        out,
+                        "  <oneSwitch name=\"{}\">{}</oneSwitch>",
+                        escape(&e.name),
+                        on_off(e.on)
+                    );
+                }
+            }
+            Elements::Text(e) => {
+                for e in e {
+                    let _ = writeln!(
+                        out,
+                        "  <oneText name=\"{}\">{}</oneText>",
+                        escape(&e.name),
+                        escape(&e.value)
+                    );
+                }
+            }
+            Elements::Blob(_) => {}
+        }
+        let _ = writeln!(out, "</set{kind}Vector>");
+        out
+    }
+}
+
+/// Contents of one BLOB element.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Blob {
+    pub name: String,
+    /// File suffix describing the data, e.g. `.fits`.
+    pub format: String,
+    pub data: Vec<u8>,
+}
+
+/// Serializes a `setBLOBVector` message; BLOB data is base64 encoded.
+pub(super) fn set_blob_xml(
+    device: &str,
+    name: &str,
+    state: PropertyState,
+    blobs: &[Blob],
+) -> String {
+    let mut out = format!(
+        "<setBLOBVector device=\"{}\" name=\"{}\" state=\"{}\">\n",
+        escape(device),
+        escape(name),
+        state.as_str()
+    );
+    for blob in blobs {
+        let _ = writeln!(
+            out,
+            "  <oneBLOB name=\"{}\" size=\"{}\" format=\"{}\">{}</oneBLOB>",
+            escape(&blob.name),
+            blob.data.len(),
+            escape(&blob.format),
+            base64::engine::general_purpose::STANDARD.encode(&blob.data)
+        );
+    }
+    out.push_str("</setBLOBVector>\n");
+    out
+}
+
+/// Serializes a `message` for the client log.
+pub(super) fn message_xml(device: &str, message: &str) -> String {
+    format!(
+        "<message device=\"{}\" message=\"{}\"/>\n",
+        escape(device),
+        escape(message)
+    )
+}
+
+/// Serializes a `delProperty` for one property, or the whole device if
+/// `name` is `None`.
+pub(super) fn del_property_xml(device: &str, name: Option<&str>) -> String {
+    match name {
+        Some(name) => format!(
+            "<delProperty device=\"{}\" name=\"{}\"/>\n",
+            escape(device),
+            escape(name)
+        ),
+        None => format!("<delProperty device=\"{}\"/>\n", escape(device)),
+    }
+}
+
+fn on_off(on: bool) -> &'static str {
+    if on {
+        "On"
+    } else {
+        "Off"
+    }
+}
+
+/// Escapes text for XML content and attribute values.
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// A parsed XML element with its attributes, text and children.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub(super) struct Element {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    /// Concatenated text content, trimmed.
+    pub text: String,
+    pub children: Vec<Element>,
+}
+
+impl Element {
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns the first child called `name`.
+    #[cfg(test)]
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|c| c.name == name)
+    }
+}
+
+/// Reads top-level elements from an INDI XML stream.
+///
+/// INDI streams have no root element; each top-level element is one message.
+pub(super) struct ElementReader<R> {
+    reader: Reader<R>,
+    buf: Vec<u8>,
+}
+
+impl<R: BufRead> ElementReader<R> {
+    pub fn new(inner: R) -> Self {
+        let mut reader = Reader::from_reader(inner);
+        let config = reader.config_mut();
+        config.trim_text(true);
+        config.expand_empty_elements = true;
+        Self {
+            reader,
+            buf: Vec::new(),
+        }
+    }
+
+    /// Reads the next top-level element, or `None` at end of stream.
+    pub fn next_element(&mut self) -> io::Result<Option<Element>> {
+        let mut stack: Vec<Element> = Vec::new();
+        loop {
+            self.buf.clear();
+            let event = self
+                .reader
+                .read_event_into(&mut self.buf)
+                .map_err(xml_error)?;
+            match event {
+                Event::Start(start) => stack.push(element(&start)?),
+                Event::End(_) => {
+                    let done = stack.pop().expect("end without start");
+                    match stack.last_mut() {
+                        Some(parent) => parent.children.push(done),
+                        None => return Ok(Some(done)),
+                    }
+                }
+                Event::Text(text) => {
+                    if let Some(current) = stack.last_mut() {
+                        current.text.push_str(&text.unescape().map_err(xml_error)?);
+                    }
+                }
+                Event::CData(data) => {
+                    if let Some(current) = stack.last_mut() {
+                        current.text.push_str(&String::from_utf8_lossy(&data));
+                    }
+                }
+                Event::Eof => {
+                    return match stack.is_empty() {
+                        true => Ok(None),
+                        false => Err(io::Error::new(
+                            io::ErrorKind::UnexpectedEof,
+                            "INDI stream ended inside an element",
+                        )),
+                    };
+                }
+                // Declarations, comments and processing instructions.
+                _ => {}
+            }
+        }
+    }
+}
+
+fn element(start: &BytesStart<'_>) -> io::Result<Element> {
+    let mut attributes = Vec::new();
+    for attr in start.attributes() {
+        let attr = attr.map_err(xml_error)?;
+        attributes.push((
+            String::from_utf8_lossy(attr.key.as_ref()).into_owned(),
+            attr.unescape_value().map_err(xml_error)?.into_owned(),
+        ));
+    }
+    Ok(Element {
+        name: String::from_utf8_lossy(start.name().as_ref()).into_owned(),
+        attributes,
+        ..Default::default()
+    })
+}
+
+fn xml_error(e: impl std::fmt::Display) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("malformed INDI XML: {e}"),
+    )
+}
+
+/// New values a client sends for a property vector.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct NewVector<T> {
+    pub device: String,
+    pub name: String,
+    pub values: Vec<(String, T)>,
+}
+
+impl<T: Copy> NewVector<T> {
+    /// Returns the value sent for element `name`.
+    pub fn get(&self, name: &str) -> Option<T> {
+        self.values.iter().find(|(n, _)| n == name).map(|(_, v)| *v)
+    }
+}
+
+/// A message from an INDI client.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum ClientMessage {
+    GetProperties {
+        device: Option<String>,
+        name: Option<String>,
+    },
+    EnableBlob {
+        device: String,
+        name: Option<String>,
+        mode: BlobMode,
+    },
+    NewNumber(NewVector<f64>),
+    NewSwitch(NewVector<bool>),
+    NewText(NewVector<String>),
+}
+
+impl ClientMessage {
+    /// Interprets a top-level element. Returns `None` for messages the server
+    /// does not handle; malformed values are an
+    /// [`InvalidData`](io::ErrorKind::InvalidData) error.
+    pub fn from_element(e: &Element) -> io::Result<Option<Self>> {
+        let owned = |name| e.attr(name).map(str::to_owned);
+        let required = |name| {
+            owned(name).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("<{}> without {name}", e.name),
+                )
+            })
+        };
+        let vector = |child: &str| -> io::Result<Vec<(String, &str)>> {
+            e.children
+                .iter()
+                .filter(|c| c.name == child)
+                .map(|c| match c.attr("name") {
+                    Some(name) => Ok((name.to_owned(), c.text.as_str())),
+                    None => Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("<{child}> without name"),
+                    )),
+                })
+                .collect()
+        };
+        let invalid =
+            |what: &str| io::Error::new(io::ErrorKind::InvalidData, format!("invalid {what}"));
+
+        Ok(Some(match e.name.as_str() {
+            "getProperties" => Self::GetProperties {
+                device: owned("device"),
+                name: owned("name"),
+            },
+            "enableBLOB" => Self::EnableBlob {
+                device: required("device")?,
+                name: owned("name"),
+                mode: BlobMode::parse(&e.text).ok_or_else(|| invalid("enableBLOB mode"))?,
+            },
+            "newNumberVector" => Self::NewNumber(NewVector {
+                device: required("device")?,
+                name: required("name")?,
+                values: vector("oneNumber")?
+                    .into_iter()
+                    .map(|(n, v)| Ok((n, parse_number(v).ok_or_else(|| invalid("number"))?)))
+                    .collect::<io::Result<_>>()?,
+            }),
+            "newSwitchVector" => Self::NewSwitch(NewVector {
+                device: required("device")?,
+                name: required("name")?,
+                values: vector("oneSwitch")?
+                    .into_iter()
+                    .map(|(n, v)| match v {
+                        "On" => Ok((n, true)),
+                        "Off" => Ok((n, false)),
+                        _ => Err(invalid("switch state")),
+                    })
+                    .collect::<io::Result<_>>()?,
+            }),
+            "newTextVector" => Self::NewText(NewVector {
+                device: required("device")?,
+                name: required("name")?,
+                values: vector("oneText")?
+                    .into_iter()
+                    .map(|(n, v)| (n, v.to_owned()))
+                    .collect(),
+            }),
+            _ => return Ok(None),
+        }))
+    }
+}
+
+/// Parses an INDI number: decimal, or sexagesimal `d:m:s` / `d:m`.
+pub(super) fn parse_number(s: &str) -> Option<f64> {
+    let s = s.trim();
+    if !s.contains(':') {
+        return s.parse().ok().filter(|v: &f64| v.is_finite());
+    }
+    let negative = s.starts_with('-');
+    let mut value = 0.0;
+    let mut scale = 1.0;
+    for part in s.trim_start_matches('-').split(':') {
+        let part: f64 = part.trim().parse().ok()?;
+        if part < 0.0 {
+            return None;
+        }
+        value += part / scale;
+        scale *= 60.0;
+    }
+    Some(if negative { -value } else { value })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_all(xml: &str) -> Vec<Element> {
+        let mut reader = ElementReader::new(xml.as_bytes());
+        let mut out = Vec::new();
+        while let Some(e) = reader.next_element().unwrap() {
+            out.push(e);
+        }
+        out
+    }
+
+    fn messages(xml: &str) -> Vec<ClientMessage> {
+        read_all(xml)
+            .iter()
+            .filter_map(|e| ClientMessage::from_element(e).unwrap())
+            .collect()
+    }
+
+    fn exposure() -> Property {
+        Property::new(
+            "SV305",
+            "CCD_EXPOSURE",
+            "Expose",
+            "Main Control",
+            Permission::ReadWrite,
+            Elements::Number(vec![NumberElement {
+                name: "CCD_EXPOSURE_VALUE".into(),
+                label: "Duration (s)".into(),
+                format: "%5.2f".into(),
+                min: 0.001,
+                max: 3600.0,
+                step: 1.0,
+                value: 1.5,
+            }]),
+        )
+    }
+
+    #[test]
+    fn parses_client_stream() {
+        let xml = r#"<?xml version="1.0"?>
+            <getProperties version='1.7'/>
+            <getProperties version="1.7" device="SV305" name="CCD_INFO"/>
+            <enableBLOB device="SV305">Also</enableBLOB>
+            <newNumberVector device="SV305" name="CCD_EXPOSURE">
+              <oneNumber name="CCD_EXPOSURE_VALUE"> 2.5 </oneNumber>
+            </newNumberVector>
+            <newSwitchVector device="SV305" name="CONNECTION">
+              <oneSwitch name="CONNECT">On</oneSwitch>
+              <oneSwitch name="DISCONNECT">Off</oneSwitch>
+            </newSwitchVector>
+            <pingReply uid="1"/>
+            <newTextVector device="SV305" name="NOTES"><oneText name="T">a &amp; b</oneText></newTextVector>"#;
+        let msgs = messages(xml);
+        assert_eq!(msgs.len(), 6);
+        assert_eq!(
+            msgs[0],
+            ClientMessage::GetProperties {
+                device: None,
+                name: None
+            }
+        );
+        assert_eq!(
+            msgs[1],
+            ClientMessage::GetProperties {
+                device: Some("SV305".into()),
+                name: Some("CCD_INFO".into())
+            }
+        );
+        assert_eq!(
+            msgs[2],
+            ClientMessage::EnableBlob {
+                device: "SV305".into(),
+                name: None,
+                mode: BlobMode::Also
+            }
+        );
+        match &msgs[3] {
+            ClientMessage::NewNumber(v) => {
+                assert_eq!(v.name, "CCD_EXPOSURE");
+                assert_eq!(v.get("CCD_EXPOSURE_VALUE"), Some(2.5));
+            }
+            other => panic!("{other:?}"),
+        }
+        match &msgs[4] {
+            ClientMessage::NewSwitch(v) => {
+                assert_eq!(v.get("CONNECT"), Some(true));
+                assert_eq!(v.get("DISCONNECT"), Some(false));
+            }
+            other => panic!("{other:?}"),
+        }
+        match &msgs[5] {
+            ClientMessage::NewText(v) => assert_eq!(v.values, [("T".into(), "a & b".into())]),
+            other => panic!("{other:?}"),
+        }
+    }
+
+    #[test]
+    fn malformed_messages_are_errors() {
+        for xml in [
+            r#"<newSwitchVector device="D" name="S"><oneSwitch name="A">Maybe</oneSwitch></newSwitchVector>"#,
+            r#"<newNumberVector device="D" name="N"><oneNumber name="A">abc</oneNumber></newNumberVector>"#,
+            r#"<newNumberVector name="N"/>"#,
+            r#"<enableBLOB device="D">Sometimes</enableBLOB>"#,
+        ] {
+            let e = &read_all(xml)[0];
+            assert!(ClientMessage::from_element(e).is_err(), "{xml}");
+        }
+        let mut truncated = ElementReader::new(&b"<newNumberVector device=\"D\">"[..]);
+        assert!(truncated.next_element().is_err());
+    }
+
+    #[test]
+    fn numbers_parse_sexagesimal() {
+        assert_eq!(parse_number("12.5"), Some(12.5));
+        assert_eq!(parse_number("-10:30"), Some(-10.5));
+        assert_eq!(parse_number("1:30:36"), Some(1.51));
+        assert_eq!(parse_number("nan"), None);
+        assert_eq!(parse_number("1:x"), None);
+    }
+
+    #[test]
+    fn def_and_set_round_trip() {
+        let mut prop = exposure();
+        let def = read_all(&prop.def_xml()).remove(0);
+        assert_eq!(def.name, "defNumberVector");
+        assert_eq!(def.attr("device"), Some("SV305"));
+        assert_eq!(def.attr("perm"), Some("rw"));
+        assert_eq!(def.attr("state"), Some("Idle"));
+        let n = def.child("defNumber").unwrap();
+        assert_eq!(n.attr("name"), Some("CCD_EXPOSURE_VALUE"));
+        assert_eq!(n.attr("max"), Some("3600"));
+        assert_eq!(n.text, "1.5");
+
+        prop.state = PropertyState::Busy;
+        assert!(prop.set_number("CCD_EXPOSURE_VALUE", 0.25));
+        assert!(!prop.set_number("MISSING", 1.0));
+        let set = read_all(&prop.set_xml(Some("exposing <1s>"))).remove(0);
+        assert_eq!(set.name, "setNumberVector");
+        assert_eq!(set.attr("state"), Some("Busy"));
+        assert_eq!(set.attr("message"), Some("exposing <1s>"));
+        assert_eq!(set.child("oneNumber").unwrap().text, "0.25");
+    }
+
+    #[test]
+    fn switches_follow_rule() {
+        let switch = |name: &str, on| SwitchElement {
+            name: name.into(),
+            label: name.into(),
+            on,
+        };
+        let mut prop = Property::new(
+            "D",
+            "CCD_CAPTURE_FORMAT",
+            "Format",
+            "Image Settings",
+            Permission::ReadWrite,
+            Elements::Switch(
+                SwitchRule::OneOfMany,
+                vec![switch("RAW8", true), switch("RAW16", false)],
+            ),
+        );
+        assert!(prop.select_switch("RAW16"));
+        assert_eq!(prop.selected_switch(), Some("RAW16"));
+        assert_eq!(prop.switch("RAW8"), Some(false));
+        assert!(!prop.select_switch("Y8"));
+
+        let def = read_all(&prop.def_xml()).remove(0);
+        assert_eq!(def.attr("rule"), Some("OneOfMany"));
+        let states: Vec<_> = def.children.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(states, ["Off", "On"]);
+    }
+
+    #[test]
+    fn blobs_are_base64() {
+        let xml = set_blob_xml(
+            "D",
+            "CCD1",
+            PropertyState::Ok,
+            &[Blob {
+                name: "CCD1".into(),
+                format: ".fits".into(),
+                data: b"SIMPLE  =".to_vec(),
+            }],
+        );
+        let e = read_all(&xml).remove(0);
+        let blob = e.child("oneBLOB").unwrap();
+        assert_eq!(blob.attr("size"), Some("9"));
+        assert_eq!(blob.attr("format"), Some(".fits"));
+        assert_eq!(blob.text, "U0lNUExFICA9");
+    }
+
+    #[test]
+    fn text_is_escaped() {
+        let xml = message_xml("D<1>", "say \"hi\" & 'bye'");
+        let e = read_all(&xml).remove(0);
+        assert_eq!(e.attr("device"), Some("D<1>"));
+        assert_eq!(e.attr("message"), Some("say \"hi\" & 'bye'"));
+        let del = read_all(&del_property_xml("D", Some("CCD_INFO"))).remove(0);
+        assert_eq!(del.attr("name"), Some("CCD_INFO"));
+    }
+}
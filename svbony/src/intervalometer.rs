@@ -0,0 +1,491 @@
+//! Time-lapse capture at fixed intervals or scheduled wall-clock times.
+//!
+//! [`Intervalometer`] takes single exposures with software triggers (see
+//! [`Camera::start_triggered`]) according to an [`IntervalConfig`]. Shot
+//! times are computed from the start time rather than from the end of the
+//! previous shot, so timing does not drift however long each exposure and
+//! download take. When a shot overruns into the next slot, the
+//! [`Overrun`] policy decides whether missed slots are skipped or taken late.
+//! Frames are handed to a [`FrameSink`].
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use svbony::Camera;
+//! use svbony::intervalometer::{
+//!     DirectorySink, IntervalConfig, Intervalometer, ShotSettings, Timing,
+//! };
+//!
+//! let cam = Camera::open(0)?;
+//! let config = IntervalConfig {
+//!     count: Some(120),
+//!     ..IntervalConfig::new(Timing::Every(Duration::from_secs(30)))
+//! };
+//! let mut sink = DirectorySink::new("allsky", "sky_")?;
+//! let report = Intervalometer::new(&cam, config).run(&mut sink, |_| ShotSettings {
+//!     exposure: Some(Duration::from_secs(10)),
+//!     gain: None,
+//! })?;
+//! println!("{} taken, {} skipped", report.taken, report.skipped);
+//! # Ok::<(), svbony::intervalometer::IntervalError>(())
+//! ```
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::{Camera, CameraMode, ControlType, Error, Frame};
+
+/// When shots are due.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Timing {
+    /// Every `interval`, starting at [`IntervalConfig::start`].
+    Every(Duration),
+    /// At the given wall-clock times (sorted before use).
+    At(Vec<SystemTime>),
+}
+
+/// What to do with slots that passed while a previous shot was still
+/// running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overrun {
+    /// Drop the missed slots and continue with the next future one.
+    Skip,
+    /// Take the missed shots immediately, one after another.
+    CatchUp,
+}
+
+/// Settings for [`Intervalometer`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct IntervalConfig {
+    /// When shots are due.
+    pub timing: Timing,
+    /// Maximum number of shots, including any that time out.
+    pub count: Option<u64>,
+    /// Time of the first [`Timing::Every`] slot; `None` starts immediately.
+    /// Earlier [`Timing::At`] times are dropped.
+    pub start: Option<SystemTime>,
+    /// No shot is started at or after this time.
+    pub stop: Option<SystemTime>,
+    /// What to do with slots missed while a shot was running.
+    pub overrun: Overrun,
+    /// Time allowed for a frame beyond its exposure before the shot is
+    /// counted as timed out.
+    pub frame_timeout: Duration,
+}
+
+impl IntervalConfig {
+    /// Creates a config with no count or time limits, skipping overrun
+    /// slots.
+    pub fn new(timing: Timing) -> Self {
+        Self {
+            timing,
+            count: None,
+            start: None,
+            stop: None,
+            overrun: Overrun::Skip,
+            frame_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Per-shot camera settings; `None` leaves the current value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ShotSettings {
+    /// Exposure time for the shot.
+    pub exposure: Option<Duration>,
+    /// Gain control value for the shot.
+    pub gain: Option<i64>,
+}
+
+/// Details of one captured shot.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Shot {
+    /// 0-based slot number in the schedule. Skipped slots leave gaps.
+    pub slot: u64,
+    /// 0-based number of this shot among those taken.
+    pub sequence: u64,
+    /// When the slot was due.
+    pub scheduled: SystemTime,
+    /// When the trigger was sent.
+    pub triggered: SystemTime,
+    /// Settings applied for this shot.
+    pub settings: ShotSettings,
+}
+
+impl Shot {
+    /// How late the trigger was relative to the schedule.
+    pub fn lateness(&self) -> Duration {
+        self.triggered
+            .duration_since(self.scheduled)
+            .unwrap_or(Duration::ZERO)
+    }
+}
+
+/// Summary returned by [`Intervalometer::run`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct IntervalReport {
+    /// Shots captured and passed to the sink.
+    pub taken: u64,
+    /// Slots dropped by [`Overrun::Skip`].
+    pub skipped: u64,
+    /// Shots triggered but not received within the timeout.
+    pub timed_out: u64,
+}
+
+/// Error from [`Intervalometer::run`].
+#[derive(Debug, thiserror::Error)]
+pub enum IntervalError {
+    /// A camera call failed.
+    #[error(transparent)]
+    Camera(#[from] Error),
+    /// The [`FrameSink`] failed to save a frame.
+    #[error("frame sink: {0}")]
+    Sink(#[from] io::Error),
+}
+
+/// Destination for captured frames.
+pub trait FrameSink {
+    /// Stores the frame captured for `shot`.
+    fn save(&mut self, shot: &Shot, frame: Frame) -> io::Result<()>;
+}
+
+impl<F: FnMut(&Shot, Frame) -> io::Result<()>> FrameSink for F {
+    fn save(&mut self, shot: &Shot, frame: Frame) -> io::Result<()> {
+        self(shot, frame)
+    }
+}
+
+/// Keeps every frame in memory.
+#[derive(Debug, Clone, Default)]
+pub struct MemorySink {
+    /// Every shot so far with its frame, in capture order.
+    pub frames: Vec<(Shot, Frame)>,
+}
+
+impl FrameSink for MemorySink {
+    fn save(&mut self, shot: &Shot, frame: Frame) -> io::Result<()> {
+        self.frames.push((*shot, frame));
+        Ok(())
+    }
+}
+
+/// Writes each frame's raw sample bytes to `<dir>/<prefix><slot>.raw`, with
+/// the slot number zero-padded to five digits. Load them back with
+/// [`Frame::new`] and the capture's ROI and image type.
+#[derive(Debug, Clone)]
+pub struct DirectorySink {
+    dir: PathBuf,
+    prefix: String,
+    /// Files written so far, in order.
+    pub paths: Vec<PathBuf>,
+}
+
+impl DirectorySink {
+    /// Creates the sink, creating `dir` if needed.
+    pub fn new(dir: impl Into<PathBuf>, prefix: impl Into<String>) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir,
+            prefix: prefix.into(),
+            paths: Vec::new(),
+        })
+    }
+}
+
+impl FrameSink for DirectorySink {
+    fn save(&mut self, shot: &Shot, frame: Frame) -> io::Result<()> {
+        let path = self
+            .dir
+            .join(format!("{}{:05}.raw", self.prefix, shot.slot));
+        fs::write(&path, &frame.data)?;
+        self.paths.push(path);
+        Ok(())
+    }
+}
+
+/// A due slot returned by [`Scheduler::next`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Slot {
+    index: u64,
+    at: SystemTime,
+    /// Slots skipped to reach this one.
+    skipped: u64,
+}
+
+/// Computes slot times from the schedule, independent of how long shots
+/// take.
+#[derive(Debug, Clone)]
+struct Scheduler {
+    timing: Timing,
+    start: SystemTime,
+    stop: Option<SystemTime>,
+    count: Option<u64>,
+    overrun: Overrun,
+    next_index: u64,
+    taken: u64,
+}
+
+impl Scheduler {
+    fn new(config: &IntervalConfig, now: SystemTime) -> Self {
+        let start = config.start.unwrap_or(now);
+        let timing = match &config.timing {
+            Timing::Every(interval) => Timing::Every(*interval),
+            Timing::At(times) => {
+                let mut times: Vec<_> = times.iter().copied().filter(|&t| t >= start).collect();
+                times.sort();
+                Timing::At(times)
+            }
+        };
+        Self {
+            timing,
+            start,
+            stop: config.stop,
+            count: config.count,
+            overrun: config.overrun,
+            next_index: 0,
+            taken: 0,
+        }
+    }
+
+    fn slot_time(&self, index: u64) -> Option<SystemTime> {
+        match &self.timing {
+            Timing::Every(interval) => {
+                let offset = interval.checked_mul(u32::try_from(index).ok()?)?;
+                self.start.checked_add(offset)
+            }
+            Timing::At(times) => times.get(usize::try_from(index).ok()?).copied(),
+        }
+    }
+
+    /// Returns the next slot to shoot as of `now`, or `None` when the
+    /// schedule is exhausted.
+    fn next(&mut self, now: SystemTime) -> Option<Slot> {
+        if self.count.map_or(false, |c| self.taken >= c) {
+            return None;
+        }
+        let mut index = self.next_index;
+        let mut at = self.slot_time(index)?;
+        let mut skipped = 0;
+        if self.overrun == Overrun::Skip {
+            // Slots are only missed once a later slot is also due.
+            while let Some(later) = self.slot_time(index + 1).filter(|&t| t <= now) {
+                index += 1;
+                at = later;
+                skipped += 1;
+            }
+        }
+        // A slot scheduled before the stop time may only come due after it,
+        // when the previous shot overran.
+        if self.stop.map_or(false, |stop| at >= stop || now >= stop) {
+            return None;
+        }
+        self.next_index = index + 1;
+        self.taken += 1;
+        Some(Slot { index, at, skipped })
+    }
+}
+
+/// Runs a time-lapse on one camera; see the [module docs](self).
+pub struct Intervalometer<'a> {
+    camera: &'a Camera,
+    config: IntervalConfig,
+}
+
+impl<'a> Intervalometer<'a> {
+    /// Creates an intervalometer for `camera` with the given schedule.
+    pub fn new(camera: &'a Camera, config: IntervalConfig) -> Self {
+        Self { camera, config }
+    }
+
+    /// Returns the schedule settings.
+    pub fn config(&self) -> &IntervalConfig {
+        &self.config
+    }
+
+    /// Runs the schedule to completion.
+    ///
+    /// `settings` is called with each slot number before its shot and its
+    /// result applied to the camera. The camera is switched to
+    /// [`CameraMode::TrigSoft`] for the run and back to
+    /// [`CameraMode::Normal`] afterwards, also on error.
+    pub fn run(
+        &self,
+        sink: &mut impl FrameSink,
+        mut settings: impl FnMut(u64) -> ShotSettings,
+    ) -> std::result::Result<IntervalReport, IntervalError> {
+        if let Timing::Every(interval) = self.config.timing {
+            if interval.is_zero() {
+                return Err(Error::InvalidArgument("interval must be non-zero".into()).into());
+            }
+        }
+        let mut capture = self.camera.start_triggered(CameraMode::TrigSoft)?;
+        let mut scheduler = Scheduler::new(&self.config, SystemTime::now());
+        let mut report = IntervalReport::default();
+
+        while let Some(slot) = scheduler.next(SystemTime::now()) {
+            report.skipped += slot.skipped;
+            if let Ok(wait) = slot.at.duration_since(SystemTime::now()) {
+                thread::sleep(wait);
+            }
+
+            let shot_settings = settings(slot.index);
+            if let Some(exposure) = shot_settings.exposure {
+                self.camera.set_exposure(exposure)?;
+            }
+            if let Some(gain) = shot_settings.gain {
+                self.camera.set_control(ControlType::Gain, gain, false)?;
+            }
+            let exposure = self.camera.exposure()?;
+
+            let triggered = SystemTime::now();
+            capture.soft_trigger()?;
+            let Some(received) = capture.next_frame(exposure + self.config.frame_timeout)? else {
+                report.timed_out += 1;
+                continue;
+            };
+            let shot = Shot {
+                slot: slot.index,
+                sequence: report.taken,
+                scheduled: slot.at,
+                triggered,
+                settings: shot_settings,
+            };
+            sink.save(&shot, received.frame)?;
+            report.taken += 1;
+        }
+        capture.finish()?;
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn t(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000 + secs)
+    }
+
+    fn every(secs: u64, overrun: Overrun) -> IntervalConfig {
+        IntervalConfig {
+            start: Some(t(0)),
+            overrun,
+            ..IntervalConfig::new(Timing::Every(Duration::from_secs(secs)))
+        }
+    }
+
+    #[test]
+    fn interval_slots_do_not_drift() {
+        let mut s = Scheduler::new(&every(10, Overrun::Skip), t(0));
+        // Each shot finishes a few seconds into its slot.
+        for k in 0..5 {
+            let slot = s.next(t(k * 10 + 3)).unwrap();
+            assert_eq!(slot.index, k);
+            assert_eq!(slot.at, t(k * 10));
+            assert_eq!(slot.skipped, 0);
+        }
+    }
+
+    #[test]
+    fn overrun_skip_and_catch_up() {
+        let mut skip = Scheduler::new(&every(10, Overrun::Skip), t(0));
+        assert_eq!(skip.next(t(0)).unwrap().index, 0);
+        // Shot 0 ran until t=35: slots 1 and 2 are missed, 3 is due.
+        let slot = skip.next(t(35)).unwrap();
+        assert_eq!((slot.index, slot.at, slot.skipped), (3, t(30), 2));
+        assert_eq!(skip.next(t(36)).unwrap().index, 4);
+
+        let mut catch_up = Scheduler::new(&every(10, Overrun::CatchUp), t(0));
+        catch_up.next(t(0)).unwrap();
+        for k in 1..=3 {
+            let slot = catch_up.next(t(35)).unwrap();
+            assert_eq!((slot.index, slot.skipped), (k, 0));
+        }
+    }
+
+    #[test]
+    fn count_and_stop_limit_the_run() {
+        let mut s = Scheduler::new(
+            &IntervalConfig {
+                count: Some(2),
+                ..every(10, Overrun::Skip)
+            },
+            t(0),
+        );
+        assert!(s.next(t(0)).is_some());
+        assert!(s.next(t(10)).is_some());
+        assert_eq!(s.next(t(20)), None);
+
+        let mut s = Scheduler::new(
+            &IntervalConfig {
+                stop: Some(t(25)),
+                ..every(10, Overrun::Skip)
+            },
+            t(0),
+        );
+        let slots: Vec<_> = std::iter::from_fn(|| s.next(t(0)))
+            .map(|s| s.index)
+            .collect();
+        assert_eq!(slots, [0, 1, 2]);
+    }
+
+    #[test]
+    fn no_shot_starts_after_stop() {
+        for overrun in [Overrun::Skip, Overrun::CatchUp] {
+            let mut s = Scheduler::new(
+                &IntervalConfig {
+                    stop: Some(t(25)),
+                    ..every(10, overrun)
+                },
+                t(0),
+            );
+            assert!(s.next(t(0)).is_some());
+            assert!(s.next(t(10)).is_some());
+            // Shot 1 ran until t=27; slot 2 (t=20) is now past the stop.
+            assert_eq!(s.next(t(27)), None, "{overrun:?}");
+        }
+    }
+
+    #[test]
+    fn wall_clock_times() {
+        let config = IntervalConfig {
+            start: Some(t(5)),
+            ..IntervalConfig::new(Timing::At(vec![t(60), t(1), t(30), t(90)]))
+        };
+        let mut s = Scheduler::new(&config, t(0));
+        assert_eq!(s.next(t(0)).unwrap().at, t(30));
+        // Shot at 30 overran past 90: 60 is skipped.
+        let slot = s.next(t(95)).unwrap();
+        assert_eq!((slot.at, slot.skipped), (t(90), 1));
+        assert_eq!(s.next(t(100)), None);
+    }
+
+    #[test]
+    fn directory_sink_writes_raw_frames() {
+        let dir = std::env::temp_dir().join(format!("svbony-interval-{}", std::process::id()));
+        let mut sink = DirectorySink::new(&dir, "sky_").unwrap();
+        let roi = crate::RoiFormat {
+            start_x: 0,
+            start_y: 0,
+            width: 8,
+            height: 2,
+            bin: 1,
+        };
+        let frame = Frame::new(roi, crate::ImageType::Y8, (0..16).collect()).unwrap();
+        let shot = Shot {
+            slot: 7,
+            sequence: 0,
+            scheduled: t(0),
+            triggered: t(1),
+            settings: ShotSettings::default(),
+        };
+        assert_eq!(shot.lateness(), Duration::from_secs(1));
+        sink.save(&shot, frame.clone()).unwrap();
+        assert_eq!(sink.paths, [dir.join("sky_00007.raw")]);
+        assert_eq!(fs::read(&sink.paths[0]).unwrap(), frame.data);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
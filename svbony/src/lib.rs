@@ -36,22 +36,67 @@
 //!
 //! | Feature | Default | Description |
 //! |---------|---------|-------------|
+//! | `alpaca` | off | ASCOM Alpaca camera server with discovery for N.I.N.A. and other Alpaca clients ([`alpaca`]). |
 //! | `image` | off | Adds [`Camera::get_image`] which returns an [`image::DynamicImage`]. |
-
+//! | `indi` | off | INDI protocol server for KStars/Ekos and other INDI clients ([`indi`]). |
+//! | `ndarray` | off | Adds `ndarray` views of [`Frame`]s and capture into caller-provided arrays. |
+//! | `profiles` | off | Named settings profiles per camera in the user config directory ([`profile`]); implies `serde`. |
+//! | `serde` | off | Derives `Serialize` / `Deserialize` for [`CameraSettings`] and the SDK enums and ROI types. |
+
+// `c_long` is 64-bit on Unix but 32-bit on Windows, so casts that are no-ops
+// on one platform are required on the other.
+#![allow(clippy::unnecessary_cast)]
+
+#[cfg(feature = "alpaca")]
+pub mod alpaca;
+#[cfg(feature = "ndarray")]
+mod array;
+mod capabilities;
+mod controls;
+pub mod cooler;
+pub mod defect;
 mod error;
+pub mod fits;
+pub mod flat;
+mod frame;
+pub mod guide;
+#[cfg(feature = "indi")]
+pub mod indi;
+pub mod intervalometer;
+pub mod lucky;
+#[cfg(feature = "profiles")]
+pub mod profile;
+mod roi;
+#[cfg(any(feature = "alpaca", feature = "indi"))]
+mod server;
+mod settings;
+mod stats;
+pub mod stretch;
+pub mod telemetry;
+mod trigger;
+pub mod triggered;
 mod types;
 
+pub use capabilities::Capabilities;
+pub use controls::{Celsius, ControlEntry, ControlMap, ControlValue, Percent};
 pub use error::{Error, Result};
+pub use frame::{Frame, FrameStats};
+pub use roi::{max_roi_size, RoiBuilder, ROI_HEIGHT_ALIGN, ROI_WIDTH_ALIGN};
+pub use settings::{CameraSettings, ControlSetting, TriggerOutputSetting};
+pub use trigger::{
+    TriggerOutput, TriggerOutputConfig, TriggerOutputs, TriggerPolarity, MAX_TRIGGER_OUTPUT_US,
+};
 pub use types::*;
 
 use std::ffi::CStr;
 use std::mem::MaybeUninit;
 use std::os::raw::{c_char, c_int, c_long};
+use std::sync::Mutex;
 
 use error::check;
 
 #[cfg(feature = "image")]
-use image::{DynamicImage, GrayImage, ImageBuffer, Rgb, Rgba};
+use image::DynamicImage;
 
 /// Returns the SDK version string (e.g. `"1, 13, 0503"`).
 pub fn sdk_version() -> String {
@@ -111,18 +156,52 @@ pub fn connected_cameras() -> Result<Vec<CameraInfo>> {
 /// ```
 pub struct Camera {
     id: c_int,
+    /// Control capabilities, read once at open.
+    caps: Vec<ControlCaps>,
+    /// Last known value of each control in `caps`, by index.
+    control_values: Mutex<Vec<Option<ControlValue>>>,
+    /// Filled in by the first call to [`capabilities`](Self::capabilities).
+    capabilities: Mutex<Option<Capabilities>>,
 }
 
 impl Camera {
     /// Opens a camera by its ID (from [`CameraInfo::camera_id`]).
     ///
     /// The camera must not already be open. Returns an RAII handle that closes
-    /// the camera on drop.
+    /// the camera on drop. The capabilities and values of every control are
+    /// read and cached while opening (see [`controls`](Self::controls)).
+    ///
+    /// With the `profiles` feature, the camera's default profile is applied
+    /// before returning, if one is set; failing to read or apply it fails
+    /// the open.
     pub fn open(camera_id: i32) -> Result<Self> {
         check(unsafe { svbony_sys::SVBOpenCamera(camera_id as c_int) })?;
-        Ok(Self {
+        let mut cam = Self {
             id: camera_id as c_int,
-        })
+            caps: Vec::new(),
+            control_values: Mutex::new(Vec::new()),
+            capabilities: Mutex::new(None),
+        };
+        // On error `cam` is dropped, which closes the camera again.
+        cam.caps = cam.read_all_caps()?;
+        cam.control_values = Mutex::new(cam.read_control_values());
+        #[cfg(feature = "profiles")]
+        profile::apply_default(&cam)?;
+        Ok(cam)
+    }
+
+    /// Queries the caps of every control, including types this crate does
+    /// not recognise.
+    fn read_all_caps(&self) -> Result<Vec<ControlCaps>> {
+        let mut all = Vec::new();
+        for i in 0..self.num_controls()? {
+            let mut caps = MaybeUninit::uninit();
+            check(unsafe {
+                svbony_sys::SVBGetControlCaps(self.id, i as c_int, caps.as_mut_ptr())
+            })?;
+            all.push(ControlCaps::from(unsafe { &caps.assume_init() }));
+        }
+        Ok(all)
     }
 
     /// Returns the SDK camera ID.
@@ -130,6 +209,15 @@ impl Camera {
         self.id as i32
     }
 
+    /// Returns this camera's entry from [`connected_cameras`] (name, serial
+    /// and port type).
+    pub fn info(&self) -> Result<CameraInfo> {
+        connected_cameras()?
+            .into_iter()
+            .find(|info| info.camera_id == self.id as i32)
+            .ok_or(Error::InvalidId)
+    }
+
     // --- Property queries ---
 
     /// Returns static sensor properties (resolution, color, supported formats).
@@ -184,7 +272,7 @@ impl Camera {
             .iter()
             .copied()
             .take_while(|&m| m != svbony_sys::SVB_MODE_END)
-            .filter_map(|m| CameraMode::try_from(m).ok())
+            .map(CameraMode::from)
             .collect())
     }
 
@@ -228,27 +316,68 @@ impl Camera {
         Ok(ControlCaps::from(unsafe { &caps.assume_init() }))
     }
 
+    /// Returns the cached capabilities of every control the camera exposes.
+    pub fn supported_controls(&self) -> &[ControlCaps] {
+        &self.caps
+    }
+
+    /// Returns the cached capabilities of `ctrl`, or `None` if the camera
+    /// does not have this control.
+    pub fn caps(&self, ctrl: ControlType) -> Option<&ControlCaps> {
+        self.caps.iter().find(|c| c.control_type == ctrl)
+    }
+
     /// Reads the current value and auto-mode flag for a control.
     ///
     /// Returns `(value, is_auto)`. Temperature values are reported as
-    /// `float * 10` (e.g. 250 = 25.0 C).
+    /// `float * 10` (e.g. 250 = 25.0 C). Typed accessors such as
+    /// [`exposure`](Self::exposure) and
+    /// [`sensor_temperature`](Self::sensor_temperature) convert the units.
     pub fn get_control(&self, ctrl: ControlType) -> Result<(i64, bool)> {
         let mut value: c_long = 0;
         let mut auto_: c_int = 0;
         check(unsafe {
-            svbony_sys::SVBGetControlValue(self.id, ctrl as c_int, &mut value, &mut auto_)
+            svbony_sys::SVBGetControlValue(self.id, ctrl.raw(), &mut value, &mut auto_)
         })?;
         Ok((value as i64, auto_ != 0))
     }
 
     /// Sets a control value and auto-mode flag.
     ///
-    /// Values outside the valid range are clamped by the SDK.
+    /// The write is checked against the control's cached [`ControlCaps`]
+    /// first: controls the camera lacks return [`Error::InvalidControlType`],
+    /// and read-only controls, unsupported auto mode and values outside
+    /// `min_value..=max_value` return [`Error::ReadOnlyControl`],
+    /// [`Error::AutoNotSupported`] and [`Error::OutOfRange`].
     pub fn set_control(&self, ctrl: ControlType, value: i64, auto_: bool) -> Result<()> {
+        self.caps(ctrl)
+            .ok_or(Error::InvalidControlType)?
+            .validate(value, auto_)?;
+        self.write_control(ctrl, value, auto_)?;
+        self.cache_control_value(ctrl, value, auto_);
+        Ok(())
+    }
+
+    /// Like [`set_control`](Self::set_control), but clamps `value` into the
+    /// control's range instead of rejecting it.
+    ///
+    /// Returns the value reported by the camera after the write, which
+    /// reflects any further rounding by the firmware.
+    pub fn set_control_clamped(&self, ctrl: ControlType, value: i64, auto_: bool) -> Result<i64> {
+        let caps = self.caps(ctrl).ok_or(Error::InvalidControlType)?;
+        let value = caps.clamp(value);
+        caps.validate(value, auto_)?;
+        self.write_control(ctrl, value, auto_)?;
+        let (applied, auto_) = self.get_control(ctrl)?;
+        self.cache_control_value(ctrl, applied, auto_);
+        Ok(applied)
+    }
+
+    fn write_control(&self, ctrl: ControlType, value: i64, auto_: bool) -> Result<()> {
         check(unsafe {
             svbony_sys::SVBSetControlValue(
                 self.id,
-                ctrl as c_int,
+                ctrl.raw(),
                 value as c_long,
                 if auto_ { svbony_sys::SVB_TRUE } else { svbony_sys::SVB_FALSE },
             )
@@ -261,13 +390,13 @@ impl Camera {
     pub fn output_image_type(&self) -> Result<ImageType> {
         let mut ty: c_int = 0;
         check(unsafe { svbony_sys::SVBGetOutputImageType(self.id, &mut ty) })?;
-        ImageType::try_from(ty)
+        Ok(ImageType::from(ty))
     }
 
     /// Sets the output pixel format. Must be one of the formats in
     /// [`CameraProperty::supported_formats`].
     pub fn set_output_image_type(&self, ty: ImageType) -> Result<()> {
-        check(unsafe { svbony_sys::SVBSetOutputImageType(self.id, ty as c_int) })
+        check(unsafe { svbony_sys::SVBSetOutputImageType(self.id, ty.raw()) })
     }
 
     /// Returns the current region of interest and binning.
@@ -302,6 +431,32 @@ impl Camera {
         })
     }
 
+    /// Returns `true` if the camera supports the extended ROI calls
+    /// ([`roi_ex`](Self::roi_ex) / [`set_roi_ex`](Self::set_roi_ex)) and so
+    /// a selectable [`BinMode`].
+    ///
+    /// Detected by reading the extended ROI, which older models and SDK
+    /// builds reject.
+    pub fn supports_bin_mode(&self) -> bool {
+        self.roi_ex().is_ok()
+    }
+
+    /// Sets the ROI, using the extended call when a binning mode is given.
+    ///
+    /// With `None` this is [`set_roi`](Self::set_roi) and works on every
+    /// camera. With `Some(mode)` the ROI is set through
+    /// [`set_roi_ex`](Self::set_roi_ex); if the camera lacks extended ROI
+    /// support, [`Error::Unsupported`] is returned and the ROI is unchanged.
+    pub fn apply_roi(&self, roi: &RoiFormat, bin_mode: Option<BinMode>) -> Result<()> {
+        match bin_mode {
+            None => self.set_roi(roi),
+            Some(_) if !self.supports_bin_mode() => {
+                Err(Error::Unsupported("binning mode (extended ROI)".into()))
+            }
+            Some(mode) => self.set_roi_ex(&roi.with_bin_mode(mode)),
+        }
+    }
+
     /// Returns the current ROI, binning, and binning mode.
     pub fn roi_ex(&self) -> Result<RoiFormatEx> {
         let (mut x, mut y, mut w, mut h, mut bin, mut mode) =
@@ -317,12 +472,16 @@ impl Camera {
             width: w,
             height: h,
             bin,
-            bin_mode: mode,
+            bin_mode: BinMode::from(mode),
         })
     }
 
-    /// Sets the ROI, binning, and binning mode (0 = average, 1 = sum).
-    /// Capture must be stopped first.
+    /// Sets the ROI, binning, and binning mode. Capture must be stopped
+    /// first.
+    ///
+    /// Fails on cameras without extended ROI support; see
+    /// [`supports_bin_mode`](Self::supports_bin_mode) and
+    /// [`apply_roi`](Self::apply_roi).
     pub fn set_roi_ex(&self, roi: &RoiFormatEx) -> Result<()> {
         check(unsafe {
             svbony_sys::SVBSetROIFormatEx(
@@ -332,7 +491,7 @@ impl Camera {
                 roi.width,
                 roi.height,
                 roi.bin,
-                roi.bin_mode,
+                roi.bin_mode.raw(),
             )
         })
     }
@@ -368,6 +527,52 @@ impl Camera {
         })
     }
 
+    /// Captures one frame into a newly allocated [`Frame`].
+    ///
+    /// Queries the current ROI and output image type, allocates a matching
+    /// buffer and reads one frame via [`get_frame`](Self::get_frame).
+    pub fn capture_frame(&self, wait_ms: i32) -> Result<Frame> {
+        let roi = self.roi()?;
+        let img_type = self.output_image_type()?;
+        if let ImageType::Other(_) = img_type {
+            return Err(Error::InvalidImageType);
+        }
+        let mut frame = Frame::zeroed(roi, img_type);
+        self.get_frame(&mut frame.data, wait_ms)?;
+        Ok(frame)
+    }
+
+    /// Captures one exposure in normal mode, giving up early if `cancel` is
+    /// set. Returns `Ok(None)` when cancelled.
+    ///
+    /// The exposure time must already be set; `duration` bounds the wait.
+    #[cfg(any(feature = "alpaca", feature = "indi"))]
+    pub(crate) fn expose(
+        &self,
+        duration: std::time::Duration,
+        cancel: &std::sync::atomic::AtomicBool,
+    ) -> Result<Option<Frame>> {
+        use std::sync::atomic::Ordering;
+        use std::time::{Duration, Instant};
+
+        // Fails harmlessly when capture is not running.
+        let _ = self.stop_capture();
+        self.start_capture()?;
+        let deadline = Instant::now() + duration * 2 + Duration::from_secs(5);
+        let result = loop {
+            if cancel.load(Ordering::Relaxed) {
+                break Ok(None);
+            }
+            match self.capture_frame(200) {
+                Ok(frame) => break Ok(Some(frame)),
+                Err(Error::Timeout) if Instant::now() < deadline => {}
+                Err(e) => break Err(e),
+            }
+        };
+        let _ = self.stop_capture();
+        result
+    }
+
     /// Returns the number of frames dropped since capture started.
     ///
     /// Resets to 0 when capture is stopped.
@@ -383,12 +588,12 @@ impl Camera {
     pub fn mode(&self) -> Result<CameraMode> {
         let mut mode: c_int = 0;
         check(unsafe { svbony_sys::SVBGetCameraMode(self.id, &mut mode) })?;
-        CameraMode::try_from(mode)
+        Ok(CameraMode::from(mode))
     }
 
     /// Sets the camera mode. Capture must be stopped first.
     pub fn set_mode(&self, mode: CameraMode) -> Result<()> {
-        check(unsafe { svbony_sys::SVBSetCameraMode(self.id, mode as c_int) })
+        check(unsafe { svbony_sys::SVBSetCameraMode(self.id, mode.raw()) })
     }
 
     /// Sends a software trigger pulse.
@@ -402,7 +607,9 @@ impl Camera {
     /// Configures a trigger output pin.
     ///
     /// `delay_us` and `duration_us` are in microseconds (0 to 2 000 000 000).
-    /// Setting `duration_us <= 0` disables the pin.
+    /// Setting `duration_us <= 0` disables the pin. See
+    /// [`configure_trigger_output`](Self::configure_trigger_output) for a
+    /// typed, validated alternative.
     pub fn set_trigger_output(
         &self,
         pin: TrigOutputPin,
@@ -413,7 +620,7 @@ impl Camera {
         check(unsafe {
             svbony_sys::SVBSetTriggerOutputIOConf(
                 self.id,
-                pin as c_int,
+                pin.raw(),
                 if high { svbony_sys::SVB_TRUE } else { svbony_sys::SVB_FALSE },
                 delay_us as c_long,
                 duration_us as c_long,
@@ -423,7 +630,8 @@ impl Camera {
 
     /// Reads the current trigger output pin configuration.
     ///
-    /// Returns `(active_high, delay_us, duration_us)`.
+    /// Returns `(active_high, delay_us, duration_us)`. See
+    /// [`trigger_output`](Self::trigger_output) for a typed alternative.
     pub fn get_trigger_output(&self, pin: TrigOutputPin) -> Result<(bool, i64, i64)> {
         let mut high: c_int = 0;
         let mut delay: c_long = 0;
@@ -431,7 +639,7 @@ impl Camera {
         check(unsafe {
             svbony_sys::SVBGetTriggerOutputIOConf(
                 self.id,
-                pin as c_int,
+                pin.raw(),
                 &mut high,
                 &mut delay,
                 &mut duration,
@@ -447,7 +655,7 @@ impl Camera {
     /// `duration_ms` is the pulse duration in milliseconds.
     pub fn pulse_guide(&self, dir: GuideDirection, duration_ms: i32) -> Result<()> {
         check(unsafe {
-            svbony_sys::SVBPulseGuide(self.id, dir as c_int, duration_ms as c_int)
+            svbony_sys::SVBPulseGuide(self.id, dir.raw(), duration_ms as c_int)
         })
     }
 
@@ -483,9 +691,9 @@ impl Camera {
 
     /// Captures a frame and returns it as an [`image::DynamicImage`].
     ///
-    /// This is a convenience method that queries the current ROI and output
-    /// image type, allocates a buffer, captures one frame via
-    /// [`get_frame`](Self::get_frame), and wraps the result.
+    /// This is a convenience method that captures one frame via
+    /// [`capture_frame`](Self::capture_frame) and converts it with
+    /// [`Frame::into_image`].
     ///
     /// # Pixel format mapping
     ///
@@ -493,50 +701,11 @@ impl Camera {
     /// |---------------|--------------------------|
     /// | `Raw8`, `Y8` | `ImageLuma8` |
     /// | `Raw10`-`Raw16`, `Y10`-`Y16` | `ImageLuma16` (little-endian) |
-    /// | `Rgb24` | `ImageRgb8` |
-    /// | `Rgb32` | `ImageRgba8` |
+    /// | `Rgb24` | `ImageRgb8` (reordered from BGR) |
+    /// | `Rgb32` | `ImageRgba8` (reordered from BGRA) |
     #[cfg(feature = "image")]
     pub fn get_image(&self, wait_ms: i32) -> Result<DynamicImage> {
-        let roi = self.roi()?;
-        let img_type = self.output_image_type()?;
-        let w = roi.width as u32;
-        let h = roi.height as u32;
-        let buf_size = w as usize * h as usize * img_type.bytes_per_pixel();
-        let mut buf = vec![0u8; buf_size];
-        self.get_frame(&mut buf, wait_ms)?;
-
-        let image = match img_type {
-            ImageType::Raw8 | ImageType::Y8 => {
-                DynamicImage::ImageLuma8(GrayImage::from_raw(w, h, buf).unwrap())
-            }
-            ImageType::Raw10
-            | ImageType::Raw12
-            | ImageType::Raw14
-            | ImageType::Raw16
-            | ImageType::Y10
-            | ImageType::Y12
-            | ImageType::Y14
-            | ImageType::Y16 => {
-                let pixels: Vec<u16> = buf
-                    .chunks_exact(2)
-                    .map(|c| u16::from_le_bytes([c[0], c[1]]))
-                    .collect();
-                DynamicImage::ImageLuma16(
-                    ImageBuffer::from_raw(w, h, pixels).unwrap(),
-                )
-            }
-            ImageType::Rgb24 => {
-                DynamicImage::ImageRgb8(
-                    ImageBuffer::<Rgb<u8>, _>::from_raw(w, h, buf).unwrap(),
-                )
-            }
-            ImageType::Rgb32 => {
-                DynamicImage::ImageRgba8(
-                    ImageBuffer::<Rgba<u8>, _>::from_raw(w, h, buf).unwrap(),
-                )
-            }
-        };
-        Ok(image)
+        Ok(self.capture_frame(wait_ms)?.into_image())
     }
 }
 
@@ -558,69 +727,97 @@ mod tests {
     #[test]
     fn image_type_round_trip() {
         for val in 0..=11 {
-            let t = ImageType::try_from(val as c_int).unwrap();
-            assert_eq!(t as c_int, val);
+            let t = ImageType::from(val as c_int);
+            assert!(!matches!(t, ImageType::Other(_)));
+            assert_eq!(t.raw(), val);
         }
-        assert!(ImageType::try_from(-1 as c_int).is_err());
-        assert!(ImageType::try_from(99 as c_int).is_err());
+        assert_eq!(ImageType::from(-1 as c_int), ImageType::Other(-1));
+        assert_eq!(ImageType::from(99 as c_int), ImageType::Other(99));
+        assert_eq!(ImageType::Other(99).raw(), 99);
+        assert_eq!(ImageType::Other(99).bytes_per_pixel(), 0);
     }
 
     #[test]
     fn bayer_pattern_round_trip() {
         for val in 0..=3 {
-            let b = BayerPattern::try_from(val as c_int).unwrap();
-            assert_eq!(b as c_int, val);
+            let b = BayerPattern::from(val as c_int);
+            assert!(!matches!(b, BayerPattern::Other(_)));
+            assert_eq!(b.raw(), val);
         }
-        assert!(BayerPattern::try_from(4).is_err());
+        assert_eq!(BayerPattern::from(4), BayerPattern::Other(4));
+    }
+
+    #[test]
+    fn bayer_pattern_names() {
+        assert_eq!(BayerPattern::Rg.name(), Some("RGGB"));
+        assert_eq!(BayerPattern::Gb.name(), Some("GBRG"));
+        assert_eq!(BayerPattern::Other(7).name(), None);
     }
 
     #[test]
     fn control_type_round_trip() {
         for val in 0..=19 {
-            let ct = ControlType::try_from(val as c_int).unwrap();
-            assert_eq!(ct as c_int, val);
+            let ct = ControlType::from(val as c_int);
+            assert!(!matches!(ct, ControlType::Other(_)));
+            assert_eq!(ct.raw(), val);
         }
-        assert!(ControlType::try_from(20).is_err());
+        // Controls added by newer SDKs are preserved, not rejected.
+        assert_eq!(ControlType::from(20), ControlType::Other(20));
+        assert_eq!(ControlType::Other(20).raw(), 20);
     }
 
     #[test]
     fn camera_mode_round_trip() {
         for val in 0..=6 {
-            let m = CameraMode::try_from(val as c_int).unwrap();
-            assert_eq!(m as c_int, val);
+            let m = CameraMode::from(val as c_int);
+            assert!(!matches!(m, CameraMode::Other(_)));
+            assert_eq!(m.raw(), val);
         }
-        assert!(CameraMode::try_from(-1 as c_int).is_err());
-        assert!(CameraMode::try_from(7).is_err());
+        assert_eq!(CameraMode::from(-1 as c_int), CameraMode::Other(-1));
+        assert_eq!(CameraMode::from(7), CameraMode::Other(7));
+    }
+
+    #[test]
+    fn bin_mode_round_trip() {
+        assert_eq!(BinMode::from(0), BinMode::Average);
+        assert_eq!(BinMode::from(1), BinMode::Sum);
+        assert_eq!(BinMode::Sum.raw(), 1);
+        assert_eq!(BinMode::from(2), BinMode::Other(2));
     }
 
     #[test]
     fn flip_status_round_trip() {
         for val in 0..=3 {
-            let f = FlipStatus::try_from(val as c_int).unwrap();
-            assert_eq!(f as c_int, val);
+            let f = FlipStatus::from(val as c_int);
+            assert!(!matches!(f, FlipStatus::Other(_)));
+            assert_eq!(f.raw(), val);
         }
     }
 
     #[test]
     fn guide_direction_round_trip() {
         for val in 0..=3 {
-            let g = GuideDirection::try_from(val as c_int).unwrap();
-            assert_eq!(g as c_int, val);
+            let g = GuideDirection::from(val as c_int);
+            assert!(!matches!(g, GuideDirection::Other(_)));
+            assert_eq!(g.raw(), val);
         }
     }
 
     #[test]
     fn trig_output_pin_round_trip() {
-        assert_eq!(TrigOutputPin::try_from(0).unwrap(), TrigOutputPin::PinA);
-        assert_eq!(TrigOutputPin::try_from(1).unwrap(), TrigOutputPin::PinB);
-        assert!(TrigOutputPin::try_from(2).is_err());
+        assert_eq!(TrigOutputPin::from(0), TrigOutputPin::PinA);
+        assert_eq!(TrigOutputPin::from(1), TrigOutputPin::PinB);
+        assert_eq!(TrigOutputPin::from(-1), TrigOutputPin::None);
+        assert_eq!(TrigOutputPin::None.raw(), svbony_sys::SVB_TRIG_OUTPUT_NONE);
+        assert_eq!(TrigOutputPin::from(2), TrigOutputPin::Other(2));
     }
 
     #[test]
     fn exposure_status_round_trip() {
         for val in 0..=3 {
-            let e = ExposureStatus::try_from(val as c_int).unwrap();
-            assert_eq!(e as c_int, val);
+            let e = ExposureStatus::from(val as c_int);
+            assert!(!matches!(e, ExposureStatus::Other(_)));
+            assert_eq!(e.raw(), val);
         }
     }
 
@@ -723,7 +920,8 @@ mod tests {
         c.SupportedBins[2] = 0; // terminator
         c.SupportedVideoFormat[0] = svbony_sys::SVB_IMG_RAW8;
         c.SupportedVideoFormat[1] = svbony_sys::SVB_IMG_RAW16;
-        c.SupportedVideoFormat[2] = svbony_sys::SVB_IMG_END; // terminator
+        c.SupportedVideoFormat[2] = 42; // unknown to this crate
+        c.SupportedVideoFormat[3] = svbony_sys::SVB_IMG_END; // terminator
         c.MaxBitDepth = 12;
         c.IsTriggerCam = 0;
 
@@ -731,11 +929,17 @@ mod tests {
         assert_eq!(prop.max_width, 1920);
         assert_eq!(prop.max_height, 1080);
         assert!(prop.is_color);
-        assert_eq!(prop.bayer_pattern, BayerPattern::Gr);
+        assert_eq!(prop.bayer_pattern, Some(BayerPattern::Gr));
         assert_eq!(prop.supported_bins, vec![1, 2]);
-        assert_eq!(prop.supported_formats, vec![ImageType::Raw8, ImageType::Raw16]);
+        assert_eq!(
+            prop.supported_formats,
+            vec![ImageType::Raw8, ImageType::Raw16, ImageType::Other(42)]
+        );
         assert_eq!(prop.max_bit_depth, 12);
         assert!(!prop.is_trigger_cam);
+
+        c.IsColorCam = 0;
+        assert_eq!(CameraProperty::from(&c).bayer_pattern, None);
     }
 
     #[test]
@@ -749,6 +953,52 @@ mod tests {
         assert!(!prop.supports_temp_control);
     }
 
+    fn gain_caps() -> ControlCaps {
+        ControlCaps {
+            name: "Gain".into(),
+            description: String::new(),
+            max_value: 300,
+            min_value: 0,
+            default_value: 10,
+            is_auto_supported: false,
+            is_writable: true,
+            control_type: ControlType::Gain,
+        }
+    }
+
+    #[test]
+    fn control_caps_validate() {
+        let mut caps = gain_caps();
+        assert!(caps.validate(0, false).is_ok());
+        assert!(caps.validate(300, false).is_ok());
+        assert_eq!(
+            caps.validate(301, false),
+            Err(Error::OutOfRange {
+                control: ControlType::Gain,
+                value: 301,
+                min: 0,
+                max: 300
+            })
+        );
+        assert_eq!(
+            caps.validate(10, true),
+            Err(Error::AutoNotSupported(ControlType::Gain))
+        );
+        caps.is_writable = false;
+        assert_eq!(
+            caps.validate(10, false),
+            Err(Error::ReadOnlyControl(ControlType::Gain))
+        );
+    }
+
+    #[test]
+    fn control_caps_clamp() {
+        let caps = gain_caps();
+        assert_eq!(caps.clamp(-5), 0);
+        assert_eq!(caps.clamp(150), 150);
+        assert_eq!(caps.clamp(1000), 300);
+    }
+
     #[test]
     fn control_caps_from_c() {
         let mut c = unsafe { std::mem::zeroed::<svbony_sys::SVB_CONTROL_CAPS>() };
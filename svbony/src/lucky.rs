@@ -0,0 +1,814 @@
+//! Lucky imaging: frame quality scoring and best-frame selection.
+//!
+//! Planetary and lunar imaging captures thousands of short exposures and
+//! keeps only the few taken in moments of good seeing. A [`Scorer`] rates the
+//! sharpness of a frame (optionally inside a region around the target), a
+//! [`LuckySelector`] keeps the best frames of a stream in memory or on disk,
+//! and [`LuckyCapture`] drives both from the camera's video capture loop.
+//!
+//! ```no_run
+//! use svbony::Camera;
+//! use svbony::lucky::{Keep, LuckyCapture, LuckyConfig, QualityMetric, Region};
+//!
+//! let cam = Camera::open(0)?;
+//! let mut config = LuckyConfig::default();
+//! config.frame_count = 5000;
+//! config.keep = Keep::BestFraction(0.05);
+//! config.scorer.metric = QualityMetric::LaplacianVariance;
+//! config.scorer.region = Some(Region { x: 200, y: 150, width: 240, height: 180 });
+//!
+//! let selection = LuckyCapture::new(&cam, config).run(|scored| {
+//!     if scored.index % 500 == 0 {
+//!         println!("frame {}: {:.3e}", scored.index, scored.score);
+//!     }
+//! })?;
+//! for kept in &selection.kept {
+//!     println!("#{} score {:.3e}", kept.index, kept.score);
+//! }
+//! for (i, count) in selection.histogram.counts.iter().enumerate() {
+//!     let (lo, hi) = selection.histogram.bin_range(i);
+//!     println!("{lo:.3e}..{hi:.3e}: {count}");
+//! }
+//! # Ok::<(), svbony::lucky::LuckyError>(())
+//! ```
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::{Camera, Error, Frame, ImageType, Result, RoiFormat};
+
+/// Side of the square blocks used by [`QualityMetric::LocalContrast`].
+const CONTRAST_BLOCK: usize = 8;
+
+/// Sharpness measure used to rank frames.
+///
+/// All metrics are computed on the luminance of the scored region, normalised
+/// to `0.0..=1.0` of the sample container, and grow with image sharpness.
+/// Scores are only comparable between frames scored with the same settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QualityMetric {
+    /// Variance of the 4-neighbour Laplacian. Sensitive to fine detail and
+    /// the usual choice for planetary surfaces.
+    LaplacianVariance,
+    /// Mean squared Sobel gradient magnitude (Tenengrad). Less sensitive to
+    /// noise than the Laplacian; works well on lunar and solar limbs.
+    GradientEnergy,
+    /// Mean standard deviation over 8×8 blocks divided by the mean level.
+    /// Robust to transparency changes that alter overall brightness.
+    LocalContrast,
+}
+
+/// Rectangle within a frame, in frame pixels (relative to the ROI origin).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    /// Left edge.
+    pub x: usize,
+    /// Top edge.
+    pub y: usize,
+    /// Width in pixels.
+    pub width: usize,
+    /// Height in pixels.
+    pub height: usize,
+}
+
+/// Computes a [`QualityMetric`] for frames.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scorer {
+    /// Metric to compute.
+    pub metric: QualityMetric,
+    /// Area to score; `None` scores the whole frame.
+    pub region: Option<Region>,
+    /// Average 2×2 blocks before scoring. Enable for raw output of colour
+    /// cameras so the Bayer pattern is not mistaken for detail.
+    pub superpixel: bool,
+}
+
+impl Default for Scorer {
+    fn default() -> Self {
+        Self {
+            metric: QualityMetric::LaplacianVariance,
+            region: None,
+            superpixel: false,
+        }
+    }
+}
+
+impl Scorer {
+    /// Creates a scorer for `metric` over the whole frame.
+    pub fn new(metric: QualityMetric) -> Self {
+        Self {
+            metric,
+            ..Self::default()
+        }
+    }
+
+    /// Scores `frame`; higher is sharper.
+    ///
+    /// Returns [`Error::InvalidArgument`] if the region does not fit inside
+    /// the frame or is smaller than 3×3 pixels after superpixel binning.
+    pub fn score(&self, frame: &Frame) -> Result<f64> {
+        let (plane, w, h) = self.luminance(frame)?;
+        Ok(match self.metric {
+            QualityMetric::LaplacianVariance => laplacian_variance(&plane, w, h),
+            QualityMetric::GradientEnergy => gradient_energy(&plane, w, h),
+            QualityMetric::LocalContrast => local_contrast(&plane, w, h),
+        })
+    }
+
+    /// Extracts the normalised luminance of the scored region.
+    fn luminance(&self, frame: &Frame) -> Result<(Vec<f64>, usize, usize)> {
+        let r = self.region.unwrap_or(Region {
+            x: 0,
+            y: 0,
+            width: frame.width(),
+            height: frame.height(),
+        });
+        if r.x + r.width > frame.width() || r.y + r.height > frame.height() {
+            return Err(Error::InvalidArgument(format!(
+                "region {}x{} at ({}, {}) exceeds the {}x{} frame",
+                r.width,
+                r.height,
+                r.x,
+                r.y,
+                frame.width(),
+                frame.height()
+            )));
+        }
+        // Alpha carries no image information.
+        let channels = match frame.image_type {
+            ImageType::Rgb32 => 3,
+            _ => frame.channels(),
+        };
+        let full_scale = if frame.image_type.bytes_per_pixel() == 2 {
+            65535.0
+        } else {
+            255.0
+        };
+        let norm = 1.0 / (channels as f64 * full_scale);
+        let lum = |x: usize, y: usize| -> f64 {
+            (0..channels)
+                .map(|c| frame.sample(r.x + x, r.y + y, c) as f64)
+                .sum::<f64>()
+                * norm
+        };
+
+        let (w, h) = if self.superpixel {
+            (r.width / 2, r.height / 2)
+        } else {
+            (r.width, r.height)
+        };
+        if w < 3 || h < 3 {
+            return Err(Error::InvalidArgument(
+                "scoring region must be at least 3x3 pixels".into(),
+            ));
+        }
+        let mut plane = Vec::with_capacity(w * h);
+        for y in 0..h {
+            for x in 0..w {
+                plane.push(if self.superpixel {
+                    let (sx, sy) = (2 * x, 2 * y);
+                    (lum(sx, sy) + lum(sx + 1, sy) + lum(sx, sy + 1) + lum(sx + 1, sy + 1)) / 4.0
+                } else {
+                    lum(x, y)
+                });
+            }
+        }
+        Ok((plane, w, h))
+    }
+}
+
+fn laplacian_variance(p: &[f64], w: usize, h: usize) -> f64 {
+    let mut sum = 0.0;
+    let mut sum_sq = 0.0;
+    for y in 1..h - 1 {
+        for x in 1..w - 1 {
+            let i = y * w + x;
+            let l = 4.0 * p[i] - p[i - 1] - p[i + 1] - p[i - w] - p[i + w];
+            sum += l;
+            sum_sq += l * l;
+        }
+    }
+    let n = ((w - 2) * (h - 2)) as f64;
+    let mean = sum / n;
+    (sum_sq / n - mean * mean).max(0.0)
+}
+
+fn gradient_energy(p: &[f64], w: usize, h: usize) -> f64 {
+    let mut sum = 0.0;
+    for y in 1..h - 1 {
+        for x in 1..w - 1 {
+            let at = |dx: isize, dy: isize| {
+                p[(y as isize + dy) as usize * w + (x as isize + dx) as usize]
+            };
+            let gx =
+                at(1, -1) + 2.0 * at(1, 0) + at(1, 1) - at(-1, -1) - 2.0 * at(-1, 0) - at(-1, 1);
+            let gy =
+                at(-1, 1) + 2.0 * at(0, 1) + at(1, 1) - at(-1, -1) - 2.0 * at(0, -1) - at(1, -1);
+            sum += gx * gx + gy * gy;
+        }
+    }
+    sum / ((w - 2) * (h - 2)) as f64
+}
+
+fn local_contrast(p: &[f64], w: usize, h: usize) -> f64 {
+    let mean = p.iter().sum::<f64>() / p.len() as f64;
+    if mean <= 0.0 {
+        return 0.0;
+    }
+    // Regions smaller than one block are treated as a single block.
+    let bw = CONTRAST_BLOCK.min(w);
+    let bh = CONTRAST_BLOCK.min(h);
+    let mut total = 0.0;
+    let mut blocks = 0usize;
+    for by in (0..=h - bh).step_by(bh) {
+        for bx in (0..=w - bw).step_by(bw) {
+            let rows = || (by..by + bh).flat_map(|y| &p[y * w + bx..y * w + bx + bw]);
+            let n = (bw * bh) as f64;
+            let m = rows().sum::<f64>() / n;
+            total += (rows().map(|v| (v - m) * (v - m)).sum::<f64>() / n).sqrt();
+            blocks += 1;
+        }
+    }
+    total / blocks as f64 / mean
+}
+
+/// How many frames a [`LuckySelector`] keeps.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Keep {
+    /// The `n` best frames.
+    Best(usize),
+    /// The best fraction of the run (e.g. `0.1` = top 10 %), rounded up to at
+    /// least one frame.
+    BestFraction(f64),
+}
+
+impl Keep {
+    /// Number of frames to keep out of `total`, never more than `total`.
+    ///
+    /// Returns [`Error::InvalidArgument`] for a zero count or a fraction
+    /// outside `(0, 1]`.
+    pub fn count(self, total: usize) -> Result<usize> {
+        match self {
+            Keep::Best(0) => Err(Error::InvalidArgument(
+                "must keep at least one frame".into(),
+            )),
+            Keep::Best(n) => Ok(n.min(total).max(1)),
+            Keep::BestFraction(f) if f > 0.0 && f <= 1.0 => {
+                Ok(((total as f64 * f).ceil() as usize).max(1))
+            }
+            Keep::BestFraction(f) => Err(Error::InvalidArgument(format!(
+                "keep fraction {f} is outside (0, 1]"
+            ))),
+        }
+    }
+}
+
+/// Where kept frames are held until the run finishes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Storage {
+    /// Keep frame buffers in memory.
+    Memory,
+    /// Write each candidate to `frame_NNNNNN.raw` in this directory and
+    /// delete it again when a better frame displaces it. The directory is
+    /// created if needed.
+    Disk(PathBuf),
+}
+
+/// Errors from lucky-imaging runs, which may fail in the camera or while
+/// writing frames to disk.
+#[derive(Debug, thiserror::Error)]
+pub enum LuckyError {
+    /// The camera failed to deliver a frame.
+    #[error(transparent)]
+    Camera(#[from] Error),
+    /// A frame could not be written to or removed from [`Storage::Disk`].
+    #[error("frame storage: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// A frame retained by a [`LuckySelector`].
+#[derive(Debug, Clone)]
+pub enum StoredFrame {
+    /// The frame itself, held in memory.
+    Memory(Frame),
+    /// Raw sample bytes in a file, as written by [`Storage::Disk`].
+    Disk {
+        /// File holding the sample bytes.
+        path: PathBuf,
+        /// ROI the frame was captured with.
+        roi: RoiFormat,
+        /// Sample format of the bytes.
+        image_type: ImageType,
+    },
+}
+
+impl StoredFrame {
+    /// Returns the frame, reading it back from disk if necessary.
+    pub fn load(&self) -> io::Result<Frame> {
+        match self {
+            StoredFrame::Memory(frame) => Ok(frame.clone()),
+            StoredFrame::Disk {
+                path,
+                roi,
+                image_type,
+            } => Frame::new(*roi, *image_type, fs::read(path)?).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{}: {e}", path.display()),
+                )
+            }),
+        }
+    }
+}
+
+/// A kept frame with its position in the stream and its score.
+#[derive(Debug, Clone)]
+pub struct KeptFrame {
+    /// Zero-based index of the frame in the stream.
+    pub index: usize,
+    /// Quality score; higher is sharper.
+    pub score: f64,
+    /// The frame, in memory or on disk.
+    pub frame: StoredFrame,
+}
+
+impl PartialEq for KeptFrame {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for KeptFrame {}
+
+impl PartialOrd for KeptFrame {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Orders by score; among equal scores the earlier frame ranks higher.
+impl Ord for KeptFrame {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.score
+            .total_cmp(&other.score)
+            .then(other.index.cmp(&self.index))
+    }
+}
+
+/// Result of offering one frame to a [`LuckySelector`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Scored {
+    /// Zero-based index of the frame in the stream.
+    pub index: usize,
+    /// Quality score; higher is sharper.
+    pub score: f64,
+    /// Whether the frame is currently among the best (it may still be
+    /// displaced by later frames).
+    pub kept: bool,
+}
+
+/// Histogram of frame scores.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QualityHistogram {
+    /// Lowest score (left edge of the first bin).
+    pub min: f64,
+    /// Highest score (right edge of the last bin).
+    pub max: f64,
+    /// Number of frames per equal-width bin.
+    pub counts: Vec<usize>,
+}
+
+impl QualityHistogram {
+    /// Bins `scores` into `bins` equal-width bins spanning their range.
+    pub fn from_scores(scores: &[f64], bins: usize) -> Self {
+        let bins = bins.max(1);
+        let mut counts = vec![0; bins];
+        if scores.is_empty() {
+            return Self {
+                min: 0.0,
+                max: 0.0,
+                counts,
+            };
+        }
+        let min = scores.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = scores.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let width = (max - min) / bins as f64;
+        for &s in scores {
+            let bin = if width > 0.0 {
+                (((s - min) / width) as usize).min(bins - 1)
+            } else {
+                0
+            };
+            counts[bin] += 1;
+        }
+        Self { min, max, counts }
+    }
+
+    /// Score range `(low, high)` covered by bin `i`.
+    pub fn bin_range(&self, i: usize) -> (f64, f64) {
+        let width = (self.max - self.min) / self.counts.len() as f64;
+        (
+            self.min + width * i as f64,
+            self.min + width * (i + 1) as f64,
+        )
+    }
+
+    /// Total number of frames counted.
+    pub fn total(&self) -> usize {
+        self.counts.iter().sum()
+    }
+}
+
+/// Outcome of a lucky-imaging run.
+#[derive(Debug, Clone)]
+pub struct Selection {
+    /// Kept frames, best first.
+    pub kept: Vec<KeptFrame>,
+    /// Score of every frame, in stream order.
+    pub scores: Vec<f64>,
+    /// Distribution of [`scores`](Self::scores).
+    pub histogram: QualityHistogram,
+}
+
+/// Scores a stream of frames and retains the best `capacity` of them.
+///
+/// Frames can come from any source; [`LuckyCapture`] feeds one from the
+/// camera. Only the current best frames are held, so memory (or disk) use is
+/// bounded by `capacity` regardless of the stream length.
+#[derive(Debug)]
+pub struct LuckySelector {
+    scorer: Scorer,
+    capacity: usize,
+    storage: Storage,
+    /// Min-heap on score: the worst kept frame is on top.
+    best: BinaryHeap<Reverse<KeptFrame>>,
+    scores: Vec<f64>,
+}
+
+impl LuckySelector {
+    /// Creates a selector keeping `capacity` frames.
+    ///
+    /// Creates the storage directory for [`Storage::Disk`].
+    pub fn new(
+        scorer: Scorer,
+        capacity: usize,
+        storage: Storage,
+    ) -> std::result::Result<Self, LuckyError> {
+        if capacity == 0 {
+            return Err(Error::InvalidArgument("must keep at least one frame".into()).into());
+        }
+        if let Storage::Disk(dir) = &storage {
+            fs::create_dir_all(dir)?;
+        }
+        Ok(Self {
+            scorer,
+            capacity,
+            storage,
+            best: BinaryHeap::new(),
+            scores: Vec::new(),
+        })
+    }
+
+    /// Number of frames offered so far.
+    pub fn frames_seen(&self) -> usize {
+        self.scores.len()
+    }
+
+    /// Score of every frame offered so far, in stream order.
+    pub fn scores(&self) -> &[f64] {
+        &self.scores
+    }
+
+    /// Lowest score that is currently kept, once the selector is full.
+    pub fn threshold(&self) -> Option<f64> {
+        if self.best.len() < self.capacity {
+            None
+        } else {
+            self.best.peek().map(|Reverse(k)| k.score)
+        }
+    }
+
+    /// Histogram of the scores seen so far, for live reporting.
+    pub fn histogram(&self, bins: usize) -> QualityHistogram {
+        QualityHistogram::from_scores(&self.scores, bins)
+    }
+
+    /// Scores `frame` and keeps a copy if it ranks among the best so far,
+    /// evicting the previous worst kept frame.
+    pub fn push(&mut self, frame: &Frame) -> std::result::Result<Scored, LuckyError> {
+        let index = self.scores.len();
+        let score = self.scorer.score(frame)?;
+        self.scores.push(score);
+
+        let kept = match self.best.peek() {
+            _ if self.best.len() < self.capacity => true,
+            Some(Reverse(worst)) => {
+                // A new frame must beat the worst kept one; ties keep the
+                // earlier frame.
+                score.total_cmp(&worst.score) == Ordering::Greater
+            }
+            None => true,
+        };
+        if kept {
+            let stored = match &self.storage {
+                Storage::Memory => StoredFrame::Memory(frame.clone()),
+                Storage::Disk(dir) => {
+                    let path = dir.join(format!("frame_{index:06}.raw"));
+                    fs::write(&path, &frame.data)?;
+                    StoredFrame::Disk {
+                        path,
+                        roi: frame.roi,
+                        image_type: frame.image_type,
+                    }
+                }
+            };
+            self.best.push(Reverse(KeptFrame {
+                index,
+                score,
+                frame: stored,
+            }));
+            if self.best.len() > self.capacity {
+                if let Some(Reverse(evicted)) = self.best.pop() {
+                    if let StoredFrame::Disk { path, .. } = evicted.frame {
+                        fs::remove_file(path)?;
+                    }
+                }
+            }
+        }
+        Ok(Scored { index, score, kept })
+    }
+
+    /// Finishes the run, returning the kept frames best first.
+    pub fn finish(self, histogram_bins: usize) -> Selection {
+        let histogram = QualityHistogram::from_scores(&self.scores, histogram_bins);
+        // Ascending order of `Reverse` is descending score.
+        let kept = self
+            .best
+            .into_sorted_vec()
+            .into_iter()
+            .map(|Reverse(k)| k)
+            .collect();
+        Selection {
+            kept,
+            scores: self.scores,
+            histogram,
+        }
+    }
+}
+
+/// Settings for [`LuckyCapture`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct LuckyConfig {
+    /// How frames are scored.
+    pub scorer: Scorer,
+    /// Number of frames to capture.
+    pub frame_count: usize,
+    /// How many of the captured frames to keep.
+    pub keep: Keep,
+    /// Where kept frames are held during the run.
+    pub storage: Storage,
+    /// Number of bins in the reported quality histogram.
+    pub histogram_bins: usize,
+}
+
+impl Default for LuckyConfig {
+    fn default() -> Self {
+        Self {
+            scorer: Scorer::default(),
+            frame_count: 1000,
+            keep: Keep::BestFraction(0.1),
+            storage: Storage::Memory,
+            histogram_bins: 32,
+        }
+    }
+}
+
+/// Captures a video stream and keeps its sharpest frames.
+///
+/// The camera should already be configured (ROI, output type, a short
+/// exposure, high-speed mode) before calling [`run`](Self::run).
+pub struct LuckyCapture<'a> {
+    camera: &'a Camera,
+    config: LuckyConfig,
+}
+
+impl<'a> LuckyCapture<'a> {
+    /// Creates a run on `camera`; nothing is captured until
+    /// [`run`](Self::run).
+    pub fn new(camera: &'a Camera, config: LuckyConfig) -> Self {
+        Self { camera, config }
+    }
+
+    /// Returns the run's settings.
+    pub fn config(&self) -> &LuckyConfig {
+        &self.config
+    }
+
+    /// Starts video capture, scores `frame_count` frames and stops capture.
+    ///
+    /// `on_frame` is called after each frame is scored, e.g. to update a
+    /// progress display. Capture is stopped even if the run fails.
+    pub fn run(
+        &self,
+        mut on_frame: impl FnMut(&Scored),
+    ) -> std::result::Result<Selection, LuckyError> {
+        let capacity = self.config.keep.count(self.config.frame_count)?;
+        let mut selector = LuckySelector::new(
+            self.config.scorer.clone(),
+            capacity,
+            self.config.storage.clone(),
+        )?;
+        let roi = self.camera.roi()?;
+        let image_type = self.camera.output_image_type()?;
+        let wait_ms = (self.camera.exposure()?.as_millis() * 2 + 500) as i32;
+        let mut frame = Frame::zeroed(roi, image_type);
+
+        self.camera.start_capture()?;
+        let result = (|| -> std::result::Result<(), LuckyError> {
+            for _ in 0..self.config.frame_count {
+                self.camera.get_frame(&mut frame.data, wait_ms)?;
+                on_frame(&selector.push(&frame)?);
+            }
+            Ok(())
+        })();
+        let stopped = self.camera.stop_capture();
+        result?;
+        stopped?;
+        Ok(selector.finish(self.config.histogram_bins))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roi(w: i32, h: i32) -> RoiFormat {
+        RoiFormat {
+            start_x: 0,
+            start_y: 0,
+            width: w,
+            height: h,
+            bin: 1,
+        }
+    }
+
+    /// 32×32 Y8 frame of 4-pixel checks, blurred `blur` times by a 3×3 box.
+    fn checks(blur: usize) -> Frame {
+        let n = 32;
+        let mut v: Vec<f64> = (0..n * n)
+            .map(|i| {
+                let (x, y) = (i % n, i / n);
+                if (x / 4 + y / 4) % 2 == 0 {
+                    40.0
+                } else {
+                    200.0
+                }
+            })
+            .collect();
+        for _ in 0..blur {
+            let src = v.clone();
+            for y in 0..n {
+                for x in 0..n {
+                    let mut sum = 0.0;
+                    for dy in -1isize..=1 {
+                        for dx in -1isize..=1 {
+                            let xx = (x as isize + dx).clamp(0, n as isize - 1) as usize;
+                            let yy = (y as isize + dy).clamp(0, n as isize - 1) as usize;
+                            sum += src[yy * n + xx];
+                        }
+                    }
+                    v[y * n + x] = sum / 9.0;
+                }
+            }
+        }
+        let data = v.iter().map(|&p| p.round() as u8).collect();
+        Frame::new(roi(n as i32, n as i32), ImageType::Y8, data).unwrap()
+    }
+
+    #[test]
+    fn metrics_prefer_sharp_frames() {
+        let sharp = checks(0);
+        let soft = checks(1);
+        let softer = checks(3);
+        for metric in [
+            QualityMetric::LaplacianVariance,
+            QualityMetric::GradientEnergy,
+            QualityMetric::LocalContrast,
+        ] {
+            let s = Scorer::new(metric);
+            let a = s.score(&sharp).unwrap();
+            let b = s.score(&soft).unwrap();
+            let c = s.score(&softer).unwrap();
+            assert!(a > b && b > c, "{metric:?}: {a} {b} {c}");
+        }
+    }
+
+    #[test]
+    fn flat_frame_scores_zero() {
+        let f = Frame::new(roi(8, 8), ImageType::Y8, vec![100; 64]).unwrap();
+        for metric in [
+            QualityMetric::LaplacianVariance,
+            QualityMetric::GradientEnergy,
+            QualityMetric::LocalContrast,
+        ] {
+            assert!(Scorer::new(metric).score(&f).unwrap() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn region_is_validated_and_used() {
+        let f = checks(0);
+        let mut s = Scorer {
+            region: Some(Region {
+                x: 20,
+                y: 20,
+                width: 16,
+                height: 4,
+            }),
+            ..Scorer::default()
+        };
+        assert!(matches!(s.score(&f), Err(Error::InvalidArgument(_))));
+
+        // A region inside one uniform check has no detail.
+        s.region = Some(Region {
+            x: 0,
+            y: 0,
+            width: 4,
+            height: 4,
+        });
+        assert!(s.score(&f).unwrap() < 1e-12);
+
+        s.superpixel = true;
+        assert!(matches!(s.score(&f), Err(Error::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn keep_count_resolves_fraction() {
+        assert_eq!(Keep::BestFraction(0.1).count(1000).unwrap(), 100);
+        assert_eq!(Keep::BestFraction(0.1).count(5).unwrap(), 1);
+        assert_eq!(Keep::Best(7).count(3).unwrap(), 3);
+        assert_eq!(Keep::Best(usize::MAX).count(10).unwrap(), 10);
+        assert!(LuckySelector::new(Scorer::default(), usize::MAX, Storage::Memory).is_ok());
+        assert!(Keep::BestFraction(0.0).count(10).is_err());
+        assert!(Keep::BestFraction(1.5).count(10).is_err());
+        assert!(Keep::Best(0).count(10).is_err());
+    }
+
+    #[test]
+    fn selector_keeps_best_frames() {
+        let frames = [
+            checks(2),
+            checks(0),
+            checks(4),
+            checks(1),
+            checks(0),
+            checks(0),
+        ];
+        let mut sel = LuckySelector::new(Scorer::default(), 2, Storage::Memory).unwrap();
+        let kept: Vec<bool> = frames.iter().map(|f| sel.push(f).unwrap().kept).collect();
+        // The third sharp frame ties with the worst kept one and does not
+        // displace it.
+        assert_eq!(kept, [true, true, false, true, true, false]);
+        assert_eq!(sel.threshold(), Some(sel.scores()[1]));
+
+        let out = sel.finish(4);
+        let order: Vec<usize> = out.kept.iter().map(|k| k.index).collect();
+        assert_eq!(order, [1, 4]);
+        assert_eq!(out.scores.len(), 6);
+        assert_eq!(out.histogram.total(), 6);
+        assert_eq!(out.histogram.counts[3], 3);
+        match &out.kept[0].frame {
+            StoredFrame::Memory(f) => assert_eq!(f.data, frames[1].data),
+            other => panic!("unexpected storage {other:?}"),
+        }
+    }
+
+    #[test]
+    fn disk_storage_evicts_files() {
+        let dir = std::env::temp_dir().join(format!("svbony-lucky-{}", std::process::id()));
+        let mut sel = LuckySelector::new(Scorer::default(), 1, Storage::Disk(dir.clone())).unwrap();
+        sel.push(&checks(3)).unwrap();
+        assert!(dir.join("frame_000000.raw").exists());
+        sel.push(&checks(0)).unwrap();
+        assert!(!dir.join("frame_000000.raw").exists());
+
+        let out = sel.finish(8);
+        let frame = out.kept[0].frame.load().unwrap();
+        assert_eq!(frame.data, checks(0).data);
+        assert_eq!(frame.image_type, ImageType::Y8);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn histogram_bins() {
+        let h = QualityHistogram::from_scores(&[0.0, 1.0, 2.0, 3.0, 4.0], 4);
+        assert_eq!(h.counts, [1, 1, 1, 2]);
+        assert_eq!(h.bin_range(1), (1.0, 2.0));
+        let flat = QualityHistogram::from_scores(&[2.0; 3], 4);
+        assert_eq!(flat.counts, [3, 0, 0, 0]);
+        assert_eq!(QualityHistogram::from_scores(&[], 4).total(), 0);
+    }
+}
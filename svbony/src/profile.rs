@@ -0,0 +1,298 @@
+//! Named configuration profiles stored per camera (feature = "profiles").
+//!
+//! A profile is a [`CameraSettings`] snapshot saved as JSON under
+//!
+//! ```text
+//! <root>/<model>/<serial>/<name>.json
+//! ```
+//!
+//! where `<root>` defaults to `<user config dir>/svbony/profiles` (e.g.
+//! `~/.config/svbony/profiles` on Linux) and model and serial come from the
+//! camera's [`CameraInfo`]. Each camera may have a default profile, which
+//! [`Camera::open`] applies automatically.
+//!
+//! ```no_run
+//! use svbony::Camera;
+//! use svbony::profile::ProfileStore;
+//!
+//! let cam = Camera::open(0)?;
+//! let info = cam.info()?;
+//! let store = ProfileStore::user_default().expect("no config directory");
+//!
+//! store.save(&info, "planetary-fast", &cam.snapshot_settings()?)?;
+//! store.set_default(&info, Some("planetary-fast"))?;
+//! for name in store.list(&info)? {
+//!     println!("{name}");
+//! }
+//! cam.apply_settings(&store.load(&info, "planetary-fast")?)?;
+//! # Ok::<(), svbony::Error>(())
+//! ```
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::{Camera, CameraInfo, CameraSettings, Error, Result};
+
+/// Marker file holding the name of a camera's default profile.
+const DEFAULT_MARKER: &str = ".default";
+
+/// Directory of saved profiles.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileStore {
+    root: PathBuf,
+}
+
+impl ProfileStore {
+    /// Creates a store rooted at `root`.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Returns the store in the user's config directory, or `None` if the
+    /// platform has none.
+    pub fn user_default() -> Option<Self> {
+        dirs::config_dir().map(|dir| Self::new(dir.join("svbony").join("profiles")))
+    }
+
+    /// Directory holding every camera's profiles.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Directory holding the profiles of the camera described by `info`.
+    pub fn camera_dir(&self, info: &CameraInfo) -> PathBuf {
+        self.root
+            .join(path_component(&info.name))
+            .join(path_component(&info.serial))
+    }
+
+    /// Saves `settings` as profile `name`, replacing any existing one.
+    pub fn save(&self, info: &CameraInfo, name: &str, settings: &CameraSettings) -> Result<()> {
+        let path = self.profile_path(info, name)?;
+        let json = serde_json::to_string_pretty(settings)
+            .map_err(|e| Error::Profile(format!("cannot encode profile {name:?}: {e}")))?;
+        fs::create_dir_all(self.camera_dir(info)).map_err(|e| io_error(&path, e))?;
+        fs::write(&path, json).map_err(|e| io_error(&path, e))
+    }
+
+    /// Loads profile `name`.
+    pub fn load(&self, info: &CameraInfo, name: &str) -> Result<CameraSettings> {
+        let path = self.profile_path(info, name)?;
+        let json = fs::read_to_string(&path).map_err(|e| not_found(name, &path, e))?;
+        serde_json::from_str(&json).map_err(|e| Error::Profile(format!("{}: {e}", path.display())))
+    }
+
+    /// Lists the profile names saved for a camera, sorted.
+    pub fn list(&self, info: &CameraInfo) -> Result<Vec<String>> {
+        let dir = self.camera_dir(info);
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(io_error(&dir, e)),
+        };
+        let mut names = Vec::new();
+        for entry in entries {
+            let path = entry.map_err(|e| io_error(&dir, e))?.path();
+            if path.extension().map_or(false, |ext| ext == "json") {
+                if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+                    names.push(stem.to_owned());
+                }
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// Deletes profile `name`, clearing the default if it pointed there.
+    pub fn delete(&self, info: &CameraInfo, name: &str) -> Result<()> {
+        let path = self.profile_path(info, name)?;
+        fs::remove_file(&path).map_err(|e| not_found(name, &path, e))?;
+        if self.default_profile(info)?.as_deref() == Some(name) {
+            self.set_default(info, None)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the name of the camera's default profile, if one is set.
+    pub fn default_profile(&self, info: &CameraInfo) -> Result<Option<String>> {
+        let path = self.camera_dir(info).join(DEFAULT_MARKER);
+        match fs::read_to_string(&path) {
+            Ok(name) if !name.trim().is_empty() => Ok(Some(name.trim().to_owned())),
+            Ok(_) => Ok(None),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(io_error(&path, e)),
+        }
+    }
+
+    /// Sets (or with `None`, clears) the profile [`Camera::open`] applies.
+    /// The profile must already exist.
+    pub fn set_default(&self, info: &CameraInfo, name: Option<&str>) -> Result<()> {
+        let marker = self.camera_dir(info).join(DEFAULT_MARKER);
+        match name {
+            Some(name) => {
+                let path = self.profile_path(info, name)?;
+                if !path.is_file() {
+                    return Err(Error::ProfileNotFound(name.to_owned()));
+                }
+                fs::write(&marker, name).map_err(|e| io_error(&marker, e))
+            }
+            None => match fs::remove_file(&marker) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(io_error(&marker, e)),
+                _ => Ok(()),
+            },
+        }
+    }
+
+    fn profile_path(&self, info: &CameraInfo, name: &str) -> Result<PathBuf> {
+        validate_name(name)?;
+        Ok(self.camera_dir(info).join(format!("{name}.json")))
+    }
+}
+
+/// Accepts names made of ASCII letters, digits, `-`, `_`, `.` and spaces
+/// that do not start with a dot, so a name is always a single file name.
+fn validate_name(name: &str) -> Result<()> {
+    let ok = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ' '));
+    if ok {
+        Ok(())
+    } else {
+        Err(Error::InvalidArgument(format!(
+            "invalid profile name {name:?}"
+        )))
+    }
+}
+
+/// Maps a model name or serial to a safe directory name.
+fn path_component(s: &str) -> String {
+    let cleaned: String = s
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    match cleaned.trim_start_matches('.') {
+        "" => "unknown".to_owned(),
+        rest => rest.to_owned(),
+    }
+}
+
+fn io_error(path: &Path, e: io::Error) -> Error {
+    Error::Profile(format!("{}: {e}", path.display()))
+}
+
+/// Like [`io_error`], but reports a missing file as profile `name` not
+/// existing.
+fn not_found(name: &str, path: &Path, e: io::Error) -> Error {
+    if e.kind() == io::ErrorKind::NotFound {
+        Error::ProfileNotFound(name.to_owned())
+    } else {
+        io_error(path, e)
+    }
+}
+
+/// Applies the default profile of `cam` from [`ProfileStore::user_default`],
+/// if one is set. Called by [`Camera::open`].
+pub(crate) fn apply_default(cam: &Camera) -> Result<()> {
+    let Some(store) = ProfileStore::user_default() else {
+        return Ok(());
+    };
+    let info = cam.info()?;
+    if let Some(name) = store.default_profile(&info)? {
+        cam.apply_settings(&store.load(&info, &name)?)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ImageType, RoiFormat};
+
+    fn info() -> CameraInfo {
+        CameraInfo {
+            name: "SV705C".into(),
+            serial: "ab/12".into(),
+            port_type: "USB3.0".into(),
+            device_id: 0,
+            camera_id: 0,
+        }
+    }
+
+    fn settings(width: i32) -> CameraSettings {
+        CameraSettings {
+            controls: Vec::new(),
+            roi: RoiFormat {
+                start_x: 0,
+                start_y: 0,
+                width,
+                height: 480,
+                bin: 1,
+            },
+            bin_mode: None,
+            image_type: ImageType::Raw8,
+            mode: None,
+            trigger_outputs: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn save_list_load_delete() {
+        let root = std::env::temp_dir().join(format!("svbony-profiles-{}", std::process::id()));
+        let store = ProfileStore::new(&root);
+        let info = info();
+        assert_eq!(store.camera_dir(&info), root.join("SV705C").join("ab_12"));
+        assert!(store.list(&info).unwrap().is_empty());
+
+        store.save(&info, "planetary-fast", &settings(640)).unwrap();
+        store.save(&info, "flats", &settings(800)).unwrap();
+        assert_eq!(store.list(&info).unwrap(), ["flats", "planetary-fast"]);
+        assert_eq!(store.load(&info, "flats").unwrap(), settings(800));
+
+        assert_eq!(store.default_profile(&info).unwrap(), None);
+        assert_eq!(
+            store.set_default(&info, Some("missing")),
+            Err(Error::ProfileNotFound("missing".into()))
+        );
+        store.set_default(&info, Some("flats")).unwrap();
+        assert_eq!(
+            store.default_profile(&info).unwrap().as_deref(),
+            Some("flats")
+        );
+
+        store.delete(&info, "flats").unwrap();
+        assert_eq!(store.list(&info).unwrap(), ["planetary-fast"]);
+        assert_eq!(store.default_profile(&info).unwrap(), None);
+        let missing = Error::ProfileNotFound("flats".into());
+        assert_eq!(store.load(&info, "flats").unwrap_err(), missing);
+        assert_eq!(store.delete(&info, "flats").unwrap_err(), missing);
+
+        fs::write(store.camera_dir(&info).join("broken.json"), "{").unwrap();
+        assert!(matches!(
+            store.load(&info, "broken"),
+            Err(Error::Profile(_))
+        ));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn names_are_validated() {
+        for bad in ["", ".hidden", "a/b", "..", "x\\y"] {
+            assert!(validate_name(bad).is_err(), "{bad:?}");
+        }
+        for good in ["deep-sky cooled", "flats_2", "v1.2"] {
+            assert!(validate_name(good).is_ok(), "{good:?}");
+        }
+        assert_eq!(path_component("../x y"), "_x_y");
+        assert_eq!(path_component(""), "unknown");
+    }
+}
@@ -0,0 +1,386 @@
+//! ROI construction, validation and coordinate conversion.
+//!
+//! [`RoiFormat`] coordinates are in output (binned) pixels: `start_x` /
+//! `start_y` count binned pixels from the sensor origin and `width` /
+//! `height` are the frame size. [`RoiBuilder`] produces ROIs that the SDK
+//! accepts for a given camera; the conversion methods on [`RoiFormat`] map
+//! between frame and sensor pixels.
+
+use crate::{BinMode, CameraProperty, Error, Result, RoiFormat, RoiFormatEx};
+
+/// ROI widths must be a multiple of this many pixels.
+pub const ROI_WIDTH_ALIGN: i32 = 8;
+/// ROI heights must be a multiple of this many pixels.
+pub const ROI_HEIGHT_ALIGN: i32 = 2;
+
+#[derive(Debug, Clone, Copy)]
+enum Placement {
+    FullFrame,
+    Centered {
+        width: i32,
+        height: i32,
+    },
+    AroundPoint {
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+    },
+    At {
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+    },
+}
+
+/// Builds an [`RoiFormat`] that is valid for a camera.
+///
+/// Created with [`RoiFormat::builder`]. All positions and sizes are in
+/// binned pixels for the selected [`bin`](Self::bin) factor. Sizes are
+/// rounded down to the SDK's alignment (width a multiple of 8, height a
+/// multiple of 2) and, on colour sensors, start offsets are rounded down to
+/// even values to keep the Bayer phase.
+///
+/// ```no_run
+/// use svbony::{Camera, RoiFormat};
+///
+/// let cam = Camera::open(0)?;
+/// let prop = cam.property()?;
+/// let roi = RoiFormat::builder(&prop).bin(2).centered(640, 480).build()?;
+/// cam.set_roi(&roi)?;
+/// # Ok::<(), svbony::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct RoiBuilder<'a> {
+    prop: &'a CameraProperty,
+    bin: i32,
+    placement: Placement,
+}
+
+impl<'a> RoiBuilder<'a> {
+    fn new(prop: &'a CameraProperty) -> Self {
+        Self {
+            prop,
+            bin: 1,
+            placement: Placement::FullFrame,
+        }
+    }
+
+    /// Sets the binning factor (default 1).
+    pub fn bin(mut self, bin: i32) -> Self {
+        self.bin = bin;
+        self
+    }
+
+    /// Uses the whole sensor (the default).
+    pub fn full_frame(mut self) -> Self {
+        self.placement = Placement::FullFrame;
+        self
+    }
+
+    /// Places a `width` x `height` ROI in the middle of the sensor.
+    pub fn centered(mut self, width: i32, height: i32) -> Self {
+        self.placement = Placement::Centered { width, height };
+        self
+    }
+
+    /// Places a `size` x `size` ROI centred on `(x, y)`, shifted as needed to
+    /// stay on the sensor. Useful for tracking a star or planet.
+    pub fn around_point(mut self, x: i32, y: i32, size: i32) -> Self {
+        self.placement = Placement::AroundPoint {
+            x,
+            y,
+            width: size,
+            height: size,
+        };
+        self
+    }
+
+    /// Places a `width` x `height` ROI with its top-left corner at `(x, y)`.
+    ///
+    /// Unlike the other placements this is not shifted to fit; [`build`]
+    /// fails with [`Error::OutOfBoundary`] if it extends past the sensor.
+    ///
+    /// [`build`]: Self::build
+    pub fn at(mut self, x: i32, y: i32, width: i32, height: i32) -> Self {
+        self.placement = Placement::At {
+            x,
+            y,
+            width,
+            height,
+        };
+        self
+    }
+
+    /// Validates and rounds the ROI.
+    ///
+    /// Returns [`Error::InvalidArgument`] for a bin factor the camera does not
+    /// list in [`CameraProperty::supported_bins`], [`Error::InvalidSize`] for
+    /// sizes that are empty after rounding or larger than the sensor, and
+    /// [`Error::OutOfBoundary`] for an [`at`](Self::at) ROI that does not fit.
+    pub fn build(&self) -> Result<RoiFormat> {
+        let bin = self.bin;
+        if bin < 1 || !self.prop.supported_bins.contains(&bin) {
+            return Err(Error::InvalidArgument(format!(
+                "bin {bin} is not supported (supported: {:?})",
+                self.prop.supported_bins
+            )));
+        }
+        let (max_w, max_h) = max_roi_size(self.prop, bin);
+        let (width, height) = match self.placement {
+            Placement::FullFrame => (max_w, max_h),
+            Placement::Centered { width, height }
+            | Placement::AroundPoint { width, height, .. }
+            | Placement::At { width, height, .. } => (
+                align_down(width, ROI_WIDTH_ALIGN),
+                align_down(height, ROI_HEIGHT_ALIGN),
+            ),
+        };
+        if width <= 0 || height <= 0 || width > max_w || height > max_h {
+            return Err(Error::InvalidSize);
+        }
+
+        // Sensor extent in binned pixels, before alignment of the ROI size.
+        let sensor_w = (self.prop.max_width / bin as i64) as i32;
+        let sensor_h = (self.prop.max_height / bin as i64) as i32;
+        let (x, y) = match self.placement {
+            Placement::FullFrame | Placement::Centered { .. } => {
+                ((sensor_w - width) / 2, (sensor_h - height) / 2)
+            }
+            Placement::AroundPoint { x, y, .. } => (
+                (x - width / 2).clamp(0, sensor_w - width),
+                (y - height / 2).clamp(0, sensor_h - height),
+            ),
+            Placement::At { x, y, .. } => {
+                if x < 0 || y < 0 || x + width > sensor_w || y + height > sensor_h {
+                    return Err(Error::OutOfBoundary);
+                }
+                (x, y)
+            }
+        };
+        let (x, y) = if self.prop.is_color {
+            (align_down(x, 2), align_down(y, 2))
+        } else {
+            (x, y)
+        };
+
+        Ok(RoiFormat {
+            start_x: x,
+            start_y: y,
+            width,
+            height,
+            bin,
+        })
+    }
+}
+
+/// Largest legal ROI size for `prop` at `bin`, in binned pixels.
+pub fn max_roi_size(prop: &CameraProperty, bin: i32) -> (i32, i32) {
+    let bin = i64::from(bin.max(1));
+    (
+        align_down((prop.max_width / bin) as i32, ROI_WIDTH_ALIGN),
+        align_down((prop.max_height / bin) as i32, ROI_HEIGHT_ALIGN),
+    )
+}
+
+fn align_down(v: i32, align: i32) -> i32 {
+    v - v.rem_euclid(align)
+}
+
+impl RoiFormat {
+    /// Starts building a ROI validated against `prop`.
+    pub fn builder(prop: &CameraProperty) -> RoiBuilder<'_> {
+        RoiBuilder::new(prop)
+    }
+
+    /// Combines this ROI with a binning mode for
+    /// [`Camera::set_roi_ex`](crate::Camera::set_roi_ex).
+    pub fn with_bin_mode(&self, bin_mode: BinMode) -> RoiFormatEx {
+        RoiFormatEx {
+            start_x: self.start_x,
+            start_y: self.start_y,
+            width: self.width,
+            height: self.height,
+            bin: self.bin,
+            bin_mode,
+        }
+    }
+
+    /// Returns `true` if the width and height have the SDK's alignment.
+    pub fn is_aligned(&self) -> bool {
+        self.width % ROI_WIDTH_ALIGN == 0 && self.height % ROI_HEIGHT_ALIGN == 0
+    }
+
+    /// Maps frame pixel `(x, y)` to the top-left sensor pixel it covers.
+    pub fn frame_to_sensor(&self, x: usize, y: usize) -> (u32, u32) {
+        let bin = self.bin.max(1) as i64;
+        (
+            ((self.start_x as i64 + x as i64) * bin) as u32,
+            ((self.start_y as i64 + y as i64) * bin) as u32,
+        )
+    }
+
+    /// Maps sensor column `x` to a frame column, or `None` if outside the ROI.
+    pub fn sensor_x_to_frame(&self, x: u32) -> Option<usize> {
+        sensor_to_frame_axis(x, self.start_x, self.width, self.bin)
+    }
+
+    /// Maps sensor row `y` to a frame row, or `None` if outside the ROI.
+    pub fn sensor_y_to_frame(&self, y: u32) -> Option<usize> {
+        sensor_to_frame_axis(y, self.start_y, self.height, self.bin)
+    }
+
+    /// Maps sensor pixel `(x, y)` to the frame pixel that contains it, or
+    /// `None` if it is outside the ROI.
+    pub fn sensor_to_frame(&self, x: u32, y: u32) -> Option<(usize, usize)> {
+        Some((self.sensor_x_to_frame(x)?, self.sensor_y_to_frame(y)?))
+    }
+
+    /// Returns the covered sensor area as `(x, y, width, height)` in sensor
+    /// pixels.
+    pub fn sensor_rect(&self) -> (u32, u32, u32, u32) {
+        let bin = self.bin.max(1) as u32;
+        let (x, y) = self.frame_to_sensor(0, 0);
+        (
+            x,
+            y,
+            self.width.max(0) as u32 * bin,
+            self.height.max(0) as u32 * bin,
+        )
+    }
+}
+
+impl RoiFormatEx {
+    /// Returns the ROI without the binning mode.
+    pub fn roi(&self) -> RoiFormat {
+        RoiFormat {
+            start_x: self.start_x,
+            start_y: self.start_y,
+            width: self.width,
+            height: self.height,
+            bin: self.bin,
+        }
+    }
+}
+
+fn sensor_to_frame_axis(sensor: u32, start: i32, len: i32, bin: i32) -> Option<usize> {
+    let pos = sensor as i64 / bin.max(1) as i64 - start as i64;
+    (0..len as i64).contains(&pos).then_some(pos as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BayerPattern, ImageType};
+
+    fn prop(is_color: bool) -> CameraProperty {
+        CameraProperty {
+            max_width: 1944,
+            max_height: 1097,
+            is_color,
+            bayer_pattern: is_color.then_some(BayerPattern::Rg),
+            supported_bins: vec![1, 2],
+            supported_formats: vec![ImageType::Raw16],
+            max_bit_depth: 12,
+            is_trigger_cam: false,
+        }
+    }
+
+    #[test]
+    fn full_frame_is_aligned() {
+        let p = prop(false);
+        let roi = RoiFormat::builder(&p).build().unwrap();
+        assert_eq!((roi.width, roi.height), (1944, 1096));
+        assert_eq!((roi.start_x, roi.start_y), (0, 0));
+        assert!(roi.is_aligned());
+
+        let roi = RoiFormat::builder(&p).bin(2).full_frame().build().unwrap();
+        assert_eq!((roi.width, roi.height, roi.bin), (968, 548, 2));
+        assert_eq!((roi.start_x, roi.start_y), (2, 0));
+    }
+
+    #[test]
+    fn centered_rounds_size() {
+        let p = prop(false);
+        let roi = RoiFormat::builder(&p).centered(645, 481).build().unwrap();
+        assert_eq!((roi.width, roi.height), (640, 480));
+        assert_eq!((roi.start_x, roi.start_y), (652, 308));
+
+        // Colour sensors keep even offsets.
+        let roi = RoiFormat::builder(&prop(true))
+            .centered(640, 478)
+            .build()
+            .unwrap();
+        assert_eq!((roi.start_x, roi.start_y), (652, 308));
+    }
+
+    #[test]
+    fn around_point_stays_on_sensor() {
+        let p = prop(false);
+        let roi = RoiFormat::builder(&p)
+            .around_point(1000, 500, 128)
+            .build()
+            .unwrap();
+        assert_eq!((roi.start_x, roi.start_y, roi.width), (936, 436, 128));
+
+        let roi = RoiFormat::builder(&p)
+            .around_point(10, 1090, 128)
+            .build()
+            .unwrap();
+        assert_eq!((roi.start_x, roi.start_y), (0, 1097 - 128));
+    }
+
+    #[test]
+    fn invalid_requests_are_rejected() {
+        let p = prop(false);
+        assert!(matches!(
+            RoiFormat::builder(&p).bin(4).build(),
+            Err(Error::InvalidArgument(_))
+        ));
+        assert_eq!(
+            RoiFormat::builder(&p).centered(4, 100).build().unwrap_err(),
+            Error::InvalidSize
+        );
+        assert_eq!(
+            RoiFormat::builder(&p)
+                .centered(4000, 100)
+                .build()
+                .unwrap_err(),
+            Error::InvalidSize
+        );
+        assert_eq!(
+            RoiFormat::builder(&p)
+                .at(1900, 0, 64, 64)
+                .build()
+                .unwrap_err(),
+            Error::OutOfBoundary
+        );
+        let roi = RoiFormat::builder(&p).at(8, 6, 70, 33).build().unwrap();
+        assert_eq!(
+            (roi.start_x, roi.start_y, roi.width, roi.height),
+            (8, 6, 64, 32)
+        );
+    }
+
+    #[test]
+    fn coordinate_conversions() {
+        let roi = RoiFormat {
+            start_x: 10,
+            start_y: 5,
+            width: 64,
+            height: 32,
+            bin: 2,
+        };
+        assert_eq!(roi.frame_to_sensor(0, 0), (20, 10));
+        assert_eq!(roi.frame_to_sensor(3, 1), (26, 12));
+        assert_eq!(roi.sensor_to_frame(27, 13), Some((3, 1)));
+        assert_eq!(roi.sensor_to_frame(19, 10), None);
+        assert_eq!(roi.sensor_x_to_frame(20 + 128), None);
+        assert_eq!(roi.sensor_rect(), (20, 10, 128, 64));
+
+        let ex = roi.with_bin_mode(BinMode::Sum);
+        assert_eq!(ex.bin_mode, BinMode::Sum);
+        assert_eq!(ex.roi(), roi);
+    }
+}
@@ -0,0 +1,144 @@
+//! TCP plumbing shared by the INDI and Alpaca servers.
+
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Pause after a failed `accept`, e.g. when out of file descriptors.
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Accepts connections until `shutdown` is set and hands each to `accept`
+/// with Nagle's algorithm off and `write_timeout` applied.
+///
+/// Errors only affect one connection: a stream that cannot be set up is
+/// dropped, and a failed `accept` is retried after a short pause.
+pub(crate) fn accept_loop(
+    listener: &TcpListener,
+    shutdown: &AtomicBool,
+    write_timeout: Duration,
+    mut accept: impl FnMut(TcpStream) -> io::Result<()>,
+) {
+    for stream in listener.incoming() {
+        if shutdown.load(Ordering::Relaxed) {
+            break;
+        }
+        let stream = match stream {
+            Ok(stream) => stream,
+            // A client that went away before being accepted.
+            Err(e) if e.kind() == io::ErrorKind::ConnectionAborted => continue,
+            Err(_) => {
+                thread::sleep(ACCEPT_BACKOFF);
+                continue;
+            }
+        };
+        let _ = stream.set_nodelay(true);
+        if stream.set_write_timeout(Some(write_timeout)).is_ok() {
+            // On error the stream is dropped, closing the connection.
+            let _ = accept(stream);
+        }
+    }
+}
+
+/// A server running on a background thread, stopped on drop.
+pub(crate) struct ServerThread {
+    addr: SocketAddr,
+    shutdown: Arc<AtomicBool>,
+    thread: Option<JoinHandle<io::Result<()>>>,
+    /// Names the server in the error reported if its thread panics.
+    name: &'static str,
+}
+
+impl ServerThread {
+    /// Runs `run` on a new thread. It must serve an [`accept_loop`] on
+    /// `addr` that watches `shutdown`.
+    pub(crate) fn spawn(
+        name: &'static str,
+        addr: SocketAddr,
+        shutdown: Arc<AtomicBool>,
+        run: impl FnOnce() -> io::Result<()> + Send + 'static,
+    ) -> Self {
+        Self {
+            addr,
+            shutdown,
+            thread: Some(thread::spawn(run)),
+            name,
+        }
+    }
+
+    pub(crate) fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Stops the server and returns the result of its thread.
+    pub(crate) fn stop(&mut self) -> io::Result<()> {
+        let Some(thread) = self.thread.take() else {
+            return Ok(());
+        };
+        self.shutdown.store(true, Ordering::Relaxed);
+        // Wake the accept loop.
+        let mut wake = self.addr;
+        if wake.ip().is_unspecified() {
+            wake.set_ip(match wake {
+                SocketAddr::V4(_) => Ipv4Addr::LOCALHOST.into(),
+                SocketAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+            });
+        }
+        let _ = TcpStream::connect_timeout(&wake, Duration::from_secs(1));
+        thread.join().unwrap_or_else(|_| {
+            Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("{} server panicked", self.name),
+            ))
+        })
+    }
+}
+
+impl Drop for ServerThread {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::sync::mpsc;
+
+    #[test]
+    fn accepts_until_stopped() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let (accepted, received) = mpsc::channel();
+        let flag = Arc::clone(&shutdown);
+        let mut server = ServerThread::spawn("test", addr, shutdown, move || {
+            let mut first = true;
+            accept_loop(&listener, &flag, Duration::from_secs(1), |stream| {
+                if std::mem::take(&mut first) {
+                    return Err(io::Error::new(io::ErrorKind::Other, "setup failed"));
+                }
+                let _ = accepted.send(stream.write_timeout()?);
+                Ok(())
+            });
+            Ok(())
+        });
+
+        // The first connection fails to set up but the server keeps going.
+        let mut rejected = TcpStream::connect(addr).unwrap();
+        rejected
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        assert_eq!(rejected.read(&mut [0; 1]).unwrap(), 0);
+        let _client = TcpStream::connect(addr).unwrap();
+        let timeout = received.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(timeout, Some(Duration::from_secs(1)));
+        server.stop().unwrap();
+        // The wake-up connection is not handed on.
+        assert!(received.try_recv().is_err());
+        server.stop().unwrap();
+    }
+}
@@ -0,0 +1,200 @@
+//! Snapshot and restore of a camera's configuration.
+
+use crate::{
+    BinMode, Camera, CameraMode, ControlType, ImageType, Result, RoiFormat, TrigOutputPin,
+    TriggerOutput,
+};
+
+/// Value and auto flag of one control.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ControlSetting {
+    /// The control.
+    pub control: ControlType,
+    /// Raw value in SDK units.
+    pub value: i64,
+    /// Whether the camera adjusts the control automatically.
+    #[cfg_attr(feature = "serde", serde(rename = "auto"))]
+    pub auto_: bool,
+}
+
+/// Configuration of one trigger output pin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TriggerOutputSetting {
+    /// The output pin.
+    pub pin: TrigOutputPin,
+    /// Whether the pin is driven, and how.
+    pub output: TriggerOutput,
+}
+
+/// A camera's full configuration, as captured by
+/// [`Camera::snapshot_settings`].
+///
+/// With the `serde` feature the snapshot can be stored as JSON, TOML or any
+/// other serde format and applied later, to the same camera or another unit
+/// of the same model, with [`Camera::apply_settings`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CameraSettings {
+    /// Writable controls in the camera's enumeration order.
+    pub controls: Vec<ControlSetting>,
+    /// Region of interest and binning.
+    pub roi: RoiFormat,
+    /// Binning mode, for cameras that support it
+    /// ([`Camera::supports_bin_mode`]).
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub bin_mode: Option<BinMode>,
+    /// Output image format.
+    pub image_type: ImageType,
+    /// Capture / trigger mode, for cameras that report one.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub mode: Option<CameraMode>,
+    /// Trigger output pins, for cameras that have them.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub trigger_outputs: Vec<TriggerOutputSetting>,
+}
+
+impl CameraSettings {
+    /// Returns the stored setting for `control`, if any.
+    pub fn control(&self, control: ControlType) -> Option<&ControlSetting> {
+        self.controls.iter().find(|c| c.control == control)
+    }
+}
+
+impl Camera {
+    /// Reads the camera's current configuration.
+    ///
+    /// Captures every writable control, the ROI and binning mode, the output
+    /// image type, the camera mode and the trigger output pins. Optional
+    /// parts the camera cannot report (extended ROI, trigger mode, trigger
+    /// outputs) are left empty rather than failing the snapshot.
+    pub fn snapshot_settings(&self) -> Result<CameraSettings> {
+        let mut controls = Vec::new();
+        for caps in self.supported_controls().iter().filter(|c| c.is_writable) {
+            let (value, auto_) = self.get_control(caps.control_type)?;
+            controls.push(ControlSetting {
+                control: caps.control_type,
+                value,
+                auto_,
+            });
+        }
+
+        let (roi, bin_mode) = match self.roi_ex() {
+            Ok(ex) => (ex.roi(), Some(ex.bin_mode)),
+            Err(_) => (self.roi()?, None),
+        };
+
+        let trigger_outputs = [TrigOutputPin::PinA, TrigOutputPin::PinB]
+            .into_iter()
+            .filter_map(|pin| {
+                let output = self.trigger_output(pin).ok()?;
+                Some(TriggerOutputSetting { pin, output })
+            })
+            .collect();
+
+        Ok(CameraSettings {
+            controls,
+            roi,
+            bin_mode,
+            image_type: self.output_image_type()?,
+            mode: self.mode().ok(),
+            trigger_outputs,
+        })
+    }
+
+    /// Applies a configuration captured by
+    /// [`snapshot_settings`](Self::snapshot_settings).
+    ///
+    /// Video capture is stopped first, since the ROI and image type cannot
+    /// change while capturing, and is left stopped. Settings are then applied
+    /// in dependency order: camera mode, output image type, ROI and binning,
+    /// readout speed, the remaining controls, and finally the trigger
+    /// outputs. Control values are validated as in
+    /// [`set_control`](Self::set_control); the first failure is returned and
+    /// later settings are not applied.
+    pub fn apply_settings(&self, settings: &CameraSettings) -> Result<()> {
+        // Fails harmlessly when capture is not running.
+        let _ = self.stop_capture();
+
+        if let Some(mode) = settings.mode {
+            self.set_mode(mode)?;
+        }
+        self.set_output_image_type(settings.image_type)?;
+        self.apply_roi(&settings.roi, settings.bin_mode)?;
+
+        // Readout speed limits the exposure range, so it goes first.
+        let (speed, rest): (Vec<&ControlSetting>, Vec<_>) = settings
+            .controls
+            .iter()
+            .partition(|c| c.control == ControlType::FrameSpeedMode);
+        for c in speed.into_iter().chain(rest) {
+            self.set_control(c.control, c.value, c.auto_)?;
+        }
+
+        for t in &settings.trigger_outputs {
+            self.configure_trigger_output(t.pin, t.output)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_round_trip_through_json() {
+        let settings = CameraSettings {
+            controls: vec![
+                ControlSetting {
+                    control: ControlType::Exposure,
+                    value: 20_000,
+                    auto_: false,
+                },
+                ControlSetting {
+                    control: ControlType::Gain,
+                    value: 120,
+                    auto_: true,
+                },
+            ],
+            roi: RoiFormat {
+                start_x: 8,
+                start_y: 4,
+                width: 640,
+                height: 480,
+                bin: 2,
+            },
+            bin_mode: Some(BinMode::Sum),
+            image_type: ImageType::Raw16,
+            mode: Some(CameraMode::TrigSoft),
+            trigger_outputs: vec![TriggerOutputSetting {
+                pin: TrigOutputPin::PinA,
+                output: TriggerOutput::Enabled(crate::TriggerOutputConfig {
+                    polarity: crate::TriggerPolarity::ActiveHigh,
+                    delay: std::time::Duration::ZERO,
+                    duration: std::time::Duration::from_micros(500),
+                }),
+            }],
+        };
+        let json = serde_json::to_string(&settings).unwrap();
+        assert!(json.contains("\"Exposure\""));
+        assert!(json.contains("\"auto\":true"));
+        let back: CameraSettings = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, settings);
+        assert_eq!(back.control(ControlType::Gain).unwrap().value, 120);
+    }
+
+    #[test]
+    fn optional_parts_default_when_missing() {
+        let json = r#"{
+            "controls": [],
+            "roi": {"start_x": 0, "start_y": 0, "width": 64, "height": 64, "bin": 1},
+            "image_type": "Y8"
+        }"#;
+        let s: CameraSettings = serde_json::from_str(json).unwrap();
+        assert_eq!(s.bin_mode, None);
+        assert_eq!(s.mode, None);
+        assert!(s.trigger_outputs.is_empty());
+    }
+}
@@ -0,0 +1,26 @@
+//! Small robust-statistics helpers shared by the frame analysis modules.
+
+/// Scale factor turning a median absolute deviation into a Gaussian sigma.
+pub(crate) const MAD_TO_SIGMA: f64 = 1.4826;
+
+/// Median of `values`, reordering the slice in place. Returns 0 when empty.
+pub(crate) fn median(values: &mut [f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    let len = values.len();
+    let mid = len / 2;
+    let (lower, &mut upper, _) = values.select_nth_unstable_by(mid, |a, b| a.total_cmp(b));
+    if len % 2 == 1 {
+        upper
+    } else {
+        let below = lower.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        (below + upper) / 2.0
+    }
+}
+
+/// Median absolute deviation of `values` around `center`.
+pub(crate) fn mad(values: &[f64], center: f64) -> f64 {
+    let mut dev: Vec<f64> = values.iter().map(|v| (v - center).abs()).collect();
+    median(&mut dev)
+}
@@ -0,0 +1,422 @@
+//! Display stretching of linear frames into 8-bit previews.
+//!
+//! Linear astronomical frames look almost black when shown directly. This
+//! module converts any [`Frame`] into an 8-bit preview using either an
+//! automatic screen-transfer function (a midtones transfer function fitted to
+//! the median and MAD of the data), manual black / white / gamma levels, or a
+//! caller-supplied lookup table.
+//!
+//! ```no_run
+//! use svbony::Camera;
+//! use svbony::stretch::{preview, Stretch};
+//!
+//! let cam = Camera::open(0)?;
+//! cam.start_capture()?;
+//! let frame = cam.capture_frame(5000)?;
+//! cam.stop_capture()?;
+//!
+//! // 8-bit Y8 or Rgb24 frame, ready for display
+//! let preview = preview(&frame, &Stretch::auto());
+//! # Ok::<(), svbony::Error>(())
+//! ```
+//!
+//! Raw (Bayer) frames are stretched as a single luminance channel without
+//! debayering. `Rgb24` / `Rgb32` frames keep their channel order; the padding
+//! channel of `Rgb32` is dropped, so both produce an `Rgb24` preview.
+
+use crate::stats::{mad, median, MAD_TO_SIGMA};
+use crate::{Frame, ImageType};
+
+/// Upper bound on the number of samples per channel used to estimate the
+/// auto-stretch statistics; larger frames are subsampled.
+const MAX_STAT_SAMPLES: usize = 500_000;
+
+/// Parameters of the automatic screen-transfer function.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AutoStretch {
+    /// Shadows clipping point in units of the normalized MAD-derived sigma,
+    /// relative to the median (typically negative).
+    pub shadows_clip: f64,
+    /// Normalized level the background (median) is mapped to.
+    pub target_background: f64,
+    /// `true` to apply the same transfer function to all color channels,
+    /// `false` to fit each channel separately (which also neutralizes color
+    /// casts in the background).
+    pub linked: bool,
+}
+
+impl Default for AutoStretch {
+    fn default() -> Self {
+        Self {
+            shadows_clip: -2.8,
+            target_background: 0.25,
+            linked: true,
+        }
+    }
+}
+
+/// How a frame is mapped to 8 bits.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stretch {
+    /// Automatic screen-transfer function.
+    Auto(AutoStretch),
+    /// Manual levels in sample units (ADU).
+    Levels {
+        /// Level mapped to black.
+        black: u16,
+        /// Level mapped to white.
+        white: u16,
+        /// Gamma applied after the linear black / white mapping (1.0 =
+        /// linear, > 1 brightens midtones).
+        gamma: f64,
+    },
+    /// A single lookup table applied to every channel.
+    Lut(Lut),
+}
+
+impl Stretch {
+    /// Linked auto-stretch with default parameters.
+    pub fn auto() -> Self {
+        Self::Auto(AutoStretch::default())
+    }
+}
+
+/// Lookup table from sample value to 8-bit display value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lut {
+    table: Vec<u8>,
+}
+
+impl Lut {
+    /// Builds a table covering inputs `0..=input_max` by evaluating `f` on the
+    /// normalized input `x ∈ [0, 1]`. `f` should return a value in `[0, 1]`.
+    pub fn from_fn(input_max: u16, f: impl Fn(f64) -> f64) -> Self {
+        let max = input_max.max(1) as f64;
+        let table = (0..=input_max as usize)
+            .map(|v| (f(v as f64 / max).clamp(0.0, 1.0) * 255.0).round() as u8)
+            .collect();
+        Self { table }
+    }
+
+    /// Builds a table directly from its entries, indexed by sample value.
+    /// Inputs beyond the end of `table` map to its last entry.
+    pub fn from_table(table: Vec<u8>) -> Self {
+        Self { table }
+    }
+
+    /// Manual levels: linear from `black` to `white`, followed by `gamma`.
+    pub fn levels(input_max: u16, black: u16, white: u16, gamma: f64) -> Self {
+        let max = input_max.max(1) as f64;
+        let (lo, hi) = (black as f64 / max, white.max(black.saturating_add(1)) as f64 / max);
+        let inv_gamma = 1.0 / gamma.max(f64::EPSILON);
+        Self::from_fn(input_max, |x| {
+            ((x - lo) / (hi - lo)).clamp(0.0, 1.0).powf(inv_gamma)
+        })
+    }
+
+    /// Maps one sample.
+    pub fn apply(&self, v: u16) -> u8 {
+        match self.table.get(v as usize) {
+            Some(&out) => out,
+            None => self.table.last().copied().unwrap_or(0),
+        }
+    }
+
+    /// Number of entries in the table.
+    pub fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    /// Returns `true` if the table has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+}
+
+/// Midtones transfer function: maps `x ∈ [0, 1]` so that `m` goes to 0.5.
+pub fn mtf(m: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        0.0
+    } else if x >= 1.0 {
+        1.0
+    } else if (x - m).abs() < f64::EPSILON {
+        0.5
+    } else {
+        ((m - 1.0) * x) / ((2.0 * m - 1.0) * x - m)
+    }
+}
+
+/// Shadows, highlights and midtones of a fitted screen-transfer function, all
+/// normalized to `[0, 1]`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TransferParams {
+    /// Shadows clipping point.
+    pub shadows: f64,
+    /// Highlights clipping point.
+    pub highlights: f64,
+    /// Midtones balance.
+    pub midtones: f64,
+}
+
+impl TransferParams {
+    /// Evaluates the transfer function at normalized input `x`.
+    pub fn apply(&self, x: f64) -> f64 {
+        let range = (self.highlights - self.shadows).max(f64::EPSILON);
+        mtf(self.midtones, ((x - self.shadows) / range).clamp(0.0, 1.0))
+    }
+
+    /// Builds a lookup table for inputs `0..=input_max`.
+    pub fn lut(&self, input_max: u16) -> Lut {
+        Lut::from_fn(input_max, |x| self.apply(x))
+    }
+}
+
+impl AutoStretch {
+    /// Fits the transfer function to each color channel of `frame`.
+    ///
+    /// When [`linked`](Self::linked) is set, all returned entries are the
+    /// average of the per-channel fits.
+    pub fn fit(&self, frame: &Frame) -> Vec<TransferParams> {
+        let max = input_max(frame.image_type) as f64;
+        let per_channel: Vec<TransferParams> = (0..color_channels(frame))
+            .map(|c| {
+                let mut values = subsampled(frame, c, max);
+                let med = median(&mut values);
+                let sigma = MAD_TO_SIGMA * mad(&values, med);
+                self.params(med, sigma)
+            })
+            .collect();
+
+        if self.linked && per_channel.len() > 1 {
+            let n = per_channel.len() as f64;
+            let avg = TransferParams {
+                shadows: per_channel.iter().map(|p| p.shadows).sum::<f64>() / n,
+                highlights: per_channel.iter().map(|p| p.highlights).sum::<f64>() / n,
+                midtones: per_channel.iter().map(|p| p.midtones).sum::<f64>() / n,
+            };
+            vec![avg; per_channel.len()]
+        } else {
+            per_channel
+        }
+    }
+
+    /// Transfer parameters for a channel with normalized `median` and
+    /// MAD-derived `sigma`. The midtones balance is chosen so that the median
+    /// lands exactly on [`target_background`](Self::target_background) after
+    /// clipping.
+    fn params(&self, median: f64, sigma: f64) -> TransferParams {
+        if median <= 0.5 {
+            let shadows = (median + self.shadows_clip * sigma).clamp(0.0, 1.0);
+            TransferParams {
+                shadows,
+                highlights: 1.0,
+                midtones: mtf(
+                    self.target_background,
+                    (median - shadows) / (1.0 - shadows).max(f64::EPSILON),
+                ),
+            }
+        } else {
+            let highlights = (median - self.shadows_clip * sigma).clamp(0.0, 1.0);
+            TransferParams {
+                shadows: 0.0,
+                highlights,
+                midtones: 1.0
+                    - mtf(
+                        self.target_background,
+                        (highlights - median) / highlights.max(f64::EPSILON),
+                    ),
+            }
+        }
+    }
+}
+
+/// Returns the lookup tables `stretch` applies to each color channel of
+/// `frame`.
+pub fn luts(frame: &Frame, stretch: &Stretch) -> Vec<Lut> {
+    let max = input_max(frame.image_type);
+    let n = color_channels(frame);
+    match stretch {
+        Stretch::Auto(auto) => auto.fit(frame).iter().map(|p| p.lut(max)).collect(),
+        Stretch::Levels {
+            black,
+            white,
+            gamma,
+        } => vec![Lut::levels(max, *black, *white, *gamma); n],
+        Stretch::Lut(lut) => vec![lut.clone(); n],
+    }
+}
+
+/// Converts `frame` into an 8-bit preview: `Y8` for single-channel input,
+/// `Rgb24` (same channel order as the input) for color input.
+pub fn preview(frame: &Frame, stretch: &Stretch) -> Frame {
+    apply_luts(frame, &luts(frame, stretch))
+}
+
+/// Applies one lookup table per color channel (see [`luts`]).
+///
+/// # Panics
+///
+/// Panics if fewer tables than color channels are supplied.
+pub fn apply_luts(frame: &Frame, luts: &[Lut]) -> Frame {
+    let n = color_channels(frame);
+    assert!(
+        luts.len() >= n,
+        "need {n} lookup tables, got {}",
+        luts.len()
+    );
+    let out_type = if n == 1 {
+        ImageType::Y8
+    } else {
+        ImageType::Rgb24
+    };
+    let mut out = Frame::zeroed(frame.roi, out_type);
+    let mut i = 0;
+    for y in 0..frame.height() {
+        for x in 0..frame.width() {
+            for (c, lut) in luts.iter().enumerate().take(n) {
+                out.data[i] = lut.apply(frame.sample(x, y, c));
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+/// Converts `frame` into an 8-bit preview [`image::DynamicImage`].
+#[cfg(feature = "image")]
+pub fn preview_image(frame: &Frame, stretch: &Stretch) -> image::DynamicImage {
+    preview(frame, stretch).into_image()
+}
+
+/// Largest sample value representable by `ty`.
+fn input_max(ty: ImageType) -> u16 {
+    if ty.bytes_per_pixel() == 2 {
+        u16::MAX
+    } else {
+        u8::MAX as u16
+    }
+}
+
+fn color_channels(frame: &Frame) -> usize {
+    frame.channels().min(3)
+}
+
+/// Normalized samples of channel `c`, subsampled to at most
+/// [`MAX_STAT_SAMPLES`] values.
+fn subsampled(frame: &Frame, c: usize, max: f64) -> Vec<f64> {
+    let total = frame.width() * frame.height();
+    let step = ((total + MAX_STAT_SAMPLES - 1) / MAX_STAT_SAMPLES).max(1);
+    let w = frame.width().max(1);
+    (0..total)
+        .step_by(step)
+        .map(|i| frame.sample(i % w, i / w, c) as f64 / max)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RoiFormat;
+
+    fn roi(w: i32, h: i32) -> RoiFormat {
+        RoiFormat {
+            start_x: 0,
+            start_y: 0,
+            width: w,
+            height: h,
+            bin: 1,
+        }
+    }
+
+    /// Dark 16-bit frame: background ~1000 ADU with small noise and one star.
+    fn dark_frame() -> Frame {
+        let mut f = Frame::zeroed(roi(64, 64), ImageType::Raw16);
+        for y in 0..64 {
+            for x in 0..64 {
+                f.set_sample(x, y, 0, 1000 + ((x * 31 + y * 17) % 40) as u16);
+            }
+        }
+        f.set_sample(32, 32, 0, 60000);
+        f
+    }
+
+    #[test]
+    fn mtf_fixed_points() {
+        assert_eq!(mtf(0.3, 0.0), 0.0);
+        assert_eq!(mtf(0.3, 1.0), 1.0);
+        assert!((mtf(0.3, 0.3) - 0.5).abs() < 1e-12);
+        assert!(mtf(0.1, 0.05) > 0.05);
+    }
+
+    #[test]
+    fn auto_stretch_lifts_background() {
+        let f = dark_frame();
+        let p = preview(&f, &Stretch::auto());
+        assert_eq!(p.image_type, ImageType::Y8);
+        assert_eq!(p.data.len(), 64 * 64);
+        let bg = p.stats().median;
+        assert!((40.0..=90.0).contains(&bg), "background at {bg}");
+        assert_eq!(p.sample(32, 32, 0), 255);
+    }
+
+    #[test]
+    fn levels_and_gamma() {
+        let lut = Lut::levels(u16::MAX, 1000, 3000, 1.0);
+        assert_eq!(lut.apply(500), 0);
+        assert_eq!(lut.apply(2000), 128);
+        assert_eq!(lut.apply(3000), 255);
+        let bright = Lut::levels(u16::MAX, 1000, 3000, 2.2);
+        assert!(bright.apply(2000) > 128);
+        let clipped = Lut::levels(u16::MAX, u16::MAX, 0, 1.0);
+        assert_eq!(clipped.apply(1000), 0);
+
+        let f = dark_frame();
+        let p = preview(
+            &f,
+            &Stretch::Levels {
+                black: 0,
+                white: 65535,
+                gamma: 1.0,
+            },
+        );
+        assert!(p.sample(0, 0, 0) <= 5);
+    }
+
+    #[test]
+    fn unlinked_equalizes_channels() {
+        let mut f = Frame::zeroed(roi(16, 16), ImageType::Rgb32);
+        for (i, px) in f.data.chunks_exact_mut(4).enumerate() {
+            let n = (i % 7) as u8;
+            px.copy_from_slice(&[10 + n, 40 + n, 20 + n, 255]);
+        }
+        let linked = preview(&f, &Stretch::auto());
+        assert_eq!(linked.image_type, ImageType::Rgb24);
+        assert_eq!(linked.data.len(), 16 * 16 * 3);
+        let s = linked.sample(0, 0, 0);
+        assert!(linked.sample(0, 0, 1) > s);
+
+        let unlinked = preview(
+            &f,
+            &Stretch::Auto(AutoStretch {
+                linked: false,
+                ..AutoStretch::default()
+            }),
+        );
+        // Pixel 3 sits on every channel's median, which maps to the target
+        // background regardless of the channel's offset.
+        for c in 0..3 {
+            assert_eq!(unlinked.sample(3, 0, c), 64);
+        }
+    }
+
+    #[test]
+    fn custom_lut() {
+        let mut f = Frame::zeroed(roi(8, 1), ImageType::Y8);
+        f.data.copy_from_slice(&[0, 1, 2, 3, 4, 5, 6, 255]);
+        let invert = Lut::from_fn(255, |x| 1.0 - x);
+        let p = preview(&f, &Stretch::Lut(invert));
+        assert_eq!(p.data[0], 255);
+        assert_eq!(p.data[7], 0);
+        assert_eq!(Lut::from_table(vec![7, 9]).apply(1000), 9);
+    }
+}
@@ -0,0 +1,623 @@
+//! Background recording of temperature, cooler power and other read-only
+//! controls.
+//!
+//! [`TelemetryRecorder`] polls the selected controls on its own thread at a
+//! fixed interval and keeps the readings in memory as a [`TelemetryLog`],
+//! which can be exported as CSV or JSON. Control reads go through
+//! [`Camera::get_control`], which the SDK allows during video capture, so
+//! the recorder can run alongside an imaging session.
+//!
+//! The recorder also watches for a saturated cooler: running at (or near)
+//! full power while the sensor stays above the set point. When that lasts
+//! for [`SaturationConfig::hold`], an [`Alert::CoolerSaturated`] is raised;
+//! [`Alert::CoolerRecovered`] follows once the condition clears.
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use std::time::Duration;
+//! use svbony::Camera;
+//! use svbony::telemetry::{TelemetryConfig, TelemetryRecorder};
+//!
+//! let cam = Arc::new(Camera::open(0)?);
+//! let config = TelemetryConfig {
+//!     interval: Duration::from_secs(30),
+//!     ..TelemetryConfig::default()
+//! };
+//! let recorder = TelemetryRecorder::start(Arc::clone(&cam), config, |alert| {
+//!     eprintln!("{alert:?}");
+//! })?;
+//! // ... imaging session ...
+//! let log = recorder.stop();
+//! log.write_csv(std::fs::File::create("telemetry.csv").unwrap()).unwrap();
+//! # Ok::<(), svbony::Error>(())
+//! ```
+
+use std::io::{self, Write};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::{Camera, Celsius, ControlType, Error, Percent, Result};
+
+/// Settings for [`TelemetryRecorder`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TelemetryConfig {
+    /// Time between samples.
+    pub interval: Duration,
+    /// Controls to record. `None` records every read-only control the
+    /// camera has, plus [`ControlType::TargetTemperature`] and
+    /// [`ControlType::CoolerEnable`] when present.
+    pub controls: Option<Vec<ControlType>>,
+    /// Maximum number of samples kept; the oldest are dropped first.
+    /// `None` keeps everything.
+    pub capacity: Option<usize>,
+    /// Cooler saturation alert settings.
+    pub saturation: SaturationConfig,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(10),
+            controls: None,
+            capacity: None,
+            saturation: SaturationConfig::default(),
+        }
+    }
+}
+
+/// When to raise [`Alert::CoolerSaturated`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SaturationConfig {
+    /// Cooler power at or above which the cooler counts as saturated.
+    pub power: Percent,
+    /// How far above the set point, in °C, the sensor must be.
+    pub min_error: f64,
+    /// How long the condition must last before the alert is raised.
+    pub hold: Duration,
+}
+
+impl Default for SaturationConfig {
+    fn default() -> Self {
+        Self {
+            power: Percent(100),
+            min_error: 1.0,
+            hold: Duration::from_secs(5 * 60),
+        }
+    }
+}
+
+/// A condition detected while recording.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Alert {
+    /// The cooler has been at full power without reaching the set point for
+    /// [`SaturationConfig::hold`].
+    CoolerSaturated {
+        /// Time since recording started.
+        elapsed: Duration,
+        /// Cooler drive level, in percent.
+        power: Percent,
+        /// Sensor temperature in °C.
+        temperature: Celsius,
+        /// Cooler set point in °C.
+        target: Celsius,
+    },
+    /// A saturated cooler has caught up again.
+    CoolerRecovered {
+        /// Time since recording started.
+        elapsed: Duration,
+        /// Sensor temperature in °C.
+        temperature: Celsius,
+    },
+}
+
+/// One set of readings.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Sample {
+    /// Time since recording started.
+    pub elapsed: Duration,
+    /// Wall-clock time of the sample.
+    pub time: SystemTime,
+    /// Raw control values, in the order of [`TelemetryLog::columns`];
+    /// `None` where the read failed.
+    pub values: Vec<Option<i64>>,
+}
+
+/// Recorded time series.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TelemetryLog {
+    /// Recorded controls.
+    pub columns: Vec<ControlType>,
+    /// Samples, oldest first.
+    pub samples: Vec<Sample>,
+    /// Alerts raised while recording, in the order they occurred.
+    pub alerts: Vec<Alert>,
+}
+
+impl TelemetryLog {
+    fn new(columns: Vec<ControlType>) -> Self {
+        Self {
+            columns,
+            ..Self::default()
+        }
+    }
+
+    /// Returns the raw readings of `ctrl` as `(elapsed, value)` pairs,
+    /// skipping failed reads.
+    pub fn series(&self, ctrl: ControlType) -> Vec<(Duration, i64)> {
+        let Some(col) = self.columns.iter().position(|&c| c == ctrl) else {
+            return Vec::new();
+        };
+        self.samples
+            .iter()
+            .filter_map(|s| Some((s.elapsed, s.values[col]?)))
+            .collect()
+    }
+
+    /// Returns the sensor temperature readings.
+    pub fn temperatures(&self) -> Vec<(Duration, Celsius)> {
+        self.series(ControlType::CurrentTemperature)
+            .into_iter()
+            .map(|(t, v)| (t, Celsius::from_tenths(v)))
+            .collect()
+    }
+
+    /// Returns the cooler power readings.
+    pub fn cooler_power(&self) -> Vec<(Duration, Percent)> {
+        self.series(ControlType::CoolerPower)
+            .into_iter()
+            .map(|(t, v)| (t, Percent::from_raw(v)))
+            .collect()
+    }
+
+    /// Writes the samples as CSV with a header row.
+    ///
+    /// The first columns are `elapsed_s` and `unix_time_s`, followed by one
+    /// column per control named after its [`ControlType`]. Temperatures are
+    /// in °C, other controls in raw SDK units; failed reads are left empty.
+    pub fn write_csv(&self, mut w: impl Write) -> io::Result<()> {
+        write!(w, "elapsed_s,unix_time_s")?;
+        for c in &self.columns {
+            write!(w, ",{c:?}")?;
+        }
+        writeln!(w)?;
+        for s in &self.samples {
+            write!(
+                w,
+                "{:.3},{:.3}",
+                s.elapsed.as_secs_f64(),
+                unix_seconds(s.time)
+            )?;
+            for (&c, v) in self.columns.iter().zip(&s.values) {
+                match v {
+                    Some(v) => write!(w, ",{}", format_value(c, *v))?,
+                    None => write!(w, ",")?,
+                }
+            }
+            writeln!(w)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the log as a JSON object with `columns`, `samples` and
+    /// `alerts` arrays, using the same units as
+    /// [`write_csv`](Self::write_csv).
+    pub fn write_json(&self, mut w: impl Write) -> io::Result<()> {
+        write!(w, "{{\"columns\":[")?;
+        for (i, c) in self.columns.iter().enumerate() {
+            write!(w, "{}\"{c:?}\"", sep(i))?;
+        }
+        write!(w, "],\"samples\":[")?;
+        for (i, s) in self.samples.iter().enumerate() {
+            write!(
+                w,
+                "{}{{\"elapsed_s\":{:.3},\"unix_time_s\":{:.3},\"values\":{{",
+                sep(i),
+                s.elapsed.as_secs_f64(),
+                unix_seconds(s.time)
+            )?;
+            for (j, (&c, v)) in self.columns.iter().zip(&s.values).enumerate() {
+                match v {
+                    Some(v) => write!(w, "{}\"{c:?}\":{}", sep(j), format_value(c, *v))?,
+                    None => write!(w, "{}\"{c:?}\":null", sep(j))?,
+                }
+            }
+            write!(w, "}}}}")?;
+        }
+        write!(w, "],\"alerts\":[")?;
+        for (i, a) in self.alerts.iter().enumerate() {
+            match a {
+                Alert::CoolerSaturated {
+                    elapsed,
+                    power,
+                    temperature,
+                    target,
+                } => write!(
+                    w,
+                    "{}{{\"kind\":\"CoolerSaturated\",\"elapsed_s\":{:.3},\"power\":{},\
+                     \"temperature\":{:.1},\"target\":{:.1}}}",
+                    sep(i),
+                    elapsed.as_secs_f64(),
+                    power.0,
+                    temperature.0,
+                    target.0
+                )?,
+                Alert::CoolerRecovered {
+                    elapsed,
+                    temperature,
+                } => write!(
+                    w,
+                    "{}{{\"kind\":\"CoolerRecovered\",\"elapsed_s\":{:.3},\"temperature\":{:.1}}}",
+                    sep(i),
+                    elapsed.as_secs_f64(),
+                    temperature.0
+                )?,
+            }
+        }
+        write!(w, "]}}")
+    }
+}
+
+fn sep(i: usize) -> &'static str {
+    if i == 0 {
+        ""
+    } else {
+        ","
+    }
+}
+
+fn unix_seconds(t: SystemTime) -> f64 {
+    t.duration_since(UNIX_EPOCH)
+        .map_or(0.0, |d| d.as_secs_f64())
+}
+
+fn format_value(ctrl: ControlType, v: i64) -> String {
+    match ctrl {
+        ControlType::CurrentTemperature | ControlType::TargetTemperature => {
+            format!("{:.1}", Celsius::from_tenths(v).0)
+        }
+        _ => v.to_string(),
+    }
+}
+
+/// Detects a cooler that runs saturated without reaching its set point.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaturationDetector {
+    config: SaturationConfig,
+    since: Option<Duration>,
+    alerted: bool,
+}
+
+impl SaturationDetector {
+    /// Creates a detector that has not seen any readings.
+    pub fn new(config: SaturationConfig) -> Self {
+        Self {
+            config,
+            since: None,
+            alerted: false,
+        }
+    }
+
+    /// Feeds one reading taken `elapsed` after the start and returns the
+    /// alert it triggers, if any.
+    pub fn update(
+        &mut self,
+        elapsed: Duration,
+        power: Percent,
+        temperature: Celsius,
+        target: Celsius,
+    ) -> Option<Alert> {
+        let saturated =
+            power >= self.config.power && temperature.0 - target.0 > self.config.min_error;
+        if !saturated {
+            self.since = None;
+            if self.alerted {
+                self.alerted = false;
+                return Some(Alert::CoolerRecovered {
+                    elapsed,
+                    temperature,
+                });
+            }
+            return None;
+        }
+        let since = *self.since.get_or_insert(elapsed);
+        if !self.alerted && elapsed.saturating_sub(since) >= self.config.hold {
+            self.alerted = true;
+            return Some(Alert::CoolerSaturated {
+                elapsed,
+                power,
+                temperature,
+                target,
+            });
+        }
+        None
+    }
+}
+
+/// Records telemetry on a background thread until stopped or dropped.
+pub struct TelemetryRecorder {
+    log: Arc<Mutex<TelemetryLog>>,
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl TelemetryRecorder {
+    /// Starts recording from `camera`.
+    ///
+    /// The first sample is taken immediately. `on_alert` is called on the
+    /// recorder thread for every [`Alert`]; alerts are also kept in the log.
+    ///
+    /// Returns [`Error::InvalidArgument`] for a zero interval.
+    pub fn start(
+        camera: Arc<Camera>,
+        config: TelemetryConfig,
+        mut on_alert: impl FnMut(&Alert) + Send + 'static,
+    ) -> Result<Self> {
+        validate_config(&config)?;
+        let columns = config.controls.clone().unwrap_or_else(|| {
+            camera
+                .supported_controls()
+                .iter()
+                .filter(|c| {
+                    !c.is_writable
+                        || matches!(
+                            c.control_type,
+                            ControlType::TargetTemperature | ControlType::CoolerEnable
+                        )
+                })
+                .map(|c| c.control_type)
+                .collect()
+        });
+        let log = Arc::new(Mutex::new(TelemetryLog::new(columns.clone())));
+        let (stop, stopped) = mpsc::channel();
+
+        let shared = Arc::clone(&log);
+        let thread = thread::spawn(move || {
+            let start = Instant::now();
+            let mut detector = SaturationDetector::new(config.saturation);
+            let mut next = start;
+            loop {
+                let sample = Sample {
+                    elapsed: start.elapsed(),
+                    time: SystemTime::now(),
+                    values: columns
+                        .iter()
+                        .map(|&c| camera.get_control(c).ok().map(|(v, _)| v))
+                        .collect(),
+                };
+                let alert = saturation_inputs(&columns, &sample.values).and_then(
+                    |(power, temperature, target)| {
+                        detector.update(sample.elapsed, power, temperature, target)
+                    },
+                );
+
+                {
+                    let mut log = shared.lock().unwrap();
+                    log.samples.push(sample);
+                    if let Some(cap) = config.capacity {
+                        let excess = log.samples.len().saturating_sub(cap);
+                        log.samples.drain(..excess);
+                    }
+                    if let Some(alert) = alert {
+                        log.alerts.push(alert);
+                    }
+                }
+                if let Some(alert) = alert {
+                    on_alert(&alert);
+                }
+
+                next += config.interval;
+                let wait = next.saturating_duration_since(Instant::now());
+                match stopped.recv_timeout(wait) {
+                    Err(RecvTimeoutError::Timeout) => {}
+                    // Stop requested or recorder dropped.
+                    _ => break,
+                }
+            }
+        });
+
+        Ok(Self {
+            log,
+            stop: Some(stop),
+            thread: Some(thread),
+        })
+    }
+
+    /// Returns a copy of everything recorded so far.
+    pub fn log(&self) -> TelemetryLog {
+        self.log.lock().unwrap().clone()
+    }
+
+    /// Stops the recorder thread and returns the recorded log.
+    pub fn stop(mut self) -> TelemetryLog {
+        self.shutdown();
+        self.log()
+    }
+
+    fn shutdown(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for TelemetryRecorder {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Extracts cooler power, sensor temperature and set point from a sample,
+/// if all were recorded and the cooler is not reported as off.
+fn saturation_inputs(
+    columns: &[ControlType],
+    values: &[Option<i64>],
+) -> Option<(Percent, Celsius, Celsius)> {
+    let get = |ctrl| {
+        let col = columns.iter().position(|&c| c == ctrl)?;
+        values[col]
+    };
+    if get(ControlType::CoolerEnable) == Some(0) {
+        return None;
+    }
+    Some((
+        Percent::from_raw(get(ControlType::CoolerPower)?),
+        Celsius::from_tenths(get(ControlType::CurrentTemperature)?),
+        Celsius::from_tenths(get(ControlType::TargetTemperature)?),
+    ))
+}
+
+fn validate_config(config: &TelemetryConfig) -> Result<()> {
+    if config.interval.is_zero() {
+        return Err(Error::InvalidArgument(
+            "telemetry interval must not be zero".into(),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log() -> TelemetryLog {
+        let columns = vec![
+            ControlType::CurrentTemperature,
+            ControlType::CoolerPower,
+            ControlType::TargetTemperature,
+        ];
+        let mut log = TelemetryLog::new(columns);
+        log.samples = vec![
+            Sample {
+                elapsed: Duration::ZERO,
+                time: UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+                values: vec![Some(215), Some(0), Some(-100)],
+            },
+            Sample {
+                elapsed: Duration::from_secs(10),
+                time: UNIX_EPOCH + Duration::from_secs(1_700_000_010),
+                values: vec![Some(198), None, Some(-100)],
+            },
+        ];
+        log.alerts = vec![Alert::CoolerRecovered {
+            elapsed: Duration::from_secs(10),
+            temperature: Celsius(-9.5),
+        }];
+        log
+    }
+
+    #[test]
+    fn series_accessors() {
+        let log = log();
+        assert_eq!(
+            log.temperatures(),
+            [
+                (Duration::ZERO, Celsius(21.5)),
+                (Duration::from_secs(10), Celsius(19.8))
+            ]
+        );
+        assert_eq!(log.cooler_power(), [(Duration::ZERO, Percent(0))]);
+        assert!(log.series(ControlType::Gain).is_empty());
+    }
+
+    #[test]
+    fn csv_export() {
+        let mut out = Vec::new();
+        log().write_csv(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "elapsed_s,unix_time_s,CurrentTemperature,CoolerPower,TargetTemperature\n\
+             0.000,1700000000.000,21.5,0,-10.0\n\
+             10.000,1700000010.000,19.8,,-10.0\n"
+        );
+    }
+
+    #[test]
+    fn json_export_is_valid() {
+        let mut out = Vec::new();
+        log().write_json(&mut out).unwrap();
+        let v: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(v["columns"][1], "CoolerPower");
+        assert_eq!(v["samples"][0]["values"]["CurrentTemperature"], 21.5);
+        assert!(v["samples"][1]["values"]["CoolerPower"].is_null());
+        assert_eq!(v["samples"][1]["unix_time_s"], 1_700_000_010.0);
+        assert_eq!(v["alerts"][0]["kind"], "CoolerRecovered");
+        assert_eq!(v["alerts"][0]["temperature"], -9.5);
+
+        let mut empty = Vec::new();
+        TelemetryLog::default().write_json(&mut empty).unwrap();
+        assert_eq!(empty, b"{\"columns\":[],\"samples\":[],\"alerts\":[]}");
+    }
+
+    #[test]
+    fn saturation_alert_needs_hold_and_recovers() {
+        let mut d = SaturationDetector::new(SaturationConfig {
+            power: Percent(98),
+            min_error: 1.0,
+            hold: Duration::from_secs(60),
+        });
+        let s = Duration::from_secs;
+        let target = Celsius(-10.0);
+        assert_eq!(d.update(s(0), Percent(100), Celsius(-5.0), target), None);
+        // Within min_error of the set point: not saturated, restarts hold.
+        assert_eq!(d.update(s(30), Percent(100), Celsius(-9.5), target), None);
+        assert_eq!(d.update(s(40), Percent(99), Celsius(-7.0), target), None);
+        assert_eq!(d.update(s(90), Percent(99), Celsius(-7.0), target), None);
+        assert!(matches!(
+            d.update(s(100), Percent(100), Celsius(-7.0), target),
+            Some(Alert::CoolerSaturated {
+                power: Percent(100),
+                ..
+            })
+        ));
+        // Raised only once while the condition lasts.
+        assert_eq!(d.update(s(200), Percent(100), Celsius(-7.0), target), None);
+        assert!(matches!(
+            d.update(s(210), Percent(80), Celsius(-9.8), target),
+            Some(Alert::CoolerRecovered { .. })
+        ));
+        assert_eq!(d.update(s(220), Percent(80), Celsius(-9.8), target), None);
+    }
+
+    #[test]
+    fn saturation_inputs_skip_when_cooler_off() {
+        let columns = [
+            ControlType::CoolerEnable,
+            ControlType::CoolerPower,
+            ControlType::CurrentTemperature,
+            ControlType::TargetTemperature,
+        ];
+        assert_eq!(
+            saturation_inputs(&columns, &[Some(1), Some(100), Some(-50), Some(-100)]),
+            Some((Percent(100), Celsius(-5.0), Celsius(-10.0)))
+        );
+        assert_eq!(
+            saturation_inputs(&columns, &[Some(0), Some(100), Some(-50), Some(-100)]),
+            None
+        );
+        assert_eq!(
+            saturation_inputs(&columns, &[Some(1), None, Some(-50), Some(-100)]),
+            None
+        );
+    }
+
+    #[test]
+    fn zero_interval_is_rejected() {
+        let config = TelemetryConfig {
+            interval: Duration::ZERO,
+            ..TelemetryConfig::default()
+        };
+        assert!(matches!(
+            validate_config(&config),
+            Err(Error::InvalidArgument(_))
+        ));
+        assert!(validate_config(&TelemetryConfig::default()).is_ok());
+    }
+}
@@ -0,0 +1,226 @@
+//! Typed trigger output pin configuration.
+
+use std::time::Duration;
+
+use crate::{Camera, Error, Result, TrigOutputPin};
+
+/// Largest delay or pulse duration the SDK accepts, in microseconds.
+pub const MAX_TRIGGER_OUTPUT_US: u64 = 2_000_000_000;
+
+/// Level of a trigger output pin while its pulse is active.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TriggerPolarity {
+    /// The pin is driven high during the pulse.
+    ActiveHigh,
+    /// The pin is driven low during the pulse.
+    ActiveLow,
+}
+
+/// Pulse emitted on a trigger output pin for each exposure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TriggerOutputConfig {
+    /// Level of the pin during the pulse.
+    pub polarity: TriggerPolarity,
+    /// Delay from the start of the exposure to the start of the pulse.
+    pub delay: Duration,
+    /// Pulse length. Must be non-zero; use [`TriggerOutput::Disabled`] for no
+    /// pulse.
+    pub duration: Duration,
+}
+
+impl TriggerOutputConfig {
+    /// Checks that delay and duration are whole microseconds within
+    /// `0..=`[`MAX_TRIGGER_OUTPUT_US`] and that the duration is non-zero.
+    pub fn validate(&self) -> Result<()> {
+        let duration = micros("duration", self.duration)?;
+        micros("delay", self.delay)?;
+        if duration == 0 {
+            return Err(Error::InvalidArgument(
+                "trigger output duration must be non-zero (use TriggerOutput::Disabled)".into(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// State of one trigger output pin.
+///
+/// The SDK has no separate enable flag: a pin configured with a zero pulse
+/// duration never fires, and that is how [`Disabled`](Self::Disabled) is
+/// written and recognised.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TriggerOutput {
+    /// The pin never fires.
+    #[default]
+    Disabled,
+    /// The pin emits this pulse for each exposure.
+    Enabled(TriggerOutputConfig),
+}
+
+impl TriggerOutput {
+    /// Converts the SDK's `(active_high, delay_us, duration_us)`.
+    pub fn from_raw(active_high: bool, delay_us: i64, duration_us: i64) -> Self {
+        if duration_us <= 0 {
+            return Self::Disabled;
+        }
+        Self::Enabled(TriggerOutputConfig {
+            polarity: if active_high {
+                TriggerPolarity::ActiveHigh
+            } else {
+                TriggerPolarity::ActiveLow
+            },
+            delay: Duration::from_micros(delay_us.max(0) as u64),
+            duration: Duration::from_micros(duration_us as u64),
+        })
+    }
+
+    /// Converts to the SDK's `(active_high, delay_us, duration_us)` after
+    /// validating the configuration.
+    pub fn to_raw(&self) -> Result<(bool, i64, i64)> {
+        match self {
+            Self::Disabled => Ok((true, 0, 0)),
+            Self::Enabled(config) => {
+                config.validate()?;
+                Ok((
+                    config.polarity == TriggerPolarity::ActiveHigh,
+                    config.delay.as_micros() as i64,
+                    config.duration.as_micros() as i64,
+                ))
+            }
+        }
+    }
+
+    /// Returns `true` if the pin fires.
+    pub fn is_enabled(&self) -> bool {
+        matches!(self, Self::Enabled(_))
+    }
+}
+
+/// Configuration of both trigger output pins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TriggerOutputs {
+    /// Output pin A.
+    pub pin_a: TriggerOutput,
+    /// Output pin B.
+    pub pin_b: TriggerOutput,
+}
+
+fn micros(what: &str, d: Duration) -> Result<u64> {
+    let us = d.as_micros();
+    if d.subsec_nanos() % 1000 != 0 || us > u128::from(MAX_TRIGGER_OUTPUT_US) {
+        return Err(Error::InvalidArgument(format!(
+            "trigger output {what} {d:?} is not a whole number of microseconds \
+             in 0..={MAX_TRIGGER_OUTPUT_US} µs"
+        )));
+    }
+    Ok(us as u64)
+}
+
+fn check_pin(pin: TrigOutputPin) -> Result<()> {
+    match pin {
+        TrigOutputPin::PinA | TrigOutputPin::PinB => Ok(()),
+        other => Err(Error::InvalidArgument(format!(
+            "{other:?} is not a trigger output pin"
+        ))),
+    }
+}
+
+impl Camera {
+    /// Reads the configuration of trigger output `pin`.
+    pub fn trigger_output(&self, pin: TrigOutputPin) -> Result<TriggerOutput> {
+        check_pin(pin)?;
+        let (high, delay, duration) = self.get_trigger_output(pin)?;
+        Ok(TriggerOutput::from_raw(high, delay, duration))
+    }
+
+    /// Configures trigger output `pin`.
+    ///
+    /// Returns [`Error::InvalidArgument`] for [`TrigOutputPin::None`] /
+    /// [`TrigOutputPin::Other`] or an invalid [`TriggerOutputConfig`].
+    pub fn configure_trigger_output(
+        &self,
+        pin: TrigOutputPin,
+        output: TriggerOutput,
+    ) -> Result<()> {
+        check_pin(pin)?;
+        let (high, delay, duration) = output.to_raw()?;
+        self.set_trigger_output(pin, high, delay, duration)
+    }
+
+    /// Reads the configuration of both trigger output pins.
+    pub fn trigger_outputs(&self) -> Result<TriggerOutputs> {
+        Ok(TriggerOutputs {
+            pin_a: self.trigger_output(TrigOutputPin::PinA)?,
+            pin_b: self.trigger_output(TrigOutputPin::PinB)?,
+        })
+    }
+
+    /// Configures both trigger output pins. Both configurations are
+    /// validated before either pin is written.
+    pub fn set_trigger_outputs(&self, outputs: &TriggerOutputs) -> Result<()> {
+        outputs.pin_a.to_raw()?;
+        outputs.pin_b.to_raw()?;
+        self.configure_trigger_output(TrigOutputPin::PinA, outputs.pin_a)?;
+        self.configure_trigger_output(TrigOutputPin::PinB, outputs.pin_b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strobe(delay_us: u64, duration_us: u64) -> TriggerOutput {
+        TriggerOutput::Enabled(TriggerOutputConfig {
+            polarity: TriggerPolarity::ActiveLow,
+            delay: Duration::from_micros(delay_us),
+            duration: Duration::from_micros(duration_us),
+        })
+    }
+
+    #[test]
+    fn raw_round_trip() {
+        let out = strobe(1500, 20_000);
+        assert_eq!(out.to_raw().unwrap(), (false, 1500, 20_000));
+        assert_eq!(TriggerOutput::from_raw(false, 1500, 20_000), out);
+
+        assert_eq!(TriggerOutput::Disabled.to_raw().unwrap(), (true, 0, 0));
+        assert_eq!(
+            TriggerOutput::from_raw(true, 100, 0),
+            TriggerOutput::Disabled
+        );
+        assert!(!TriggerOutput::default().is_enabled());
+    }
+
+    #[test]
+    fn range_is_validated() {
+        assert!(strobe(0, MAX_TRIGGER_OUTPUT_US).to_raw().is_ok());
+        for bad in [
+            strobe(MAX_TRIGGER_OUTPUT_US + 1, 10),
+            strobe(0, MAX_TRIGGER_OUTPUT_US + 1),
+            strobe(0, 0),
+        ] {
+            assert!(
+                matches!(bad.to_raw(), Err(Error::InvalidArgument(_))),
+                "{bad:?}"
+            );
+        }
+        let sub_micro = TriggerOutput::Enabled(TriggerOutputConfig {
+            polarity: TriggerPolarity::ActiveHigh,
+            delay: Duration::from_nanos(1500),
+            duration: Duration::from_millis(1),
+        });
+        assert!(sub_micro.to_raw().is_err());
+    }
+
+    #[test]
+    fn only_real_pins_are_accepted() {
+        assert!(check_pin(TrigOutputPin::PinA).is_ok());
+        assert!(check_pin(TrigOutputPin::PinB).is_ok());
+        assert!(check_pin(TrigOutputPin::None).is_err());
+        assert!(check_pin(TrigOutputPin::Other(5)).is_err());
+    }
+}
@@ -0,0 +1,262 @@
+//! Acquisition of externally triggered frames.
+//!
+//! [`Camera::start_triggered`] switches the camera to a trigger
+//! [`CameraMode`], arms video capture and returns a [`TriggeredCapture`]
+//! that waits for frames as triggers arrive. Each frame is stamped with the
+//! host time it was received and a trigger counter that includes triggers
+//! the camera reported as dropped. The camera is returned to
+//! [`CameraMode::Normal`] when the capture is finished or dropped.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use svbony::{Camera, CameraMode};
+//!
+//! let cam = Camera::open(0)?;
+//! let mut capture = cam.start_triggered(CameraMode::TrigRiseEdge)?;
+//! while let Some(shot) = capture.next_frame(Duration::from_secs(30))? {
+//!     println!(
+//!         "trigger {} at {:?}, {} missed so far",
+//!         shot.trigger, shot.elapsed, capture.stats().missed
+//!     );
+//! }
+//! let stats = capture.finish()?;
+//! println!("{} frames, {} missed", stats.received, stats.missed);
+//! # Ok::<(), svbony::Error>(())
+//! ```
+
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::{Camera, CameraMode, Error, Frame, ImageType, Result, RoiFormat};
+
+/// A frame received in trigger mode.
+#[derive(Debug, Clone)]
+pub struct TriggeredFrame {
+    /// The image data.
+    pub frame: Frame,
+    /// 1-based number of the trigger that produced this frame, counting
+    /// triggers whose frames were dropped.
+    pub trigger: u64,
+    /// Triggers dropped between the previous frame and this one.
+    pub missed_before: u64,
+    /// Host wall-clock time the frame was received.
+    pub received_at: SystemTime,
+    /// Time from arming to receiving the frame.
+    pub elapsed: Duration,
+}
+
+/// Counters of a [`TriggeredCapture`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TriggerStats {
+    /// Frames received.
+    pub received: u64,
+    /// Frames the camera reported as dropped.
+    pub missed: u64,
+}
+
+impl TriggerStats {
+    /// Total triggers seen: received plus missed.
+    pub fn triggers(&self) -> u64 {
+        self.received + self.missed
+    }
+}
+
+/// Returns `true` for modes in which frames are started by a trigger.
+pub fn is_trigger_mode(mode: CameraMode) -> bool {
+    matches!(
+        mode,
+        CameraMode::TrigSoft
+            | CameraMode::TrigRiseEdge
+            | CameraMode::TrigFallEdge
+            | CameraMode::TrigDoubleEdge
+            | CameraMode::TrigHighLevel
+            | CameraMode::TrigLowLevel
+    )
+}
+
+/// Turns readings of [`Camera::dropped_frames`] into per-frame deltas.
+///
+/// The SDK counter resets when capture starts; readings that go backwards
+/// are treated as a reset rather than a negative delta.
+#[derive(Debug, Clone, Default)]
+struct DropCounter {
+    last: i32,
+}
+
+impl DropCounter {
+    fn update(&mut self, dropped: i32) -> u64 {
+        let delta = if dropped >= self.last {
+            dropped - self.last
+        } else {
+            dropped.max(0)
+        };
+        self.last = dropped;
+        delta as u64
+    }
+}
+
+/// An armed trigger-mode capture; see the [module docs](self).
+pub struct TriggeredCapture<'a> {
+    camera: &'a Camera,
+    roi: RoiFormat,
+    image_type: ImageType,
+    armed_at: Instant,
+    drops: DropCounter,
+    stats: TriggerStats,
+    finished: bool,
+}
+
+impl Camera {
+    /// Switches to trigger `mode` and starts capture.
+    ///
+    /// Any running capture is stopped first. Returns [`Error::InvalidMode`]
+    /// if `mode` is not a trigger mode and [`Error::Unsupported`] if the
+    /// camera does not list it in its supported modes.
+    pub fn start_triggered(&self, mode: CameraMode) -> Result<TriggeredCapture<'_>> {
+        if !is_trigger_mode(mode) {
+            return Err(Error::InvalidMode);
+        }
+        if !self.capabilities()?.supports_trigger(mode) {
+            return Err(Error::Unsupported(format!("trigger mode {mode:?}")));
+        }
+        let image_type = self.output_image_type()?;
+        if let ImageType::Other(_) = image_type {
+            return Err(Error::InvalidImageType);
+        }
+        let roi = self.roi()?;
+
+        // Fails harmlessly when capture is not running.
+        let _ = self.stop_capture();
+        self.set_mode(mode)?;
+        // From here on, dropping the guard restores normal mode.
+        let mut capture = TriggeredCapture {
+            camera: self,
+            roi,
+            image_type,
+            armed_at: Instant::now(),
+            drops: DropCounter::default(),
+            stats: TriggerStats::default(),
+            finished: false,
+        };
+        self.start_capture()?;
+        capture.armed_at = Instant::now();
+        Ok(capture)
+    }
+}
+
+impl<'a> TriggeredCapture<'a> {
+    /// Waits up to `timeout` for the next triggered frame.
+    ///
+    /// Returns `Ok(None)` if no trigger arrived in time.
+    pub fn next_frame(&mut self, timeout: Duration) -> Result<Option<TriggeredFrame>> {
+        let wait_ms = timeout.as_millis().min(i32::MAX as u128) as i32;
+        let mut frame = Frame::zeroed(self.roi, self.image_type);
+        match self.camera.get_frame(&mut frame.data, wait_ms) {
+            Ok(()) => {}
+            Err(Error::Timeout) => return Ok(None),
+            Err(e) => return Err(e),
+        }
+        let received_at = SystemTime::now();
+        let elapsed = self.armed_at.elapsed();
+
+        let missed_before = self
+            .camera
+            .dropped_frames()
+            .map_or(0, |d| self.drops.update(d));
+        self.stats.missed += missed_before;
+        self.stats.received += 1;
+        Ok(Some(TriggeredFrame {
+            frame,
+            trigger: self.stats.triggers(),
+            missed_before,
+            received_at,
+            elapsed,
+        }))
+    }
+
+    /// Returns an iterator over frames that ends at the first wait longer
+    /// than `timeout`.
+    pub fn frames(&mut self, timeout: Duration) -> TriggeredFrames<'_, 'a> {
+        TriggeredFrames {
+            capture: self,
+            timeout,
+        }
+    }
+
+    /// Sends a software trigger, for [`CameraMode::TrigSoft`].
+    pub fn soft_trigger(&self) -> Result<()> {
+        self.camera.send_soft_trigger()
+    }
+
+    /// Returns the counters so far.
+    pub fn stats(&self) -> TriggerStats {
+        self.stats
+    }
+
+    /// Stops capture, restores [`CameraMode::Normal`] and returns the final
+    /// counters.
+    pub fn finish(mut self) -> Result<TriggerStats> {
+        self.finished = true;
+        let stopped = self.camera.stop_capture();
+        self.camera.set_mode(CameraMode::Normal)?;
+        stopped?;
+        Ok(self.stats)
+    }
+}
+
+/// Iterator returned by [`TriggeredCapture::frames`].
+pub struct TriggeredFrames<'c, 'a> {
+    capture: &'c mut TriggeredCapture<'a>,
+    timeout: Duration,
+}
+
+impl Iterator for TriggeredFrames<'_, '_> {
+    type Item = Result<TriggeredFrame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.capture.next_frame(self.timeout).transpose()
+    }
+}
+
+impl Drop for TriggeredCapture<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = self.camera.stop_capture();
+            let _ = self.camera.set_mode(CameraMode::Normal);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trigger_modes() {
+        assert!(!is_trigger_mode(CameraMode::Normal));
+        assert!(!is_trigger_mode(CameraMode::Other(9)));
+        for raw in 1..=6 {
+            assert!(is_trigger_mode(CameraMode::from(raw)));
+        }
+    }
+
+    #[test]
+    fn drop_counter_deltas() {
+        let mut d = DropCounter::default();
+        assert_eq!(d.update(0), 0);
+        assert_eq!(d.update(2), 2);
+        assert_eq!(d.update(2), 0);
+        assert_eq!(d.update(5), 3);
+        // Counter reset by the SDK.
+        assert_eq!(d.update(1), 1);
+        assert_eq!(d.update(-1), 0);
+    }
+
+    #[test]
+    fn stats_count_triggers() {
+        let stats = TriggerStats {
+            received: 10,
+            missed: 3,
+        };
+        assert_eq!(stats.triggers(), 13);
+    }
+}
@@ -1,7 +1,8 @@
 //! Safe Rust types mirroring the C SDK structs and enums.
 //!
 //! All types drop the `SVB_` prefix since the `svbony::` namespace provides it.
-//! Enums implement [`TryFrom<c_int>`] for conversion from raw SDK values, and
+//! Enums implement [`From<c_int>`] for conversion from raw SDK values, keeping
+//! unknown values as an `Other(raw)` variant, and `raw()` for the reverse;
 //! structs implement [`From`] for conversion from their `svbony_sys` counterparts.
 
 use std::ffi::CStr;
@@ -18,6 +19,10 @@ fn chars_to_string(buf: &[c_char]) -> String {
 // Enums
 // ---------------------------------------------------------------------------
 
+/// Defines an enum over SDK integer values.
+///
+/// Values this crate does not know (e.g. added by a newer SDK) are kept as
+/// `Other(raw)` so they survive a round trip instead of being lost.
 macro_rules! c_enum {
     (
         $(#[$meta:meta])*
@@ -27,17 +32,28 @@ macro_rules! c_enum {
     ) => {
         $(#[$meta])*
         #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-        #[repr(i32)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
         $vis enum $name {
-            $($(#[$vmeta])* $variant = $val),+
+            $($(#[$vmeta])* $variant,)+
+            /// A value this crate does not recognise (e.g. from a newer SDK).
+            Other(i32),
         }
 
-        impl TryFrom<c_int> for $name {
-            type Error = crate::Error;
-            fn try_from(v: c_int) -> crate::Result<Self> {
+        impl $name {
+            /// Returns the raw SDK value.
+            pub fn raw(self) -> c_int {
+                match self {
+                    $(Self::$variant => $val,)+
+                    Self::Other(v) => v as c_int,
+                }
+            }
+        }
+
+        impl From<c_int> for $name {
+            fn from(v: c_int) -> Self {
                 match v {
-                    $($val => Ok(Self::$variant),)+
-                    _ => Err(crate::Error::Unknown(v as i32)),
+                    $($val => Self::$variant,)+
+                    _ => Self::Other(v as i32),
                 }
             }
         }
@@ -49,7 +65,8 @@ c_enum! {
     ///
     /// The "Raw" variants deliver Bayer-mosaic data; the "Y" variants deliver
     /// debayered luminance. Formats wider than 8 bits are packed into 16-bit
-    /// (little-endian) containers.
+    /// (little-endian) containers. The color formats are blue-first; see
+    /// [`ImageType::channel_order`].
     pub enum ImageType {
         Raw8 = 0,
         Raw10 = 1,
@@ -61,7 +78,9 @@ c_enum! {
         Y12 = 7,
         Y14 = 8,
         Y16 = 9,
+        /// 8-bit color, 3 bytes per pixel in BGR order.
         Rgb24 = 10,
+        /// 8-bit color, 4 bytes per pixel in BGRA order.
         Rgb32 = 11,
     }
 }
@@ -84,14 +103,16 @@ c_enum! {
     /// Adjustable camera control.
     pub enum ControlType {
         Gain = 0,
+        /// In microseconds.
         Exposure = 1,
         Gamma = 2,
         GammaContrast = 3,
         WbR = 4,
         WbG = 5,
         WbB = 6,
+        /// A [`FlipStatus`] value.
         Flip = 7,
-        /// 0 = low, 1 = medium, 2 = high.
+        /// A [`FrameSpeed`] value: 0 = low, 1 = medium, 2 = high.
         FrameSpeedMode = 8,
         Contrast = 9,
         Sharpness = 10,
@@ -112,6 +133,25 @@ c_enum! {
     }
 }
 
+c_enum! {
+    /// Sensor readout speed ([`ControlType::FrameSpeedMode`]).
+    pub enum FrameSpeed {
+        Low = 0,
+        Medium = 1,
+        High = 2,
+    }
+}
+
+c_enum! {
+    /// How binned pixels are combined ([`RoiFormatEx::bin_mode`]).
+    pub enum BinMode {
+        /// Mean of the combined pixels; keeps the unbinned brightness.
+        Average = 0,
+        /// Sum of the combined pixels; raises signal, may saturate.
+        Sum = 1,
+    }
+}
+
 c_enum! {
     /// Camera capture / trigger mode.
     pub enum CameraMode {
@@ -157,6 +197,8 @@ c_enum! {
     pub enum TrigOutputPin {
         PinA = 0,
         PinB = 1,
+        /// No output pin.
+        None = -1,
     }
 }
 
@@ -174,11 +216,40 @@ c_enum! {
     }
 }
 
+/// Byte order of the channels in an interleaved color buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChannelOrder {
+    /// Blue, green, red.
+    Bgr,
+    /// Blue, green, red, alpha.
+    Bgra,
+}
+
+impl ChannelOrder {
+    /// Returns the buffer offsets of the red, green and blue channels.
+    pub fn rgb_indices(&self) -> [usize; 3] {
+        match self {
+            Self::Bgr | Self::Bgra => [2, 1, 0],
+        }
+    }
+
+    /// Returns the buffer offset of the alpha channel, if there is one.
+    pub fn alpha_index(&self) -> Option<usize> {
+        match self {
+            Self::Bgra => Some(3),
+            Self::Bgr => None,
+        }
+    }
+}
+
 impl ImageType {
     /// Returns the number of bytes per pixel for this image type.
     ///
     /// For packed formats (Raw10/12/14, Y10/12/14) the SDK delivers
     /// 2 bytes per pixel (16-bit little-endian container), same as Raw16/Y16.
+    /// Returns 0 for [`Other`](Self::Other) formats, whose layout is unknown;
+    /// [`Frame`](crate::Frame) rejects them with
+    /// [`Error::InvalidImageType`](crate::Error::InvalidImageType).
     pub fn bytes_per_pixel(&self) -> usize {
         match self {
             Self::Raw8 | Self::Y8 => 1,
@@ -186,6 +257,54 @@ impl ImageType {
             Self::Y10 | Self::Y12 | Self::Y14 | Self::Y16 => 2,
             Self::Rgb24 => 3,
             Self::Rgb32 => 4,
+            Self::Other(_) => 0,
+        }
+    }
+
+    /// Returns the number of interleaved channels per pixel (1, 3 or 4).
+    pub fn channels(&self) -> usize {
+        match self {
+            Self::Rgb24 => 3,
+            Self::Rgb32 => 4,
+            _ => 1,
+        }
+    }
+
+    /// Returns the channel layout of the SDK buffer for color formats.
+    ///
+    /// Despite their names, the SDK delivers `Rgb24` as blue-green-red and
+    /// `Rgb32` as blue-green-red-alpha, the layout OpenCV and Windows DIBs
+    /// use. Use [`Frame::to_rgb`](crate::Frame::to_rgb) /
+    /// [`Frame::to_rgba`](crate::Frame::to_rgba) to obtain true RGB order.
+    /// Returns `None` for single-channel formats.
+    pub fn channel_order(&self) -> Option<ChannelOrder> {
+        match self {
+            Self::Rgb24 => Some(ChannelOrder::Bgr),
+            Self::Rgb32 => Some(ChannelOrder::Bgra),
+            _ => None,
+        }
+    }
+
+    /// Returns `true` for the Bayer-mosaic ("Raw") formats.
+    pub fn is_raw(&self) -> bool {
+        matches!(
+            self,
+            Self::Raw8 | Self::Raw10 | Self::Raw12 | Self::Raw14 | Self::Raw16
+        )
+    }
+}
+
+impl BayerPattern {
+    /// Returns the pattern's name as used by the FITS `BAYERPAT` keyword and
+    /// the INDI `CFA_TYPE` element, e.g. `"RGGB"`, or `None` for
+    /// [`Other`](Self::Other) patterns.
+    pub fn name(&self) -> Option<&'static str> {
+        match self {
+            Self::Rg => Some("RGGB"),
+            Self::Bg => Some("BGGR"),
+            Self::Gr => Some("GRBG"),
+            Self::Gb => Some("GBRG"),
+            Self::Other(_) => None,
         }
     }
 }
@@ -226,7 +345,8 @@ impl From<&svbony_sys::SVB_CAMERA_INFO> for CameraInfo {
 /// Static sensor properties (resolution, color, supported formats).
 ///
 /// Converted from [`svbony_sys::SVB_CAMERA_PROPERTY`].
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CameraProperty {
     /// Maximum sensor width in pixels.
     pub max_width: i64,
@@ -234,11 +354,12 @@ pub struct CameraProperty {
     pub max_height: i64,
     /// `true` if the sensor has a Bayer color filter.
     pub is_color: bool,
-    /// Bayer pattern layout (only meaningful when `is_color` is `true`).
-    pub bayer_pattern: BayerPattern,
+    /// Bayer pattern layout; `None` for mono sensors.
+    pub bayer_pattern: Option<BayerPattern>,
     /// Supported binning factors (e.g. `[1, 2]`).
     pub supported_bins: Vec<i32>,
-    /// Supported output pixel formats.
+    /// Supported output pixel formats, including any this crate does not
+    /// recognise as [`ImageType::Other`].
     pub supported_formats: Vec<ImageType>,
     /// Maximum ADC bit depth.
     pub max_bit_depth: i32,
@@ -260,14 +381,14 @@ impl From<&svbony_sys::SVB_CAMERA_PROPERTY> for CameraProperty {
             .iter()
             .copied()
             .take_while(|&f| f != svbony_sys::SVB_IMG_END)
-            .filter_map(|f| ImageType::try_from(f).ok())
+            .map(ImageType::from)
             .collect();
 
         Self {
             max_width: c.MaxWidth as i64,
             max_height: c.MaxHeight as i64,
             is_color: c.IsColorCam != 0,
-            bayer_pattern: BayerPattern::try_from(c.BayerPattern).unwrap_or(BayerPattern::Rg),
+            bayer_pattern: (c.IsColorCam != 0).then(|| BayerPattern::from(c.BayerPattern)),
             supported_bins,
             supported_formats,
             max_bit_depth: c.MaxBitDepth,
@@ -279,7 +400,8 @@ impl From<&svbony_sys::SVB_CAMERA_PROPERTY> for CameraProperty {
 /// Extended camera properties.
 ///
 /// Converted from [`svbony_sys::SVB_CAMERA_PROPERTY_EX`].
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CameraPropertyEx {
     /// `true` if the camera has an ST4 guide port.
     pub supports_pulse_guide: bool,
@@ -299,7 +421,8 @@ impl From<&svbony_sys::SVB_CAMERA_PROPERTY_EX> for CameraPropertyEx {
 /// Description of a single camera control's capabilities and range.
 ///
 /// Converted from [`svbony_sys::SVB_CONTROL_CAPS`].
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ControlCaps {
     /// Control name (e.g. "Gain", "Exposure").
     pub name: String,
@@ -329,13 +452,44 @@ impl From<&svbony_sys::SVB_CONTROL_CAPS> for ControlCaps {
             default_value: c.DefaultValue as i64,
             is_auto_supported: c.IsAutoSupported != 0,
             is_writable: c.IsWritable != 0,
-            control_type: ControlType::try_from(c.ControlType).unwrap_or(ControlType::Gain),
+            control_type: ControlType::from(c.ControlType),
         }
     }
 }
 
+impl ControlCaps {
+    /// Checks that `value` / `auto_` may be written to this control.
+    ///
+    /// Returns [`Error::ReadOnlyControl`](crate::Error::ReadOnlyControl),
+    /// [`Error::AutoNotSupported`](crate::Error::AutoNotSupported) or
+    /// [`Error::OutOfRange`](crate::Error::OutOfRange).
+    pub fn validate(&self, value: i64, auto_: bool) -> crate::Result<()> {
+        if !self.is_writable {
+            return Err(crate::Error::ReadOnlyControl(self.control_type));
+        }
+        if auto_ && !self.is_auto_supported {
+            return Err(crate::Error::AutoNotSupported(self.control_type));
+        }
+        if value < self.min_value || value > self.max_value {
+            return Err(crate::Error::OutOfRange {
+                control: self.control_type,
+                value,
+                min: self.min_value,
+                max: self.max_value,
+            });
+        }
+        Ok(())
+    }
+
+    /// Clamps `value` to `min_value..=max_value`.
+    pub fn clamp(&self, value: i64) -> i64 {
+        value.max(self.min_value).min(self.max_value)
+    }
+}
+
 /// Region of interest (ROI) and binning settings.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RoiFormat {
     /// Horizontal start offset in pixels.
     pub start_x: i32,
@@ -350,7 +504,8 @@ pub struct RoiFormat {
 }
 
 /// Extended ROI settings including binning mode.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RoiFormatEx {
     /// Horizontal start offset in pixels.
     pub start_x: i32,
@@ -362,6 +517,6 @@ pub struct RoiFormatEx {
     pub height: i32,
     /// Binning factor (1 = no binning, 2 = 2x2, etc.).
     pub bin: i32,
-    /// Binning mode: 0 = average, 1 = sum.
-    pub bin_mode: i32,
+    /// How binned pixels are combined.
+    pub bin_mode: BinMode,
 }
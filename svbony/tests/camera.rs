@@ -139,6 +139,25 @@ fn set_and_read_gain() {
     cam.stop_capture().expect("stop_capture");
 }
 
+#[test]
+#[ignore]
+fn gain_range_is_validated() {
+    let cam = open_first_camera();
+    let caps = cam.caps(ControlType::Gain).expect("gain caps").clone();
+    eprintln!("Gain range: {}..={}", caps.min_value, caps.max_value);
+
+    let err = cam
+        .set_control(ControlType::Gain, caps.max_value + 1, false)
+        .unwrap_err();
+    assert!(matches!(err, Error::OutOfRange { .. }), "{err}");
+
+    let applied = cam
+        .set_control_clamped(ControlType::Gain, caps.max_value + 1, false)
+        .expect("set_control_clamped");
+    eprintln!("Clamped gain applied: {applied}");
+    assert!(applied <= caps.max_value);
+}
+
 #[test]
 #[ignore]
 fn capture_raw_frame() {
@@ -266,6 +285,45 @@ fn camera_modes() {
     cam.set_mode(CameraMode::Normal).expect("set_mode Normal");
 }
 
+#[test]
+#[ignore]
+fn soft_triggered_capture() {
+    let cam = open_first_camera();
+    if !cam.capabilities().expect("capabilities").supports_trigger(CameraMode::TrigSoft) {
+        eprintln!("No soft trigger support, skipping");
+        return;
+    }
+    let mut capture = cam.start_triggered(CameraMode::TrigSoft).expect("start_triggered");
+    for i in 1..=3 {
+        capture.soft_trigger().expect("soft_trigger");
+        let shot = capture
+            .next_frame(std::time::Duration::from_secs(5))
+            .expect("next_frame")
+            .expect("frame after soft trigger");
+        assert!(shot.trigger >= i);
+    }
+    let stats = capture.finish().expect("finish");
+    assert_eq!(stats.received, 3);
+    assert_eq!(cam.mode().expect("mode"), CameraMode::Normal);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+#[ignore]
+fn settings_snapshot_round_trip() {
+    let cam = open_first_camera();
+    let snapshot = cam.snapshot_settings().expect("snapshot_settings");
+    let json = serde_json::to_string(&snapshot).expect("serialize");
+    let restored: CameraSettings = serde_json::from_str(&json).expect("deserialize");
+    assert_eq!(restored, snapshot);
+    cam.apply_settings(&restored).expect("apply_settings");
+    // Auto controls may drift between snapshots; compare the fixed parts.
+    let after = cam.snapshot_settings().expect("second snapshot");
+    assert_eq!(after.roi, snapshot.roi);
+    assert_eq!(after.image_type, snapshot.image_type);
+    assert_eq!(after.mode, snapshot.mode);
+}
+
 #[test]
 #[ignore]
 fn pulse_guide_support() {
@@ -277,6 +335,250 @@ fn pulse_guide_support() {
     assert_eq!(can, prop_ex.supports_pulse_guide);
 }
 
+#[test]
+#[ignore]
+fn capabilities_match_queries() {
+    let cam = open_first_camera();
+    let caps = cam.capabilities().expect("capabilities");
+    eprintln!("{caps:#?}");
+    assert_eq!(caps.property, cam.property().expect("property"));
+    assert_eq!(caps.has_st4(), cam.can_pulse_guide().expect("can_pulse_guide"));
+    assert_eq!(caps.controls.len(), cam.num_controls().expect("num_controls"));
+    assert_eq!(cam.capabilities().expect("cached capabilities"), caps);
+}
+
+#[test]
+#[ignore]
+fn telemetry_records_samples() {
+    let cam = std::sync::Arc::new(open_first_camera());
+    let config = svbony::telemetry::TelemetryConfig {
+        interval: std::time::Duration::from_millis(100),
+        ..Default::default()
+    };
+    let recorder = svbony::telemetry::TelemetryRecorder::start(cam, config, |_| {})
+        .expect("start telemetry");
+    std::thread::sleep(std::time::Duration::from_millis(550));
+    let log = recorder.stop();
+    eprintln!("columns: {:?}", log.columns);
+    assert!(log.samples.len() >= 5, "{} samples", log.samples.len());
+}
+
+#[test]
+#[ignore]
+fn async_guide_pulses() {
+    use std::time::Duration;
+    use svbony::guide::{GuidePulse, Guider, GuiderConfig};
+    use svbony::GuideDirection;
+
+    let cam = std::sync::Arc::new(open_first_camera());
+    let guider = Guider::new(cam, GuiderConfig::default()).expect("Guider::new");
+    let done = guider
+        .guide(
+            Some(GuidePulse::new(
+                GuideDirection::East,
+                Duration::from_millis(200),
+            )),
+            Some(GuidePulse::new(
+                GuideDirection::North,
+                Duration::from_millis(100),
+            )),
+        )
+        .expect("guide")
+        .wait()
+        .expect("wait");
+    assert!(done.ra.unwrap().is_complete());
+    assert!(done.dec.unwrap().is_complete());
+
+    let long = guider
+        .pulse(GuidePulse::new(
+            GuideDirection::West,
+            Duration::from_secs(2),
+        ))
+        .expect("pulse");
+    std::thread::sleep(Duration::from_millis(120));
+    long.cancel();
+    let outcome = long.wait().expect("wait");
+    eprintln!("cancelled after {:?}", outcome.delivered);
+    assert!(outcome.cancelled);
+}
+
+#[test]
+#[ignore]
+fn st4_port_records_pulses() {
+    use std::time::Duration;
+    use svbony::guide::{GuidePort, RecordingPort, St4Port};
+    use svbony::GuideDirection;
+
+    let cam = std::sync::Arc::new(open_first_camera());
+    let port = RecordingPort::wrapping(St4Port::new(cam).expect("St4Port::new"));
+    port.pulse(GuideDirection::South, Duration::from_millis(50))
+        .expect("pulse");
+    assert!(!port.is_pulsing());
+    assert_eq!(port.total(GuideDirection::South), Duration::from_millis(50));
+}
+
+#[cfg(feature = "indi")]
+#[test]
+#[ignore]
+fn indi_loopback_exposure() {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::time::Duration;
+    use svbony::indi::{IndiConfig, IndiServer};
+
+    let cam = std::sync::Arc::new(open_first_camera());
+    let server = IndiServer::bind("127.0.0.1:0", cam, IndiConfig::default()).expect("bind");
+    let device = server.device_name();
+    let server = server.spawn().expect("spawn");
+
+    let mut client = TcpStream::connect(server.local_addr()).expect("connect");
+    client
+        .set_read_timeout(Some(Duration::from_secs(30)))
+        .unwrap();
+    // Everything received so far; `wait_for` returns the text from the
+    // start of `start` to the end of `end` and drops what precedes it.
+    let mut received = String::new();
+    let mut reader = client.try_clone().unwrap();
+    let mut wait_for = |start: &str, end: &str| loop {
+        if let Some(from) = received.find(start) {
+            if let Some(to) = received[from..].find(end) {
+                let found = received[from..from + to + end.len()].to_owned();
+                received.drain(..from + to + end.len());
+                break found;
+            }
+        }
+        let mut buf = [0; 64 * 1024];
+        let n = reader.read(&mut buf).expect("read");
+        assert!(n > 0, "server closed");
+        received.push_str(&String::from_utf8_lossy(&buf[..n]));
+    };
+
+    client.write_all(b"<getProperties version='1.7'/>").unwrap();
+    wait_for("<defSwitchVector", "name=\"CONNECTION\"");
+
+    write!(
+        client,
+        "<newSwitchVector device='{device}' name='CONNECTION'>\
+         <oneSwitch name='CONNECT'>On</oneSwitch></newSwitchVector>\
+         <enableBLOB device='{device}'>Also</enableBLOB>"
+    )
+    .unwrap();
+    wait_for("<defNumberVector", "name=\"CCD_EXPOSURE\"");
+
+    write!(
+        client,
+        "<newNumberVector device='{device}' name='CCD_EXPOSURE'>\
+         <oneNumber name='CCD_EXPOSURE_VALUE'>0.1</oneNumber></newNumberVector>"
+    )
+    .unwrap();
+    let blob = wait_for("<setBLOBVector", "</setBLOBVector>");
+    eprintln!("received {} bytes of BLOB XML", blob.len());
+    assert!(blob.contains("format=\".fits\""));
+    // Base64 of "SIMPLE  =".
+    assert!(blob.contains(">U0lNUExFICA9"));
+
+    server.stop().expect("stop");
+}
+
+#[cfg(feature = "alpaca")]
+#[test]
+#[ignore]
+fn alpaca_loopback_exposure() {
+    use std::io::{Read, Write};
+    use std::net::{SocketAddr, TcpStream, UdpSocket};
+    use std::time::{Duration, Instant};
+    use svbony::alpaca::{AlpacaConfig, AlpacaServer};
+
+    // One request per connection; returns the status code and body.
+    fn http(addr: SocketAddr, method: &str, path: &str, extra: &str, body: &str) -> (u16, Vec<u8>) {
+        let mut stream = TcpStream::connect(addr).expect("connect");
+        write!(
+            stream,
+            "{method} {path} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n{extra}\
+             Content-Type: application/x-www-form-urlencoded\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        )
+        .unwrap();
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).unwrap();
+        let split = response.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+        let status = String::from_utf8_lossy(&response[9..12]).parse().unwrap();
+        (status, response[split + 4..].to_vec())
+    }
+    let json = |(status, body): (u16, Vec<u8>)| -> serde_json::Value {
+        assert_eq!(status, 200, "{}", String::from_utf8_lossy(&body));
+        let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(v["ErrorNumber"], 0, "{v}");
+        v
+    };
+
+    let cam = std::sync::Arc::new(open_first_camera());
+    let config = AlpacaConfig {
+        discovery_port: Some(0),
+        ..AlpacaConfig::default()
+    };
+    let server = AlpacaServer::bind("127.0.0.1:0", cam, config).expect("bind");
+    let discovery = server.discovery_addr().expect("discovery enabled");
+    let server = server.spawn().expect("spawn");
+    let addr = server.local_addr();
+
+    let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+    udp.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+    udp.send_to(b"alpacadiscovery1", ("127.0.0.1", discovery.port()))
+        .unwrap();
+    let mut buf = [0; 128];
+    let n = udp.recv(&mut buf).expect("discovery reply");
+    let reply: serde_json::Value = serde_json::from_slice(&buf[..n]).unwrap();
+    assert_eq!(reply["AlpacaPort"], addr.port());
+
+    let devices = json(http(
+        addr,
+        "GET",
+        "/management/v1/configureddevices",
+        "",
+        "",
+    ));
+    assert_eq!(devices["Value"][0]["DeviceType"], "Camera");
+
+    let base = "/api/v1/camera/0";
+    json(http(
+        addr,
+        "PUT",
+        &format!("{base}/connected"),
+        "",
+        "Connected=True&ClientTransactionID=5",
+    ));
+    let width = json(http(addr, "GET", &format!("{base}/numx"), "", ""))["Value"].clone();
+    json(http(
+        addr,
+        "PUT",
+        &format!("{base}/startexposure"),
+        "",
+        "Duration=0.1&Light=True",
+    ));
+    let deadline = Instant::now() + Duration::from_secs(30);
+    while json(http(addr, "GET", &format!("{base}/imageready"), "", ""))["Value"] != true {
+        assert!(Instant::now() < deadline, "image not ready");
+        std::thread::sleep(Duration::from_millis(100));
+    }
+
+    let (status, bytes) = http(
+        addr,
+        "GET",
+        &format!("{base}/imagearray?ClientTransactionID=9"),
+        "Accept: application/imagebytes\r\n",
+        "",
+    );
+    assert_eq!(status, 200);
+    let field = |i: usize| i32::from_le_bytes(bytes[4 * i..4 * i + 4].try_into().unwrap());
+    assert_eq!(field(1), 0, "error number");
+    assert_eq!(field(2), 9, "client transaction");
+    assert_eq!(serde_json::Value::from(field(8)), width);
+    eprintln!("received {}x{} image", field(8), field(9));
+
+    server.stop().expect("stop");
+}
+
 #[test]
 #[ignore]
 fn firmware_upgrade_check() {